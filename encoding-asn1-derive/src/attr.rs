@@ -0,0 +1,665 @@
+use proc_macro_error::{abort, emit_error, ResultExt};
+use quote::{quote, ToTokens, TokenStreamExt};
+use syn::{
+    self,
+    ext::IdentExt,
+    parse::{Parse, ParseStream},
+    punctuated::Punctuated,
+    Ident, LitInt, LitStr, Token,
+};
+
+pub struct QuoteOption<T>(pub Option<T>);
+
+impl<T: ToTokens> ToTokens for QuoteOption<T> {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        tokens.append_all(match self.0 {
+            Some(ref t) => quote! { ::std::option::Option::Some(#t) },
+            None => quote! { ::std::option::Option::None },
+        });
+    }
+}
+
+#[derive(Debug)]
+pub enum Asn1Attr {
+    Explicit(Ident),
+    Implicit(Ident),
+    Tag(Ident, i32),
+    Layer(Ident, Layer),
+    Class(Ident, &'static str),
+    Flatten,
+    Transparent,
+    Set,
+    Null,
+    Skip,
+    With(syn::Path),
+    EncodeWith(syn::Path),
+    DecodeWith(syn::Path),
+    Crate(syn::Path),
+    Size(Ident, Bounds),
+    Range(Ident, Bounds),
+    Alphabet(String),
+    StringType(&'static str),
+}
+
+// Bounds are the inclusive lower and upper bounds of a range constraint, each
+// of which may be left open.
+#[derive(Debug, Clone, Copy)]
+pub struct Bounds(Option<i64>, Option<i64>);
+
+impl Bounds {
+    fn check_not_empty(&self, name: &Ident) {
+        if let Bounds(Some(lower), Some(upper)) = self {
+            if lower > upper {
+                emit_error!(name, "the range {}..={} is empty", lower, upper);
+            }
+        }
+    }
+}
+
+impl Parse for Bounds {
+    fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
+        fn parse_int(input: ParseStream<'_>) -> syn::Result<Option<i64>> {
+            if input.is_empty() {
+                return Ok(None);
+            }
+            let negative = input.parse::<Option<Token![-]>>()?.is_some();
+            let v = input.parse::<LitInt>()?.base10_parse::<i64>()?;
+            Ok(Some(if negative { -v } else { v }))
+        }
+
+        let lower = if input.peek(Token![..]) {
+            None
+        } else {
+            parse_int(input)?
+        };
+        if input.peek(Token![..=]) {
+            input.parse::<Token![..=]>()?;
+            Ok(Bounds(lower, parse_int(input)?))
+        } else if input.peek(Token![..]) {
+            input.parse::<Token![..]>()?;
+            Ok(Bounds(lower, parse_int(input)?.map(|v| v - 1)))
+        } else {
+            // A single value, as in SIZE(16).
+            Ok(Bounds(lower, lower))
+        }
+    }
+}
+
+// Layer is a layer of tagging given as #[asn1(tag(explicit, application, 3))].
+// The tag is IMPLICIT and context-specific unless stated otherwise.
+#[derive(Debug, Clone, Copy)]
+pub struct Layer {
+    pub class: &'static str,
+    pub number: i32,
+    pub explicit: bool,
+}
+
+impl Parse for Layer {
+    fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
+        let mut class = "CLASS_CONTEXT_SPECIFIC";
+        let mut explicit = false;
+        let mut number = None;
+
+        while !input.is_empty() {
+            if input.peek(LitInt) {
+                number = Some(input.parse::<LitInt>()?.base10_parse::<i32>()?);
+            } else {
+                let name: Ident = input.parse()?;
+                match &*name.to_string() {
+                    "explicit" => explicit = true,
+                    "implicit" => explicit = false,
+                    "application" => class = "CLASS_APPLICATION",
+                    "context" => class = "CLASS_CONTEXT_SPECIFIC",
+                    "private" => class = "CLASS_PRIVATE",
+                    s => {
+                        return Err(syn::Error::new(
+                            name.span(),
+                            format!("unexpected tag option: {}", s),
+                        ))
+                    }
+                }
+            }
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+
+        match number {
+            Some(number) => Ok(Layer {
+                class,
+                number,
+                explicit,
+            }),
+            None => Err(input.error("expected a tag number")),
+        }
+    }
+}
+
+impl Parse for Asn1Attr {
+    fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
+        // `crate` is a keyword, so parse any identifier here.
+        let name: Ident = input.call(Ident::parse_any)?;
+        let name_str = name.to_string();
+
+        if input.peek(Token![=]) {
+            let assign_token = input.parse::<Token![=]>()?; // skip '='
+
+            if input.peek(LitInt) {
+                let lit: LitInt = input.parse()?;
+                let lit_int = lit.base10_parse::<i32>()?;
+
+                match &*name_str {
+                    "tag" => Ok(Asn1Attr::Tag(name, lit_int)),
+                    _ => abort!(name, "unexpected attribute: {}", name_str),
+                }
+            } else if input.peek(LitStr) {
+                let lit: LitStr = input.parse()?;
+
+                match &*name_str {
+                    "with" => Ok(Asn1Attr::With(lit.parse()?)),
+                    "encode_with" => Ok(Asn1Attr::EncodeWith(lit.parse()?)),
+                    "decode_with" => Ok(Asn1Attr::DecodeWith(lit.parse()?)),
+                    "crate" => Ok(Asn1Attr::Crate(lit.parse()?)),
+                    "alphabet" => Ok(Asn1Attr::Alphabet(lit.value())),
+                    _ => abort!(name, "unexpected attribute: {}", name_str),
+                }
+            } else {
+                abort!(
+                    assign_token,
+                    "expected `string literal` or `expression` after `=`"
+                );
+            }
+        } else if input.peek(syn::token::Paren) {
+            let content;
+            syn::parenthesized!(content in input);
+
+            match &*name_str {
+                "tag" => Ok(Asn1Attr::Layer(name, content.parse()?)),
+                "size" => Ok(Asn1Attr::Size(name, content.parse()?)),
+                "range" => Ok(Asn1Attr::Range(name, content.parse()?)),
+                _ => abort!(name, "unexpected attribute: {}", name_str),
+            }
+        } else {
+            match name_str.as_ref() {
+                "explicit" => Ok(Asn1Attr::Explicit(name)),
+                "application" => Ok(Asn1Attr::Class(name, "CLASS_APPLICATION")),
+                "private" => Ok(Asn1Attr::Class(name, "CLASS_PRIVATE")),
+                "implicit" => Ok(Asn1Attr::Implicit(name)),
+                "flatten" | "components_of" => Ok(Asn1Attr::Flatten),
+                "transparent" => Ok(Asn1Attr::Transparent),
+                "set" => Ok(Asn1Attr::Set),
+                "null" => Ok(Asn1Attr::Null),
+                "skip" => Ok(Asn1Attr::Skip),
+                "utf8" => Ok(Asn1Attr::StringType("TAG_UTF8_STRING")),
+                "numeric" => Ok(Asn1Attr::StringType("TAG_NUMERIC_STRING")),
+                "printable" => Ok(Asn1Attr::StringType("TAG_PRINTABLE_STRING")),
+                "ia5" => Ok(Asn1Attr::StringType("TAG_IA5_STRING")),
+                _ => abort!(name, "unexpected attribute: {}", name_str),
+            }
+        }
+    }
+}
+
+pub fn parse_attributes(attrs: &[syn::Attribute]) -> Vec<Asn1Attr> {
+    attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("asn1"))
+        .flat_map(|attr| {
+            attr.parse_args_with(Punctuated::<Asn1Attr, Token![,]>::parse_terminated)
+                .unwrap_or_abort()
+        })
+        .collect()
+}
+
+// FieldAttrs is the parsed representation of the asn1 attributes on a
+// structure field or an enum variant.
+#[derive(Default)]
+pub struct FieldAttrs {
+    pub optional: bool,
+    pub explicit: bool,
+    pub class: Option<&'static str>,
+    pub tag: Option<i32>,
+    pub tag_span: Option<proc_macro2::Span>,
+    pub implicit_span: Option<proc_macro2::Span>,
+    pub tags: Vec<Layer>,
+    pub tags_span: Option<proc_macro2::Span>,
+    pub flatten: bool,
+    pub skip: bool,
+    pub encode_with: Option<syn::Path>,
+    pub decode_with: Option<syn::Path>,
+    pub string_type: Option<&'static str>,
+    pub size: Option<Bounds>,
+    pub range: Option<Bounds>,
+    pub alphabet: Option<String>,
+}
+
+impl FieldAttrs {
+    pub fn from_attributes(attrs: &[syn::Attribute]) -> FieldAttrs {
+        let mut ret = FieldAttrs::default();
+        let mut explicit: Option<Ident> = None;
+        let mut implicit: Option<Ident> = None;
+        let mut class_name: Option<Ident> = None;
+
+        for attr in parse_attributes(attrs) {
+            match attr {
+                Asn1Attr::Explicit(name) => {
+                    if implicit.is_some() {
+                        emit_error!(name, "conflicting `explicit` and `implicit` attributes");
+                    }
+                    ret.explicit = true;
+                    explicit = Some(name);
+                }
+                Asn1Attr::Implicit(name) => {
+                    if explicit.is_some() {
+                        emit_error!(name, "conflicting `explicit` and `implicit` attributes");
+                    }
+                    ret.explicit = false;
+                    ret.implicit_span = Some(name.span());
+                    implicit = Some(name);
+                }
+                Asn1Attr::Class(name, class) => {
+                    if ret.class.is_some() {
+                        emit_error!(name, "conflicting `application` and `private` attributes");
+                    }
+                    ret.class = Some(class);
+                    class_name = Some(name);
+                }
+                Asn1Attr::Tag(name, v) => {
+                    ret.tag = Some(v);
+                    ret.tag_span = Some(name.span());
+                }
+                Asn1Attr::Layer(name, layer) => {
+                    if ret.tags.is_empty() {
+                        ret.tag_span = Some(name.span());
+                        ret.tags_span = Some(name.span());
+                    }
+                    ret.tags.push(layer);
+                }
+                Asn1Attr::Flatten => ret.flatten = true,
+                Asn1Attr::Skip => ret.skip = true,
+                Asn1Attr::With(path) => {
+                    ret.encode_with = Some(syn::parse_quote!(#path::marshal_with_params));
+                    ret.decode_with = Some(syn::parse_quote!(#path::unmarshal_with_params));
+                }
+                Asn1Attr::EncodeWith(path) => ret.encode_with = Some(path),
+                Asn1Attr::DecodeWith(path) => ret.decode_with = Some(path),
+                Asn1Attr::Size(name, bounds) => {
+                    if let Bounds(Some(lower), _) = bounds {
+                        if lower < 0 {
+                            emit_error!(name, "SIZE must not be negative");
+                        }
+                    }
+                    bounds.check_not_empty(&name);
+                    ret.size = Some(bounds);
+                }
+                Asn1Attr::Range(name, bounds) => {
+                    bounds.check_not_empty(&name);
+                    ret.range = Some(bounds);
+                }
+                Asn1Attr::Alphabet(alphabet) => ret.alphabet = Some(alphabet),
+                Asn1Attr::StringType(t) => ret.string_type = Some(t),
+                _ => {}
+            }
+        }
+
+        if let (Some(name), None) = (&explicit, ret.tag) {
+            emit_error!(name, "`explicit` requires a `tag`");
+        }
+        if let (Some(name), None) = (&class_name, ret.tag) {
+            emit_error!(name, "`{}` requires a `tag`", name);
+        }
+
+        ret
+    }
+
+    // tag_class returns the name of the constant for the class of tag.
+    pub fn tag_class(&self) -> &'static str {
+        self.class.unwrap_or("CLASS_CONTEXT_SPECIFIC")
+    }
+
+    // from_field parses the asn1 attributes of a structure field, marking
+    // fields of type Option<T> as OPTIONAL.
+    pub fn from_field(field: &syn::Field) -> FieldAttrs {
+        let mut ret = FieldAttrs::from_attributes(&field.attrs);
+        ret.optional = option_inner_type(&field.ty).is_some();
+        ret
+    }
+
+    // params returns an expression building the common::FieldParameters for
+    // this field.
+    pub fn params(&self, krate: &syn::Path) -> proc_macro2::TokenStream {
+        let optional = self.optional;
+        let explicit = self.explicit;
+        let application = self.class == Some("CLASS_APPLICATION");
+        let private = self.class == Some("CLASS_PRIVATE");
+        let tag = QuoteOption(self.tag);
+        let string_type = match self.string_type {
+            Some(t) => {
+                let t = Ident::new(t, proc_macro2::Span::call_site());
+                quote!(#krate::common::#t)
+            }
+            None => quote!(0),
+        };
+        let size = QuoteOption(self.size.map(|Bounds(lower, upper)| {
+            let lower = lower.unwrap_or(0) as usize;
+            let upper = match upper {
+                Some(upper) => {
+                    let upper = upper as usize;
+                    quote!(#upper)
+                }
+                None => quote!(::std::usize::MAX),
+            };
+            quote!(#lower..=#upper)
+        }));
+        let range = QuoteOption(self.range.map(|Bounds(lower, upper)| {
+            let lower = match lower {
+                Some(lower) => quote!(#lower),
+                None => quote!(::std::i64::MIN),
+            };
+            let upper = match upper {
+                Some(upper) => quote!(#upper),
+                None => quote!(::std::i64::MAX),
+            };
+            quote!(#lower..=#upper)
+        }));
+        let alphabet = QuoteOption(self.alphabet.as_ref());
+        let tags = self.tags.iter().map(|layer| {
+            let class = Ident::new(layer.class, proc_macro2::Span::call_site());
+            let number = layer.number;
+            let explicit = layer.explicit;
+            quote! {
+                #krate::common::Tag {
+                    class: #krate::common::#class,
+                    number: #number,
+                    explicit: #explicit,
+                }
+            }
+        });
+
+        quote! {
+            #krate::common::FieldParameters {
+                optional: #optional,
+                explicit: #explicit,
+                application: #application,
+                private: #private,
+                tag: #tag,
+                tags: ::std::vec![#(#tags),*],
+                string_type: #string_type,
+                constraints: #krate::common::Constraints {
+                    size: #size,
+                    range: #range,
+                    alphabet: #alphabet,
+                },
+                ..::std::default::Default::default()
+            }
+        }
+    }
+}
+
+// ContainerAttrs is the parsed representation of the asn1 attributes on the
+// type being derived.
+pub struct ContainerAttrs {
+    pub transparent: bool,
+    pub set: bool,
+    pub null: bool,
+    // the path to the encoding_asn1 crate used in the generated code.
+    pub krate: syn::Path,
+}
+
+impl ContainerAttrs {
+    pub fn from_attributes(attrs: &[syn::Attribute]) -> ContainerAttrs {
+        let mut ret = ContainerAttrs {
+            transparent: false,
+            set: false,
+            null: false,
+            krate: syn::parse_quote!(::encoding_asn1),
+        };
+
+        for attr in parse_attributes(attrs) {
+            match attr {
+                Asn1Attr::Transparent => ret.transparent = true,
+                Asn1Attr::Set => ret.set = true,
+                Asn1Attr::Null => ret.null = true,
+                Asn1Attr::Crate(path) => ret.krate = path,
+                _ => {}
+            }
+        }
+
+        ret
+    }
+}
+
+// add_trait_bounds requires every type parameter to implement the given trait,
+// so that the fields using them can be encoded or decoded.
+pub fn add_trait_bounds(
+    mut generics: syn::Generics,
+    bound: impl Fn(&Ident) -> proc_macro2::TokenStream,
+) -> syn::Generics {
+    let predicates: Vec<syn::WherePredicate> = generics
+        .type_params()
+        .map(|param| {
+            let ident = &param.ident;
+            let bound = bound(ident);
+            syn::parse_quote!(#ident: #bound)
+        })
+        .collect();
+    generics.make_where_clause().predicates.extend(predicates);
+    generics
+}
+
+// unmarshal_generics returns the generics of an implementation of
+// Unmarshaler<'__asn1> for a type with the given generics, as the impl
+// generics, type generics and where clause. '__asn1, the lifetime of the
+// input, outlives every lifetime of the type, so that fields can borrow from
+// the input, and every type parameter must implement Unmarshaler<'__asn1>.
+pub fn unmarshal_generics(
+    generics: &syn::Generics,
+    krate: &syn::Path,
+) -> (
+    proc_macro2::TokenStream,
+    proc_macro2::TokenStream,
+    proc_macro2::TokenStream,
+) {
+    let mut impl_generics =
+        add_trait_bounds(generics.clone(), |_| quote!(#krate::Unmarshaler<'__asn1>));
+    let mut input: syn::LifetimeDef = syn::parse_quote!('__asn1);
+    input
+        .bounds
+        .extend(generics.lifetimes().map(|param| param.lifetime.clone()));
+    impl_generics
+        .params
+        .insert(0, syn::GenericParam::Lifetime(input));
+
+    let (impl_generics, _, where_clause) = impl_generics.split_for_impl();
+    let (_, ty_generics, _) = generics.split_for_impl();
+    (
+        quote!(#impl_generics),
+        quote!(#ty_generics),
+        quote!(#where_clause),
+    )
+}
+
+// transparent_field returns the only field of a structure marked as
+// transparent, together with its member name.
+pub fn transparent_field(
+    name: &syn::Ident,
+    container: &syn::DataStruct,
+) -> (proc_macro2::TokenStream, syn::Type) {
+    let mut fields = container.fields.iter();
+    match (fields.next(), fields.next()) {
+        (Some(field), None) => (member(field, 0), field.ty.clone()),
+        _ => abort!(
+            name,
+            "#[asn1(transparent)] requires a struct with exactly one field"
+        ),
+    }
+}
+
+// option_inner_type returns T if the given type is Option<T>.
+fn option_inner_type(ty: &syn::Type) -> Option<&syn::Type> {
+    let path = match ty {
+        syn::Type::Path(ty) if ty.qself.is_none() => &ty.path,
+        _ => return None,
+    };
+    let segment = path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    match &segment.arguments {
+        syn::PathArguments::AngleBracketed(args) if args.args.len() == 1 => match &args.args[0] {
+            syn::GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// TagId identifies the outermost tag of a field as far as it can be known
+// without type information: either the class and number of its outermost tag,
+// or the type of an untagged field.
+#[derive(PartialEq)]
+enum TagId {
+    Tag(&'static str, i32),
+    Type(String),
+}
+
+fn tag_id(attrs: &FieldAttrs, ty: &syn::Type) -> Option<TagId> {
+    if attrs.flatten || attrs.skip || attrs.decode_with.is_some() {
+        return None;
+    }
+    if let Some(layer) = attrs.tags.first() {
+        return Some(TagId::Tag(layer.class, layer.number));
+    }
+    if let Some(tag) = attrs.tag {
+        return Some(TagId::Tag(attrs.tag_class(), tag));
+    }
+    let ty = option_inner_type(ty).unwrap_or(ty);
+    Some(TagId::Type(ty.to_token_stream().to_string()))
+}
+
+// check_distinct_tags reports OPTIONAL fields whose tag is the same as one of
+// the fields following them, up to and including the next mandatory field.
+// X.680 requires these to be distinct, otherwise the decoder cannot tell which
+// field is present.
+pub fn check_distinct_tags(fields: &syn::Fields) {
+    let fields: Vec<(&syn::Field, FieldAttrs)> = fields
+        .iter()
+        .map(|field| (field, FieldAttrs::from_field(field)))
+        .collect();
+
+    for (i, (field, attrs)) in fields.iter().enumerate() {
+        if !attrs.optional {
+            continue;
+        }
+        let id = match tag_id(attrs, &field.ty) {
+            Some(id) => id,
+            None => continue,
+        };
+
+        for (j, (next, next_attrs)) in fields.iter().enumerate().skip(i + 1) {
+            if tag_id(next_attrs, &next.ty).as_ref() == Some(&id) {
+                let span = attrs
+                    .tag_span
+                    .unwrap_or_else(|| syn::spanned::Spanned::span(*field));
+                emit_error!(
+                    span,
+                    "the tag of OPTIONAL field `{}` is the same as the tag of field `{}`",
+                    field_name(field, i),
+                    field_name(next, j);
+                    note = "X.680 requires the tags of OPTIONAL fields to be distinct from the following fields"
+                );
+                break;
+            }
+            if !next_attrs.optional {
+                break;
+            }
+        }
+    }
+}
+
+// check_set_tags reports members of a SET whose tag is the same as that of
+// another member. X.680 requires the tags of all the members of a SET to be
+// distinct, since they may be encoded in any order.
+pub fn check_set_tags(fields: &syn::Fields) {
+    let fields: Vec<(&syn::Field, FieldAttrs)> = fields
+        .iter()
+        .map(|field| (field, FieldAttrs::from_field(field)))
+        .collect();
+
+    for (i, (field, attrs)) in fields.iter().enumerate() {
+        let id = match tag_id(attrs, &field.ty) {
+            Some(id) => id,
+            None => continue,
+        };
+
+        for (j, (next, next_attrs)) in fields.iter().enumerate().skip(i + 1) {
+            if tag_id(next_attrs, &next.ty).as_ref() == Some(&id) {
+                let span = next_attrs
+                    .tag_span
+                    .unwrap_or_else(|| syn::spanned::Spanned::span(*next));
+                emit_error!(
+                    span,
+                    "the tag of SET member `{}` is the same as the tag of member `{}`",
+                    field_name(next, j),
+                    field_name(field, i);
+                    note = "X.680 requires the tags of the members of a SET to be distinct"
+                );
+            }
+        }
+    }
+}
+
+fn field_name(field: &syn::Field, i: usize) -> String {
+    field
+        .ident
+        .as_ref()
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| i.to_string())
+}
+
+// check_choice_tags reports CHOICE alternatives sharing the same tag, and
+// rejects tagging that an alternative, always tagged EXPLICIT, can't have.
+pub fn check_choice_tags(variants: &[(&syn::Variant, FieldAttrs)]) {
+    for (i, (_, attrs)) in variants.iter().enumerate() {
+        if let Some(span) = attrs.implicit_span {
+            abort!(
+                span,
+                "CHOICE alternatives can't be tagged IMPLICIT";
+                note = "the tag of a CHOICE alternative is always EXPLICIT"
+            );
+        }
+        if let Some(span) = attrs.tags_span {
+            abort!(
+                span,
+                "CHOICE alternatives can't have layered tags";
+                note = "a CHOICE alternative has a single EXPLICIT tag given by #[asn1(tag = ...)]"
+            );
+        }
+        if let (Some(tag), Some(span)) = (attrs.tag, attrs.tag_span) {
+            if variants[..i]
+                .iter()
+                .any(|(_, prev)| prev.tag == Some(tag) && prev.tag_class() == attrs.tag_class())
+            {
+                emit_error!(span, "duplicate CHOICE tag {}", tag);
+            }
+        }
+    }
+}
+
+// member returns the expression naming a field in a structure: its name, or
+// its index in a tuple structure.
+pub fn member(field: &syn::Field, i: usize) -> proc_macro2::TokenStream {
+    let i = syn::Index::from(i);
+    field
+        .ident
+        .as_ref()
+        .map(|name| quote!(#name))
+        .unwrap_or_else(|| quote!(#i))
+}
+
+// check_null reports a structure marked as NULL that has fields.
+pub fn check_null(name: &Ident, container: &syn::DataStruct) {
+    if !container.fields.is_empty() {
+        abort!(name, "#[asn1(null)] requires a unit struct");
+    }
+}
@@ -1,9 +1,10 @@
 extern crate proc_macro;
 
+mod attr;
 mod marshal;
 mod unmarshal;
 
-use proc_macro_error::proc_macro_error;
+use proc_macro_error::{abort, proc_macro_error};
 
 #[proc_macro_derive(Marshal, attributes(asn1))]
 #[proc_macro_error]
@@ -11,9 +12,13 @@ pub fn marshal_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream
     let input: syn::DeriveInput = syn::parse(input).unwrap();
 
     match input.data {
-        syn::Data::Struct(v) => marshal::derive_struct_impl(input.ident, input.generics, v),
-        syn::Data::Enum(v) => marshal::derive_enum_impl(input.ident, input.generics, v),
-        _ => todo!(),
+        syn::Data::Struct(v) => {
+            marshal::derive_struct_impl(input.ident, &input.attrs, input.generics, v)
+        }
+        syn::Data::Enum(v) => {
+            marshal::derive_enum_impl(input.ident, &input.attrs, input.generics, v)
+        }
+        syn::Data::Union(v) => abort!(v.union_token, "unions are not supported"),
     }
     .into()
 }
@@ -24,9 +29,13 @@ pub fn unmarshal_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStre
     let input: syn::DeriveInput = syn::parse(input).unwrap();
 
     match input.data {
-        syn::Data::Struct(v) => unmarshal::derive_struct_impl(input.ident, input.generics, v),
-        syn::Data::Enum(v) => unmarshal::derive_enum_impl(input.ident, input.generics, v),
-        _ => todo!(),
+        syn::Data::Struct(v) => {
+            unmarshal::derive_struct_impl(input.ident, &input.attrs, input.generics, v)
+        }
+        syn::Data::Enum(v) => {
+            unmarshal::derive_enum_impl(input.ident, &input.attrs, input.generics, v)
+        }
+        syn::Data::Union(v) => abort!(v.union_token, "unions are not supported"),
     }
     .into()
 }
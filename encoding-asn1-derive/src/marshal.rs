@@ -1,226 +1,201 @@
-use proc_macro_error::{abort, ResultExt};
-use quote::{quote, ToTokens, TokenStreamExt};
-use syn::{
-    self,
-    parse::{Parse, ParseStream},
-    punctuated::Punctuated,
-    Ident, LitInt, Token,
+use crate::attr::{
+    add_trait_bounds, check_choice_tags, check_distinct_tags, check_null, check_set_tags, member,
+    transparent_field, ContainerAttrs, FieldAttrs,
 };
+use proc_macro_error::abort;
+use quote::quote;
 
-struct QuoteOption<T>(Option<T>);
+pub fn derive_struct_impl(
+    name: syn::Ident,
+    attrs: &[syn::Attribute],
+    generics: syn::Generics,
+    container: syn::DataStruct,
+) -> proc_macro2::TokenStream {
+    let container_attrs = ContainerAttrs::from_attributes(attrs);
+    let krate = &container_attrs.krate;
+    let generics = add_trait_bounds(generics, |_| quote!(#krate::Marshaler));
 
-impl<T: ToTokens> ToTokens for QuoteOption<T> {
-    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
-        tokens.append_all(match self.0 {
-            Some(ref t) => quote! { ::std::option::Option::Some(#t) },
-            None => quote! { ::std::option::Option::None },
-        });
+    if container_attrs.transparent {
+        return derive_transparent_impl(name, krate, generics, container);
+    }
+    if container_attrs.null {
+        check_null(&name, &container);
+        return derive_null_impl(name, krate, generics);
     }
-}
 
-#[derive(Debug)]
-pub enum Asn1Attr {
-    Explicit(Ident),
-    Implicit(Ident),
-    Tag(Ident, i32),
-}
+    match container_attrs.set {
+        true => check_set_tags(&container.fields),
+        false => check_distinct_tags(&container.fields),
+    }
 
-impl Parse for Asn1Attr {
-    fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
-        let name: Ident = input.parse()?;
-        let name_str = name.to_string();
+    let mut list = vec![];
 
-        if input.peek(Token![=]) {
-            let assign_token = input.parse::<Token![=]>()?; // skip '='
+    for (i, field) in container.fields.iter().enumerate() {
+        let attrs = FieldAttrs::from_field(field);
+        let params = attrs.params(krate);
+        let params = quote!(#params.with_rules(params.rules));
+        let field = member(field, i);
+
+        if attrs.skip {
+            continue;
+        } else if let Some(path) = &attrs.encode_with {
+            list.push(quote! {
+                components.push(#path(&self.#field, &#params)?);
+            });
+        } else if attrs.flatten {
+            list.push(quote! {
+                components.push(#krate::Marshaler::marshal_components(&self.#field, &#params)?);
+            });
+        } else {
+            list.push(quote! {
+                components.push(#krate::marshal::marshal_with_params(&self.#field, &#params)?);
+            });
+        }
+    }
 
-            if input.peek(LitInt) {
-                let lit: LitInt = input.parse()?;
-                let lit_int = lit.base10_parse::<i32>()?;
+    let (universal_tag, components) = if container_attrs.set {
+        (
+            quote!(#krate::common::TAG_SET),
+            quote!(#krate::marshal::set_components(components)),
+        )
+    } else {
+        (
+            quote!(#krate::common::TAG_SEQUENCE),
+            quote!(components.concat()),
+        )
+    };
 
-                match &*name_str {
-                    "tag" => Ok(Asn1Attr::Tag(name, lit_int)),
-                    _ => abort!(name, "unexpected attribute: {}", name_str),
-                }
-            } else {
-                abort!(
-                    assign_token,
-                    "expected `string literal` or `expression` after `=`"
-                );
+    let marshal_impl = quote! {
+        let body = #krate::Marshaler::marshal_components(self, params)?;
+        ::std::result::Result::Ok(#krate::marshal::encode_tagged(params, #universal_tag, true, body))
+    };
+
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    quote! {
+        impl #impl_generics #krate::Marshaler for #name #ty_generics #where_clause {
+            fn marshal_with_params(&self, params: &#krate::common::FieldParameters) -> ::std::result::Result<::std::vec::Vec<u8>, #krate::marshal::Error> {
+                #marshal_impl
             }
-        } else {
-            match name_str.as_ref() {
-                "explicit" => Ok(Asn1Attr::Explicit(name)),
-                "implicit" => Ok(Asn1Attr::Implicit(name)),
-                _ => abort!(name, "unexpected attribute: {}", name_str),
+
+            #[allow(unused_variables)]
+            fn marshal_components(&self, params: &#krate::common::FieldParameters) -> ::std::result::Result<::std::vec::Vec<u8>, #krate::marshal::Error> {
+                let mut components: ::std::vec::Vec<::std::vec::Vec<u8>> = ::std::vec::Vec::new();
+                #(#list)*
+                ::std::result::Result::Ok(#components)
             }
         }
     }
 }
 
-fn parse_attributes(attrs: &[syn::Attribute]) -> Vec<Asn1Attr> {
-    attrs
-        .iter()
-        .filter(|attr| attr.path.is_ident("asn1"))
-        .flat_map(|attr| {
-            attr.parse_args_with(Punctuated::<Asn1Attr, Token![,]>::parse_terminated)
-                .unwrap_or_abort()
-        })
-        .collect()
-}
-
-pub fn derive_struct_impl(
+// derive_null_impl encodes a unit structure as NULL.
+fn derive_null_impl(
     name: syn::Ident,
+    krate: &syn::Path,
     generics: syn::Generics,
-    container: syn::DataStruct,
 ) -> proc_macro2::TokenStream {
-    let mut list = vec![];
-
-    for (i, field) in container.fields.iter().enumerate() {
-        let mut explicit = false;
-        let mut tag = QuoteOption(None);
-
-        for attr in parse_attributes(&field.attrs) {
-            match attr {
-                Asn1Attr::Explicit(_) => {
-                    explicit = true;
-                }
-                Asn1Attr::Implicit(_) => {
-                    explicit = false;
-                }
-                Asn1Attr::Tag(_, v) => {
-                    tag = QuoteOption(Some(v));
-                }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    quote! {
+        impl #impl_generics #krate::Marshaler for #name #ty_generics #where_clause {
+            fn marshal_with_params(&self, params: &#krate::common::FieldParameters) -> ::std::result::Result<::std::vec::Vec<u8>, #krate::marshal::Error> {
+                ::std::result::Result::Ok(#krate::marshal::encode_tagged(
+                    params,
+                    #krate::common::TAG_NULL,
+                    false,
+                    ::std::vec::Vec::new(),
+                ))
             }
         }
-
-        let i = syn::Index::from(i);
-        let field = field
-            .ident
-            .as_ref()
-            .map(|name| quote!(#name))
-            .unwrap_or_else(|| quote!(#i));
-
-        list.push(proc_macro2::TokenStream::from(quote! {
-            body.push(self.#field.marshal_with_params(&common::FieldParameters {
-                optional: false,
-                explicit: #explicit,
-                application: false,
-                private: false,
-                default_value: None,
-                tag: #tag,
-                string_type: 0,
-                time_type: 0,
-                set: false,
-                omit_empty: false,
-            }));
-        }));
     }
+}
 
-    let marshal_impl = quote! {
-        let mut body = vec![];
-
-        #(#list)*
+// derive_transparent_impl encodes a single field structure exactly as its
+// field, as in an ASN.1 type assignment such as `Version ::= INTEGER`.
+fn derive_transparent_impl(
+    name: syn::Ident,
+    krate: &syn::Path,
+    generics: syn::Generics,
+    container: syn::DataStruct,
+) -> proc_macro2::TokenStream {
+    let (field, _) = transparent_field(&name, &container);
 
-        let tag_and_length = match params.tag {
-            Some(tag) => {
-                common::TagAndLength {
-                    class: common::CLASS_CONTEXT_SPECIFIC,
-                    tag: tag,
-                    length: body.iter().fold(0, |total_length, item| total_length + item.len()),
-                    is_compound: true,
-                }
-            },
-            None => {
-                common::TagAndLength {
-                    class: 0,
-                    tag: common::TAG_SEQUENCE,
-                    length: body.iter().fold(0, |total_length, item| total_length + item.len()),
-                    is_compound: true,
-                }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    quote! {
+        impl #impl_generics #krate::Marshaler for #name #ty_generics #where_clause {
+            fn marshal_with_params(&self, params: &#krate::common::FieldParameters) -> ::std::result::Result<::std::vec::Vec<u8>, #krate::marshal::Error> {
+                #krate::Marshaler::marshal_with_params(&self.#field, params)
             }
-        };
 
-        let mut v = tag_and_length.encode();
-        body.iter_mut().for_each(|mut i| v.append(&mut i));
-        v
-    };
-
-    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
-    proc_macro2::TokenStream::from(quote! {
-        impl #impl_generics  Marshaler for #name #ty_generics #where_clause {
-            fn marshal_with_params(&self, params: &common::FieldParameters) -> Vec<u8> {
-                #marshal_impl
+            fn marshal_components(&self, params: &#krate::common::FieldParameters) -> ::std::result::Result<::std::vec::Vec<u8>, #krate::marshal::Error> {
+                #krate::Marshaler::marshal_components(&self.#field, params)
             }
         }
-    })
+    }
 }
 
 pub fn derive_enum_impl(
     name: syn::Ident,
+    attrs: &[syn::Attribute],
     generics: syn::Generics,
     container: syn::DataEnum,
 ) -> proc_macro2::TokenStream {
-    let variants = container.variants.iter().map(|v| {
+    let container_attrs = ContainerAttrs::from_attributes(attrs);
+    let krate = &container_attrs.krate;
+    let generics = add_trait_bounds(generics, |_| quote!(#krate::Marshaler));
+
+    let variants: Vec<_> = container
+        .variants
+        .iter()
+        .map(|v| (v, FieldAttrs::from_attributes(&v.attrs)))
+        .collect();
+    check_choice_tags(&variants);
+
+    let variants = variants.iter().map(|(v, attrs)| {
         let ident = &v.ident;
 
         match &v.fields {
-            syn::Fields::Unnamed(_) => {
-                let mut _explicit = false;
-                let mut tag = QuoteOption(None);
-                for attr in parse_attributes(&v.attrs) {
-                    match attr {
-                        Asn1Attr::Explicit(_) => {
-                            _explicit = true;
-                        }
-                        Asn1Attr::Implicit(_) => {
-                            _explicit = false;
-                        }
-                        Asn1Attr::Tag(_, v) => {
-                            tag = QuoteOption(Some(v));
-                        }
-                    }
-                }
+            syn::Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                let tag = match attrs.tag {
+                    Some(tag) => tag,
+                    None => abort!(ident, "CHOICE alternatives require #[asn1(tag = ...)]"),
+                };
+                let class = syn::Ident::new(attrs.tag_class(), proc_macro2::Span::call_site());
+                let encode = match &attrs.encode_with {
+                    Some(path) => quote!(#path),
+                    None => quote!(#krate::Marshaler::marshal_with_params),
+                };
 
                 quote! {
                     #name::#ident(value) => {
-                        let bytes = Marshaler::marshal_with_params(
+                        let bytes = #encode(
                             value,
-                            &common::FieldParameters {
-                                ..common::FieldParameters::default()
-                            },
-                        );
-
-                        let rv = encoding_asn1::types::RawValue {
-                            class: common::CLASS_CONTEXT_SPECIFIC,
-                            tag: #tag.unwrap(),
-                            is_compound: true,
-                            bytes: bytes.to_vec(),
-                            full_bytes: vec![],
+                            &#krate::common::FieldParameters::default().with_rules(params.rules),
+                        )?;
+                        let tag = #krate::common::Tag {
+                            class: #krate::common::#class,
+                            number: #tag,
+                            explicit: true,
                         };
-
-                        Marshaler::marshal_with_params(
-                            &rv,
-                            &common::FieldParameters {
-                                ..common::FieldParameters::default()
-                            },
-                        )
-                     }
+                        ::std::result::Result::Ok(#krate::marshal::wrap_explicit(&[tag], params.rules, bytes))
+                    }
                 }
             }
-            _ => {
-                todo!()
-            }
+            _ => abort!(
+                ident,
+                "CHOICE alternatives must be tuple variants with a single field"
+            ),
         }
     });
 
     let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
-    proc_macro2::TokenStream::from(quote! {
-        impl #impl_generics  Marshaler for #name #ty_generics #where_clause {
-            fn marshal_with_params(&self, params: &common::FieldParameters) -> Vec<u8> {
-                match self {
+    quote! {
+        impl #impl_generics #krate::Marshaler for #name #ty_generics #where_clause {
+            fn marshal_with_params(&self, params: &#krate::common::FieldParameters) -> ::std::result::Result<::std::vec::Vec<u8>, #krate::marshal::Error> {
+                let bytes = match self {
                     #(#variants),*
-                }
+                }?;
+                let (tags, _) = params.as_explicit().split_tags();
+                ::std::result::Result::Ok(#krate::marshal::wrap_explicit(&tags, params.rules, bytes))
             }
         }
-    })
+    }
 }
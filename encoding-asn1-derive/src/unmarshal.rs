@@ -1,200 +1,449 @@
-use proc_macro_error::{abort, ResultExt};
-use quote::{quote, ToTokens, TokenStreamExt};
-use syn::{
-    self,
-    parse::{Parse, ParseStream},
-    punctuated::Punctuated,
-    Ident, LitInt, Token,
+use crate::attr::{
+    check_choice_tags, check_distinct_tags, check_null, check_set_tags, member, transparent_field,
+    unmarshal_generics, ContainerAttrs, FieldAttrs,
 };
+use proc_macro_error::abort;
+use quote::quote;
 
-struct QuoteOption<T>(Option<T>);
+pub fn derive_struct_impl(
+    name: syn::Ident,
+    attrs: &[syn::Attribute],
+    generics: syn::Generics,
+    container: syn::DataStruct,
+) -> proc_macro2::TokenStream {
+    let container_attrs = ContainerAttrs::from_attributes(attrs);
+    let krate = &container_attrs.krate;
 
-impl<T: ToTokens> ToTokens for QuoteOption<T> {
-    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
-        tokens.append_all(match self.0 {
-            Some(ref t) => quote! { ::std::option::Option::Some(#t) },
-            None => quote! { ::std::option::Option::None },
-        });
+    if container_attrs.transparent {
+        return derive_transparent_impl(name, krate, generics, container);
     }
-}
 
-#[derive(Debug)]
-pub enum Asn1Attr {
-    Explicit(Ident),
-    Implicit(Ident),
-    Tag(Ident, i32),
-}
+    if container_attrs.null {
+        check_null(&name, &container);
+        return derive_null_impl(name, krate, generics);
+    }
 
-impl Parse for Asn1Attr {
-    fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
-        let name: Ident = input.parse()?;
-        let name_str = name.to_string();
+    if container_attrs.set {
+        check_set_tags(&container.fields);
+        return derive_set_impl(name, krate, generics, container);
+    }
 
-        if input.peek(Token![=]) {
-            let assign_token = input.parse::<Token![=]>()?; // skip '='
+    check_distinct_tags(&container.fields);
 
-            if input.peek(LitInt) {
-                let lit: LitInt = input.parse()?;
-                let lit_int = lit.base10_parse::<i32>()?;
+    let mut list = vec![];
+    let mut list2 = vec![];
+
+    for (i, field) in container.fields.iter().enumerate() {
+        let attrs = FieldAttrs::from_field(field);
+        let params = attrs.params(krate);
+        let params = quote!(#params.within(params).at(end - bytes.len()));
+        let ty = &field.ty;
+        let var = quote::format_ident!("__field{}", i);
+        let field = member(field, i);
+        let name_str = field.to_string();
+
+        if attrs.skip {
+            list.push(quote! {
+                let #var: #ty = ::std::default::Default::default();
+            });
+        } else if let Some(path) = &attrs.decode_with {
+            list.push(quote! {
+                let (#var, bytes) = #path(bytes, &#params)
+                    .map_err(|e| e.in_field(#name_str))?;
+            });
+        } else if attrs.flatten {
+            list.push(quote! {
+                let (#var, bytes) = <#ty as #krate::Unmarshaler<'__asn1>>::unmarshal_components(bytes, &#params)?;
+            });
+        } else {
+            list.push(quote! {
+                let (#var, bytes) = #krate::unmarshal::unmarshal_component::<#ty>(bytes, &#params)
+                    .map_err(|e| e.in_field(#name_str))?;
+            });
+        }
+        list2.push(quote! {
+            #field: #var,
+        });
+    }
 
-                match &*name_str {
-                    "tag" => Ok(Asn1Attr::Tag(name, lit_int)),
-                    _ => abort!(name, "unexpected attribute: {}", name_str),
+    let (impl_generics, ty_generics, where_clause) = unmarshal_generics(&generics, krate);
+    quote! {
+        impl #impl_generics #krate::Unmarshaler<'__asn1> for #name #ty_generics #where_clause {
+            fn unmarshal_with_params(
+                bytes: &'__asn1 [u8],
+                params: &#krate::common::FieldParameters,
+            ) -> ::std::result::Result<(Self, &'__asn1 [u8]), #krate::unmarshal::Error> {
+                let expected = #krate::common::Identifier::universal(#krate::common::TAG_SEQUENCE, true);
+                let (_, contents, rest, offset) = #krate::unmarshal::parse_tagged(bytes, params, ::std::option::Option::Some(expected), <Self as #krate::Unmarshaler<'__asn1>>::accepts_tag)
+                    .map_err(|e| e.in_type(::std::stringify!(#name)))?;
+                let (ret, trailing) = <Self as #krate::Unmarshaler<'__asn1>>::unmarshal_components(contents, &params.clone().at(offset))
+                    .map_err(|e| e.in_type(::std::stringify!(#name)))?;
+                if !trailing.is_empty() {
+                    return ::std::result::Result::Err(#krate::unmarshal::Error::new(
+                        #krate::unmarshal::ErrorKind::TrailingData,
+                        offset + contents.len() - trailing.len(),
+                    ).in_type(::std::stringify!(#name)));
                 }
-            } else {
-                abort!(
-                    assign_token,
-                    "expected `string literal` or `expression` after `=`"
-                );
+                ::std::result::Result::Ok((ret, rest))
             }
-        } else {
-            match name_str.as_ref() {
-                "explicit" => Ok(Asn1Attr::Explicit(name)),
-                "implicit" => Ok(Asn1Attr::Implicit(name)),
-                _ => abort!(name, "unexpected attribute: {}", name_str),
+
+            #[allow(unused_variables)]
+            fn unmarshal_components(
+                bytes: &'__asn1 [u8],
+                params: &#krate::common::FieldParameters,
+            ) -> ::std::result::Result<(Self, &'__asn1 [u8]), #krate::unmarshal::Error> {
+                // Each field is unmarshaled at its offset in the input.
+                let end = params.offset() + bytes.len();
+                #(#list)*
+                let ret = #name {
+                    #(#list2)*
+                };
+                ::std::result::Result::Ok((ret, bytes))
+            }
+
+            fn accepts_tag(
+                tag_and_length: &#krate::common::TagAndLength,
+                params: &#krate::common::FieldParameters,
+            ) -> bool {
+                params.matches_tag(tag_and_length, #krate::common::TAG_SEQUENCE, true)
             }
         }
     }
 }
 
-fn parse_attributes(attrs: &[syn::Attribute]) -> Vec<Asn1Attr> {
-    attrs
-        .iter()
-        .filter(|attr| attr.path.is_ident("asn1"))
-        .flat_map(|attr| {
-            attr.parse_args_with(Punctuated::<Asn1Attr, Token![,]>::parse_terminated)
-                .unwrap_or_abort()
-        })
-        .collect()
+// derive_null_impl decodes a unit structure from NULL.
+fn derive_null_impl(
+    name: syn::Ident,
+    krate: &syn::Path,
+    generics: syn::Generics,
+) -> proc_macro2::TokenStream {
+    let (impl_generics, ty_generics, where_clause) = unmarshal_generics(&generics, krate);
+    quote! {
+        impl #impl_generics #krate::Unmarshaler<'__asn1> for #name #ty_generics #where_clause {
+            fn unmarshal_with_params(
+                bytes: &'__asn1 [u8],
+                params: &#krate::common::FieldParameters,
+            ) -> ::std::result::Result<(Self, &'__asn1 [u8]), #krate::unmarshal::Error> {
+                let expected = #krate::common::Identifier::universal(#krate::common::TAG_NULL, false);
+                let (_, contents, bytes, offset) = #krate::unmarshal::parse_tagged(bytes, params, ::std::option::Option::Some(expected), <Self as #krate::Unmarshaler<'__asn1>>::accepts_tag)
+                    .map_err(|e| e.in_type(::std::stringify!(#name)))?;
+                if !contents.is_empty() {
+                    return ::std::result::Result::Err(#krate::unmarshal::Error::new(
+                        #krate::unmarshal::ErrorKind::InvalidValue("NULL must have no contents".to_string()),
+                        offset,
+                    ).in_type(::std::stringify!(#name)));
+                }
+                ::std::result::Result::Ok((#name {}, bytes))
+            }
+
+            fn accepts_tag(
+                tag_and_length: &#krate::common::TagAndLength,
+                params: &#krate::common::FieldParameters,
+            ) -> bool {
+                params.matches_tag(tag_and_length, #krate::common::TAG_NULL, false)
+            }
+        }
+    }
 }
 
-pub fn derive_struct_impl(
+// derive_set_impl decodes the members of a SET by dispatching on the tag of
+// each element. Under BER they may appear in any order; DER and CER require
+// the order of their tags.
+fn derive_set_impl(
     name: syn::Ident,
+    krate: &syn::Path,
     generics: syn::Generics,
     container: syn::DataStruct,
 ) -> proc_macro2::TokenStream {
-    let mut list = vec![];
+    let mut decls = vec![];
+    let mut arms = vec![];
     let mut list2 = vec![];
 
     for (i, field) in container.fields.iter().enumerate() {
-        let mut explicit = false;
-        let mut tag = QuoteOption(None);
+        let attrs = FieldAttrs::from_field(field);
+        let params = attrs.params(krate);
+        let params = quote!(#params.within(params).at(end - bytes.len()));
+        let ty = &field.ty;
+        let var = quote::format_ident!("__field{}", i);
+
+        let member = member(field, i);
+        if attrs.skip {
+            list2.push(quote! {
+                #member: ::std::default::Default::default(),
+            });
+            continue;
+        }
+        if attrs.flatten {
+            abort!(field, "COMPONENTS OF is not supported in a SET");
+        }
+
+        let name_str = member.to_string();
+
+        let accepts = match (&attrs.decode_with, attrs.tag) {
+            (None, _) => {
+                quote!(#krate::unmarshal::accepts_tag::<#ty>(&tag_and_length, &#params))
+            }
+            (Some(_), Some(tag)) => {
+                let class = syn::Ident::new(attrs.tag_class(), proc_macro2::Span::call_site());
+                quote!(tag_and_length.class == #krate::common::#class && tag_and_length.tag == #tag)
+            }
+            (Some(_), None) => abort!(
+                field,
+                "fields of a SET with a custom decoder require #[asn1(tag = ...)]"
+            ),
+        };
+        let decode = match &attrs.decode_with {
+            Some(path) => quote!(#path),
+            None => quote!(#krate::unmarshal::unmarshal_component::<#ty>),
+        };
 
-        for attr in parse_attributes(&field.attrs) {
-            match attr {
-                Asn1Attr::Explicit(_) => {
-                    explicit = true;
+        decls.push(quote! {
+            let mut #var: ::std::option::Option<#ty> = ::std::option::Option::None;
+        });
+        arms.push(quote! {
+            if #accepts {
+                if #var.is_some() {
+                    return ::std::result::Result::Err(#krate::unmarshal::Error::new(
+                        #krate::unmarshal::ErrorKind::Structural(::std::format!("duplicate member `{}` in SET", #name_str)),
+                        end - bytes.len(),
+                    ));
                 }
-                Asn1Attr::Implicit(_) => {
-                    explicit = false;
+                let (v, rest) = #decode(bytes, &#params)
+                    .map_err(|e| e.in_field(#name_str))?;
+                #var = ::std::option::Option::Some(v);
+                bytes = rest;
+                continue;
+            }
+        });
+        if attrs.optional {
+            list2.push(quote! {
+                #member: #var.unwrap_or(::std::option::Option::None),
+            });
+        } else {
+            list2.push(quote! {
+                #member: match #var {
+                    ::std::option::Option::Some(v) => v,
+                    ::std::option::Option::None => return ::std::result::Result::Err(#krate::unmarshal::Error::new(
+                        #krate::unmarshal::ErrorKind::Structural(::std::format!("missing member `{}` in SET", #name_str)),
+                        end,
+                    )),
+                },
+            });
+        }
+    }
+
+    let (impl_generics, ty_generics, where_clause) = unmarshal_generics(&generics, krate);
+    quote! {
+        impl #impl_generics #krate::Unmarshaler<'__asn1> for #name #ty_generics #where_clause {
+            fn unmarshal_with_params(
+                bytes: &'__asn1 [u8],
+                params: &#krate::common::FieldParameters,
+            ) -> ::std::result::Result<(Self, &'__asn1 [u8]), #krate::unmarshal::Error> {
+                let expected = #krate::common::Identifier::universal(#krate::common::TAG_SET, true);
+                let (_, body, rest, offset) = #krate::unmarshal::parse_tagged(bytes, params, ::std::option::Option::Some(expected), <Self as #krate::Unmarshaler<'__asn1>>::accepts_tag)
+                    .map_err(|e| e.in_type(::std::stringify!(#name)))?;
+                let (ret, trailing) = <Self as #krate::Unmarshaler<'__asn1>>::unmarshal_components(body, &params.clone().at(offset))
+                    .map_err(|e| e.in_type(::std::stringify!(#name)))?;
+                if !trailing.is_empty() {
+                    return ::std::result::Result::Err(#krate::unmarshal::Error::new(
+                        #krate::unmarshal::ErrorKind::TrailingData,
+                        offset + body.len() - trailing.len(),
+                    ).in_type(::std::stringify!(#name)));
                 }
-                Asn1Attr::Tag(_, v) => {
-                    tag = QuoteOption(Some(v));
+                ::std::result::Result::Ok((ret, rest))
+            }
+
+            #[allow(unused_variables)]
+            fn unmarshal_components(
+                bytes: &'__asn1 [u8],
+                params: &#krate::common::FieldParameters,
+            ) -> ::std::result::Result<(Self, &'__asn1 [u8]), #krate::unmarshal::Error> {
+                // Each member is unmarshaled at its offset in the input.
+                let end = params.offset() + bytes.len();
+                let mut bytes = bytes;
+                let mut previous = ::std::option::Option::None;
+                #(#decls)*
+                while !bytes.is_empty() {
+                    let (tag_and_length, _) = #krate::unmarshal::parse_tag_and_length_with_rules(bytes, params.rules)
+                        .map_err(|e| e.shifted(end - bytes.len()))?;
+                    // DER and CER require the members in the order of their tags.
+                    let tag = (tag_and_length.class, tag_and_length.tag);
+                    if params.rules != #krate::common::EncodingRules::Ber && previous.is_some_and(|previous| tag < previous) {
+                        return ::std::result::Result::Err(#krate::unmarshal::Error::new(
+                            #krate::unmarshal::ErrorKind::NonCanonical("SET ordering"),
+                            end - bytes.len(),
+                        ));
+                    }
+                    previous = ::std::option::Option::Some(tag);
+                    #(#arms)*
+                    return ::std::result::Result::Err(#krate::unmarshal::Error::new(
+                        #krate::unmarshal::ErrorKind::UnexpectedTag {
+                            expected: ::std::option::Option::None,
+                            found: #krate::common::Identifier::from(&tag_and_length),
+                        },
+                        end - bytes.len(),
+                    ));
                 }
+                let ret = #name {
+                    #(#list2)*
+                };
+                ::std::result::Result::Ok((ret, bytes))
             }
-        }
 
-        let i = syn::Index::from(i);
-        let field = field
-            .ident
-            .as_ref()
-            .map(|name| quote!(#name))
-            .unwrap_or_else(|| quote!(#i));
-
-        list.push(proc_macro2::TokenStream::from(quote! {
-            let (#field, bytes) = unmarshal_with_params(bytes, &common::FieldParameters {
-                optional: false,
-                explicit: #explicit,
-                application: false,
-                private: false,
-                default_value: None,
-                tag: #tag,
-                string_type: 0,
-                time_type: 0,
-                set: false,
-                omit_empty: false,
-            })?;
-        }));
-        list2.push(proc_macro2::TokenStream::from(quote! {
-            #field: #field,
-        }));
+            fn accepts_tag(
+                tag_and_length: &#krate::common::TagAndLength,
+                params: &#krate::common::FieldParameters,
+            ) -> bool {
+                params.matches_tag(tag_and_length, #krate::common::TAG_SET, true)
+            }
+        }
     }
+}
 
-    let unmarshal_impl = quote! {
-        let (tag_and_length, bytes) = parse_tag_and_length(bytes)?;
-        #(#list)*
-        let ret = #name {
-            #(#list2)*
-        };
-        Ok((ret, bytes))
-    };
+// derive_transparent_impl decodes a single field structure exactly as its
+// field, as in an ASN.1 type assignment such as `Version ::= INTEGER`.
+fn derive_transparent_impl(
+    name: syn::Ident,
+    krate: &syn::Path,
+    generics: syn::Generics,
+    container: syn::DataStruct,
+) -> proc_macro2::TokenStream {
+    let (field, ty) = transparent_field(&name, &container);
+
+    let (impl_generics, ty_generics, where_clause) = unmarshal_generics(&generics, krate);
+    quote! {
+        impl #impl_generics #krate::Unmarshaler<'__asn1> for #name #ty_generics #where_clause {
+            fn unmarshal_with_params(
+                bytes: &'__asn1 [u8],
+                params: &#krate::common::FieldParameters,
+            ) -> ::std::result::Result<(Self, &'__asn1 [u8]), #krate::unmarshal::Error> {
+                let (v, bytes) = <#ty as #krate::Unmarshaler<'__asn1>>::unmarshal_with_params(bytes, params)?;
+                ::std::result::Result::Ok((#name { #field: v }, bytes))
+            }
 
-    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
-    proc_macro2::TokenStream::from(quote! {
-        impl #impl_generics  Unmarshaler<#name> for #name #ty_generics #where_clause {
-            fn unmarshal_with_params<'a>(bytes: &'a [u8], params: &common::FieldParameters) -> Result<(#name, &'a [u8]), Error> {
-                #unmarshal_impl
+            fn unmarshal_components(
+                bytes: &'__asn1 [u8],
+                params: &#krate::common::FieldParameters,
+            ) -> ::std::result::Result<(Self, &'__asn1 [u8]), #krate::unmarshal::Error> {
+                let (v, bytes) = <#ty as #krate::Unmarshaler<'__asn1>>::unmarshal_components(bytes, params)?;
+                ::std::result::Result::Ok((#name { #field: v }, bytes))
+            }
+
+            fn accepts_tag(
+                tag_and_length: &#krate::common::TagAndLength,
+                params: &#krate::common::FieldParameters,
+            ) -> bool {
+                <#ty as #krate::Unmarshaler<'__asn1>>::accepts_tag(tag_and_length, params)
             }
         }
-    })
+    }
 }
 
 pub fn derive_enum_impl(
     name: syn::Ident,
+    attrs: &[syn::Attribute],
     generics: syn::Generics,
     container: syn::DataEnum,
 ) -> proc_macro2::TokenStream {
-    let variants = container.variants.iter().map(|v| {
+    let container_attrs = ContainerAttrs::from_attributes(attrs);
+    let krate = &container_attrs.krate;
+
+    let variants: Vec<_> = container
+        .variants
+        .iter()
+        .map(|v| (v, FieldAttrs::from_attributes(&v.attrs)))
+        .collect();
+    check_choice_tags(&variants);
+    let tags: Vec<_> = variants
+        .iter()
+        .filter_map(|(_, attrs)| {
+            let class = syn::Ident::new(attrs.tag_class(), proc_macro2::Span::call_site());
+            attrs.tag.map(|tag| quote!((#krate::common::#class, #tag)))
+        })
+        .collect();
+
+    let variants = variants.iter().map(|(v, attrs)| {
         let ident = &v.ident;
 
         match &v.fields {
-            syn::Fields::Unnamed(_) => {
-                let mut _explicit = false;
-                let mut tag = QuoteOption(None);
-                for attr in parse_attributes(&v.attrs) {
-                    match attr {
-                        Asn1Attr::Explicit(_) => {
-                            _explicit = true;
-                        }
-                        Asn1Attr::Implicit(_) => {
-                            _explicit = false;
-                        }
-                        Asn1Attr::Tag(_, v) => {
-                            tag = QuoteOption(Some(v));
-                        }
-                    }
-                }
+            syn::Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                let tag = match attrs.tag {
+                    Some(tag) => tag,
+                    None => abort!(ident, "CHOICE alternatives require #[asn1(tag = ...)]"),
+                };
+                let class = syn::Ident::new(attrs.tag_class(), proc_macro2::Span::call_site());
+                let ty = &fields.unnamed[0].ty;
+                let decode = match &attrs.decode_with {
+                    Some(path) => quote!(#path),
+                    None => quote!(<#ty as #krate::Unmarshaler<'__asn1>>::unmarshal_with_params),
+                };
+
+                let name_str = ident.to_string();
 
                 quote! {
-                    #tag => {
-                        let (r, _) = unmarshal_with_params::<#ident>(
-                            &rv.bytes,
-                            &common::FieldParameters {
-                                ..common::FieldParameters::default()
-                            },
-                        )?;
-                        Ok((#name::#ident(r), bytes))
-                     }
+                    (#krate::common::#class, #tag) => #decode(
+                        contents,
+                        &#krate::common::FieldParameters::default().within(&params).at(offset),
+                    )
+                    .and_then(|(r, trailing)| match trailing.is_empty() {
+                        true => ::std::result::Result::Ok(#name::#ident(r)),
+                        false => ::std::result::Result::Err(#krate::unmarshal::Error::new(
+                            #krate::unmarshal::ErrorKind::TrailingData,
+                            offset + contents.len() - trailing.len(),
+                        )),
+                    })
+                    .map_err(|e| e.in_field(#name_str))
                 }
             }
-            _ => {
-                todo!()
-            }
+            _ => abort!(
+                ident,
+                "CHOICE alternatives must be tuple variants with a single field"
+            ),
         }
     });
 
-    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
-    proc_macro2::TokenStream::from(quote! {
-        impl #impl_generics  Unmarshaler<#name> for #name #ty_generics #where_clause {
-            fn unmarshal_with_params<'a>(bytes: &'a [u8], params: &common::FieldParameters) -> Result<(#name, &'a [u8]), Error> {
-                let (rv, bytes) = unmarshal_with_params::<RawValue>(bytes, params)?;
-               match Some(rv.tag) {
-                   #(#variants),*
-                   _ => {
-                       panic!()
-                   }
+    let tags = tags.iter();
+
+    let (impl_generics, ty_generics, where_clause) = unmarshal_generics(&generics, krate);
+    quote! {
+        impl #impl_generics #krate::Unmarshaler<'__asn1> for #name #ty_generics #where_clause {
+            fn accepts_tag(
+                tag_and_length: &#krate::common::TagAndLength,
+                params: &#krate::common::FieldParameters,
+            ) -> bool {
+                if let ::std::option::Option::Some(tag) = params.as_explicit().tags.first() {
+                    return tag.matches(tag_and_length);
                 }
+                // Every alternative is tagged EXPLICIT, so is constructed.
+                tag_and_length.is_compound
+                    && [#(#tags),*].contains(&(tag_and_length.class, tag_and_length.tag))
+            }
+
+            fn unmarshal_with_params(
+                bytes: &'__asn1 [u8],
+                params: &#krate::common::FieldParameters,
+            ) -> ::std::result::Result<(Self, &'__asn1 [u8]), #krate::unmarshal::Error> {
+                // A CHOICE is always tagged EXPLICIT.
+                let params = params.as_explicit();
+                if !params.tags.is_empty() {
+                    return #krate::unmarshal::unmarshal_component::<Self>(bytes, &params);
+                }
+
+                let (tag_and_length, contents, rest, offset) = #krate::unmarshal::parse_tagged(bytes, &params, ::std::option::Option::None, <Self as #krate::Unmarshaler<'__asn1>>::accepts_tag)
+                    .map_err(|e| e.in_type(::std::stringify!(#name)))?;
+                let ret: ::std::result::Result<Self, #krate::unmarshal::Error> = match (tag_and_length.class, tag_and_length.tag) {
+                    #(#variants,)*
+                    _ => ::std::result::Result::Err(#krate::unmarshal::Error::new(
+                        #krate::unmarshal::ErrorKind::UnexpectedTag {
+                            expected: ::std::option::Option::None,
+                            found: #krate::common::Identifier::from(&tag_and_length),
+                        },
+                        params.offset(),
+                    )),
+                };
+                ret.map(|ret| (ret, rest))
+                    .map_err(|e| e.in_type(::std::stringify!(#name)))
             }
         }
-    })
+    }
 }
@@ -1,4 +1,4 @@
-use encoding_asn1::{common, Encoder, Marshal, Marshaler};
+use encoding_asn1::Marshal;
 
 #[derive(Debug, Marshal)]
 struct Message {
@@ -31,5 +31,5 @@ fn main() {
         id: 1,
         body: Body::Request(Request { num: 1 }),
     };
-    println!("{:02X?}", encoding_asn1::marshal(&m));
+    println!("{:02X?}", encoding_asn1::marshal(&m).unwrap());
 }
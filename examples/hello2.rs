@@ -1,9 +1,7 @@
-use encoding_asn1::unmarshal::Error;
-use encoding_asn1::{
-    common, parse_tag_and_length, unmarshal, unmarshal_with_params, Unmarshal, Unmarshaler,
-};
+use encoding_asn1::{unmarshal, Unmarshal};
 
 #[derive(Debug, Unmarshal)]
+#[allow(dead_code)]
 struct IntStruct {
     a: i32,
 }
@@ -1,8 +1,6 @@
-use encoding_asn1::unmarshal::Error;
-use encoding_asn1::{
-    common, parse_tag_and_length, unmarshal_with_params, Encoder, Marshal, Marshaler, RawValue,
-    Unmarshal, Unmarshaler,
-};
+use encoding_asn1::common;
+use encoding_asn1::unmarshal::{Error, ErrorKind};
+use encoding_asn1::{Marshal, Reader, Unmarshal, Unmarshaler};
 
 #[derive(Debug, Marshal, Unmarshal)]
 struct Message {
@@ -10,7 +8,7 @@ struct Message {
     body: Body,
 }
 
-#[derive(Debug, Marshal, Unmarshal)]
+#[derive(Debug, Marshal)]
 #[allow(dead_code)]
 enum Body {
     #[asn1(tag = 3000)]
@@ -20,37 +18,31 @@ enum Body {
     Response(Response),
 }
 
-/*
-impl Unmarshaler<Body> for Body {
-    fn unmarshal_with_params<'a>(
+// Body is decoded by hand, as a decoder for a structure that the derive can't
+// express would be.
+impl<'a> Unmarshaler<'a> for Body {
+    fn unmarshal_with_params(
         bytes: &'a [u8],
         params: &common::FieldParameters,
     ) -> Result<(Body, &'a [u8]), Error> {
-        println!("bytes: {:02X?}", bytes);
-
-        let (rv, bytes) = unmarshal_with_params::<RawValue>(bytes, params)?;
-        println!("222 bytes: {:02X?}", bytes);
-
-        println!("rv: {:?}", rv);
-
-        match rv.tag {
-            3000 => {
-                println!("aaaa");
-                let (r, _) = unmarshal_with_params::<Request>(
-                    &rv.bytes,
-                    &common::FieldParameters {
-                        ..common::FieldParameters::default()
+        let mut r = Reader::with_params(bytes, params);
+        let body = match r.peek_tag()? {
+            Some(id) if id.tag == 3000 => Body::Request(r.read_explicit(3000, |r| r.read())?),
+            Some(id) if id.tag == 3001 => Body::Response(r.read_explicit(3001, |r| r.read())?),
+            Some(id) => {
+                return Err(Error::new(
+                    ErrorKind::UnexpectedTag {
+                        expected: None,
+                        found: id,
                     },
-                )?;
-                Ok((Body::Request(r), bytes))
-            }
-            _ => {
-                panic!()
+                    params.offset(),
+                ))
             }
-        }
+            None => return Err(Error::new(ErrorKind::Truncated, params.offset())),
+        };
+        Ok((body, r.remaining()))
     }
 }
-*/
 
 #[derive(Debug, Marshal, Unmarshal)]
 struct Request {
@@ -67,7 +59,7 @@ fn main() {
         id: 10,
         body: Body::Request(Request { num: 20 }),
     };
-    let bytes = encoding_asn1::marshal(&m);
+    let bytes = encoding_asn1::marshal(&m).unwrap();
     println!("{:02X?}", bytes);
 
     let n = encoding_asn1::unmarshal::<Message>(&bytes);
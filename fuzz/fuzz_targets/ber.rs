@@ -0,0 +1,14 @@
+#![no_main]
+use encoding_asn1::SequenceOf;
+use libfuzzer_sys::fuzz_target;
+
+// Whatever BER decodes must re-encode as DER that decodes to the same value.
+fuzz_target!(|data: &[u8]| {
+    let _ = encoding_asn1::unmarshal_ber::<encoding_asn1::RawValue>(data);
+    if let Ok((v, _)) = encoding_asn1::unmarshal_ber::<SequenceOf<Vec<u8>>>(data) {
+        let bytes = encoding_asn1::marshal(&v).unwrap();
+        let (w, rest) = encoding_asn1::unmarshal::<SequenceOf<Vec<u8>>>(&bytes).unwrap();
+        assert_eq!(v, w);
+        assert!(rest.is_empty());
+    }
+});
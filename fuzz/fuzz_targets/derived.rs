@@ -0,0 +1,46 @@
+#![no_main]
+use encoding_asn1::{Marshal, SequenceOf, Unmarshal};
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+struct Header {
+    version: i32,
+    #[asn1(tag = 0)]
+    id: Option<i32>,
+}
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+#[asn1(set)]
+struct Flags {
+    #[asn1(tag = 0)]
+    a: i32,
+    #[asn1(tag = 1)]
+    b: Option<Vec<u8>>,
+}
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+enum Body {
+    #[asn1(tag = 0)]
+    Text(String),
+    #[asn1(tag = 1)]
+    Numbers(SequenceOf<i32>),
+}
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+struct Message {
+    #[asn1(components_of)]
+    header: Header,
+    #[asn1(explicit, tag = 1)]
+    flags: Flags,
+    #[asn1(tag(explicit, 2), tag(application, 3))]
+    num: i32,
+    body: Body,
+}
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok((v, _)) = encoding_asn1::unmarshal::<Message>(data) {
+        let bytes = encoding_asn1::marshal(&v).unwrap();
+        let (w, _) = encoding_asn1::unmarshal::<Message>(&bytes).unwrap();
+        assert_eq!(v, w);
+    }
+});
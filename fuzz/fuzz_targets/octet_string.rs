@@ -0,0 +1,11 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok((v, _)) = encoding_asn1::unmarshal::<Vec<u8>>(data) {
+        let bytes = encoding_asn1::marshal(&v).unwrap();
+        let (w, rest) = encoding_asn1::unmarshal::<Vec<u8>>(&bytes).unwrap();
+        assert_eq!(v, w);
+        assert!(rest.is_empty());
+    }
+});
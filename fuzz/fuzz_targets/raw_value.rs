@@ -0,0 +1,10 @@
+#![no_main]
+use encoding_asn1::RawValue;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok((v, rest)) = encoding_asn1::unmarshal::<RawValue>(data) {
+        assert_eq!(v.full_bytes, &data[..data.len() - rest.len()]);
+        assert_eq!(encoding_asn1::marshal(&v).unwrap(), v.full_bytes);
+    }
+});
@@ -0,0 +1,13 @@
+#![no_main]
+use encoding_asn1::{Explicit, SequenceOf};
+use libfuzzer_sys::fuzz_target;
+
+type T = SequenceOf<Explicit<i32, 0>>;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok((v, _)) = encoding_asn1::unmarshal::<T>(data) {
+        let bytes = encoding_asn1::marshal(&v).unwrap();
+        let (w, _) = encoding_asn1::unmarshal::<T>(&bytes).unwrap();
+        assert_eq!(v, w);
+    }
+});
@@ -0,0 +1,9 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok((tag_and_length, rest)) = encoding_asn1::parse_tag_and_length(data) {
+        assert!(rest.len() < data.len());
+        let _ = tag_and_length.length;
+    }
+});
@@ -0,0 +1,15 @@
+#![no_main]
+use encoding_asn1::Value;
+use libfuzzer_sys::fuzz_target;
+
+// Whatever decodes as a Value must re-encode to exactly the same bytes, and
+// display without failing.
+fuzz_target!(|data: &[u8]| {
+    if let Ok((v, rest)) = encoding_asn1::unmarshal_ber::<Value>(data) {
+        assert_eq!(
+            encoding_asn1::marshal(&v).unwrap(),
+            &data[..data.len() - rest.len()]
+        );
+        let _ = v.to_string();
+    }
+});
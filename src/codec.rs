@@ -0,0 +1,126 @@
+use crate::common;
+use crate::marshal;
+use crate::stream;
+use crate::unmarshal;
+use bytes::{BufMut, BytesMut};
+use std::marker::PhantomData;
+
+pub const DEFAULT_MAX_FRAME_LENGTH: usize = 8 * 1024 * 1024;
+
+// Codec frames a stream of messages, each of them a single TLV, for use with
+// tokio_util::codec::Framed. It decodes every frame into a T and encodes any
+// Marshaler, under the encoding rules it was given.
+//
+// A frame longer than the maximum frame length is an error. It is detected
+// from the header, before the contents are buffered.
+#[derive(Debug)]
+pub struct Codec<T> {
+    rules: common::EncodingRules,
+    limits: common::Limits,
+    max_frame_length: usize,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Codec<T> {
+    pub fn new() -> Codec<T> {
+        Codec {
+            rules: common::EncodingRules::default(),
+            limits: common::Limits::default(),
+            max_frame_length: DEFAULT_MAX_FRAME_LENGTH,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn with_rules(self, rules: common::EncodingRules) -> Codec<T> {
+        Codec { rules, ..self }
+    }
+
+    // with_limits sets the limits that frames are unmarshaled under.
+    pub fn with_limits(self, limits: common::Limits) -> Codec<T> {
+        Codec { limits, ..self }
+    }
+
+    pub fn with_max_frame_length(self, max_frame_length: usize) -> Codec<T> {
+        Codec {
+            max_frame_length,
+            ..self
+        }
+    }
+
+    fn check_length(&self, length: usize) -> Result<(), stream::Error> {
+        if length > self.max_frame_length {
+            return Err(stream::Error::FrameTooLarge {
+                length,
+                max: self.max_frame_length,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl<T> Default for Codec<T> {
+    fn default() -> Codec<T> {
+        Codec::new()
+    }
+}
+
+impl<T> Clone for Codec<T> {
+    fn clone(&self) -> Codec<T> {
+        Codec {
+            rules: self.rules,
+            limits: self.limits,
+            max_frame_length: self.max_frame_length,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: unmarshal::UnmarshalerOwned> tokio_util::codec::Decoder for Codec<T> {
+    type Item = T;
+    type Error = stream::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<T>, stream::Error> {
+        match stream::frame_length_with_limits(src, self.rules, &self.limits)
+            .map_err(|e| e.locate(src))?
+        {
+            stream::Status::NeedMore(n) => {
+                self.check_length(src.len() + n)?;
+                src.reserve(n);
+                Ok(None)
+            }
+            stream::Status::Ready(n) => {
+                self.check_length(n)?;
+                let frame = src.split_to(n);
+                Ok(Some(stream::unmarshal_frame(
+                    &frame,
+                    self.rules,
+                    self.limits,
+                )?))
+            }
+        }
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<T>, stream::Error> {
+        match self.decode(src)? {
+            Some(v) => Ok(Some(v)),
+            None if src.is_empty() => Ok(None),
+            None => Err(
+                unmarshal::Error::new(unmarshal::ErrorKind::Truncated, src.len())
+                    .locate(src)
+                    .into(),
+            ),
+        }
+    }
+}
+
+impl<T, M: marshal::Marshaler> tokio_util::codec::Encoder<M> for Codec<T> {
+    type Error = stream::Error;
+
+    fn encode(&mut self, item: M, dst: &mut BytesMut) -> Result<(), stream::Error> {
+        let params = common::FieldParameters::default().with_rules(self.rules);
+        let bytes = item.marshal_with_params(&params)?;
+        self.check_length(bytes.len())?;
+        dst.put_slice(&bytes);
+        Ok(())
+    }
+}
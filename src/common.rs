@@ -1,21 +1,43 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
 pub const TAG_BOOLEAN: i32 = 1;
 pub const TAG_INTEGER: i32 = 2;
 pub const TAG_BIT_STRING: i32 = 3;
 pub const TAG_OCTET_STRING: i32 = 4;
 pub const TAG_NULL: i32 = 5;
 pub const TAG_OID: i32 = 6;
+pub const TAG_OBJECT_DESCRIPTOR: i32 = 7;
+pub const TAG_EXTERNAL: i32 = 8;
+pub const TAG_REAL: i32 = 9;
 pub const TAG_ENUM: i32 = 10;
+pub const TAG_EMBEDDED_PDV: i32 = 11;
 pub const TAG_UTF8_STRING: i32 = 12;
+pub const TAG_RELATIVE_OID: i32 = 13;
+pub const TAG_TIME: i32 = 14;
 pub const TAG_SEQUENCE: i32 = 16;
 pub const TAG_SET: i32 = 17;
 pub const TAG_NUMERIC_STRING: i32 = 18;
 pub const TAG_PRINTABLE_STRING: i32 = 19;
 pub const TAG_T61_STRING: i32 = 20;
+pub const TAG_VIDEOTEX_STRING: i32 = 21;
 pub const TAG_IA5_STRING: i32 = 22;
 pub const TAG_UTCTIME: i32 = 23;
 pub const TAG_GENERALIZED_TIME: i32 = 24;
+pub const TAG_GRAPHIC_STRING: i32 = 25;
+pub const TAG_VISIBLE_STRING: i32 = 26;
 pub const TAG_GENERAL_STRING: i32 = 27;
+pub const TAG_UNIVERSAL_STRING: i32 = 28;
+pub const TAG_CHARACTER_STRING: i32 = 29;
 pub const TAG_BMPSTRING: i32 = 30;
+pub const TAG_DATE: i32 = 31;
+pub const TAG_TIME_OF_DAY: i32 = 32;
+pub const TAG_DATE_TIME: i32 = 33;
+pub const TAG_DURATION: i32 = 34;
+pub const TAG_OID_IRI: i32 = 35;
+pub const TAG_RELATIVE_OID_IRI: i32 = 36;
 
 // ASN.1 class types represent the namespace of the tag.
 pub const CLASS_UNIVERSAL: i32 = 0;
@@ -37,12 +59,21 @@ pub const CLASS_PRIVATE: i32 = 3;
 //
 // (This is used in order to remove ambiguity with optional elements.)
 //
-// You can layer EXPLICIT and IMPLICIT tags to an arbitrary depth, however we
-// don't support that here. We support a single layer of EXPLICIT or IMPLICIT
-// tagging with tag strings on the fields of a structure.
+// You can layer EXPLICIT and IMPLICIT tags to an arbitrary depth, as in
+// [0] EXPLICIT [APPLICATION 3] IMPLICIT OCTET STRING. The innermost layer is
+// given by tag and explicit, and any further layers around it by tags,
+// outermost first.
+
+// Tag is a layer of EXPLICIT or IMPLICIT tagging.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tag {
+    pub class: i32,     // the class of the tag, other than UNIVERSAL.
+    pub number: i32,    // the tag number.
+    pub explicit: bool, // true iff the tag is EXPLICIT.
+}
 
 // FieldParameters is the parsed representation of tag string from a structure field.
-#[derive(Debug)]
+#[derive(Debug, Default, Clone)]
 pub struct FieldParameters {
     pub optional: bool,             // true iff the field is OPTIONAL
     pub explicit: bool,             // true iff an EXPLICIT tag is in use.
@@ -50,30 +81,340 @@ pub struct FieldParameters {
     pub private: bool,              // true iff a PRIVATE tag is in use.
     pub default_value: Option<i64>, // a default value for INTEGER typed fields (maybe nil).
     pub tag: Option<i32>,           // the EXPLICIT or IMPLICIT tag (maybe nil).
+    pub tags: Vec<Tag>,             // the layers of tagging around tag, outermost first.
     pub string_type: i32,           // the string tag to use when marshaling.
     pub time_type: i32,             // the time tag to use when marshaling.
     pub set: bool,                  // true iff this should be encoded as a SET
     pub omit_empty: bool,           // true iff this should be omitted if empty when marshaling.
+    pub constraints: Constraints,   // the subtype constraints on the value.
+    pub rules: EncodingRules,       // the encoding rules in use.
+    pub limits: Limits,             // the resource limits on unmarshaling.
+    pub depth: usize,               // the nesting depth of the value being unmarshaled.
+    pub state: DecodeState,         // the state of the unmarshaling in progress.
 
                                     // Invariants:
                                     //   if explicit is set, tag is non-nil.
 }
 
-impl Default for FieldParameters {
-    fn default() -> FieldParameters {
+// EncodingRules selects the encoding rules in use. DER, the default, rejects
+// anything that is not the single canonical encoding of a value, as is needed
+// when verifying signatures. BER also accepts the other encodings that the
+// Basic Encoding Rules allow, such as non-minimal lengths, any non-zero BOOLEAN
+// as TRUE and SET OF in any order; values are still marshaled as DER. CER is
+// the other canonical subset of BER, in which constructed values have
+// indefinite lengths and long strings are segmented, so that a value can be
+// encoded without knowing its length up front.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum EncodingRules {
+    #[default]
+    Der,
+    Ber,
+    Cer,
+}
+
+// Limits bound the resources that unmarshaling may use, as a guard against
+// hostile input: how deeply values may nest, how long the contents of a single
+// element may be, how many elements a SEQUENCE OF or SET OF may have and how
+// many bytes may be allocated for the decoded value in total. By default only
+// the nesting depth is limited, since the derived decoders recurse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    pub max_depth: usize,          // the deepest nesting of values.
+    pub max_element_length: usize, // the longest contents of an element.
+    pub max_elements: usize,       // the most elements of a SEQUENCE OF or SET OF.
+    pub max_total_bytes: usize,    // the most bytes allocated for a decoded value.
+}
+
+pub const DEFAULT_MAX_DEPTH: usize = 128;
+
+// DecodeState is the state of a single unmarshaling, which is carried down to
+// the components of the value being unmarshaled: where in the input they are,
+// and the bytes allocated for the value so far, if they are limited. Every
+// unmarshal function starts it afresh, so parameters can be reused from one
+// value to the next and the limits hold for each value on its own.
+#[derive(Debug, Default, Clone)]
+pub struct DecodeState {
+    offset: usize, // the offset in the input of the bytes being unmarshaled.
+    allocated: Option<Arc<AtomicUsize>>, // the bytes allocated so far (maybe nil).
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_element_length: usize::MAX,
+            max_elements: usize::MAX,
+            max_total_bytes: usize::MAX,
+        }
+    }
+}
+
+// Constraints are the subtype constraints of a field, such as INTEGER (0..255),
+// OCTET STRING (SIZE(16)) or IA5String (FROM("0".."9")). They are checked when
+// marshaling and unmarshaling, and are available to encodings that make use of
+// them.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Constraints {
+    pub size: Option<RangeInclusive<usize>>, // the permitted number of elements (maybe nil).
+    pub range: Option<RangeInclusive<i64>>,  // the permitted values of an INTEGER (maybe nil).
+    pub alphabet: Option<&'static str>,      // the permitted characters of a string (maybe nil).
+}
+
+impl Constraints {
+    // check_size checks the length of a string or the number of elements in a
+    // collection against the SIZE constraint.
+    pub fn check_size(&self, size: usize) -> Result<(), String> {
+        match &self.size {
+            Some(r) if !r.contains(&size) => Err(format!(
+                "size {} is outside of SIZE({}..{})",
+                size,
+                r.start(),
+                r.end()
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    // check_range checks an INTEGER against the value range constraint.
+    pub fn check_range(&self, value: i64) -> Result<(), String> {
+        match &self.range {
+            Some(r) if !r.contains(&value) => Err(format!(
+                "value {} is outside of ({}..{})",
+                value,
+                r.start(),
+                r.end()
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    // check_alphabet checks that a string only contains permitted characters.
+    pub fn check_alphabet(&self, s: &str) -> Result<(), String> {
+        if let Some(alphabet) = self.alphabet {
+            if let Some(c) = s.chars().find(|c| !alphabet.contains(*c)) {
+                return Err(format!("character {:?} is not in FROM({:?})", c, alphabet));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FieldParameters {
+    // tag_class returns the class of the EXPLICIT or IMPLICIT tag.
+    pub fn tag_class(&self) -> i32 {
+        if self.application {
+            CLASS_APPLICATION
+        } else if self.private {
+            CLASS_PRIVATE
+        } else {
+            CLASS_CONTEXT_SPECIFIC
+        }
+    }
+
+    // with_rules returns params with the given encoding rules. This is how the
+    // rules are passed down to the components of a value.
+    pub fn with_rules(self, rules: EncodingRules) -> FieldParameters {
+        FieldParameters { rules, ..self }
+    }
+
+    // with_limits returns params with the given resource limits.
+    pub fn with_limits(self, limits: Limits) -> FieldParameters {
+        FieldParameters { limits, ..self }
+    }
+
+    // start returns params for a new unmarshaling, in which the bytes
+    // allocated are counted from zero. It is called by the unmarshal
+    // functions, whatever state params carry.
+    pub(crate) fn start(&self) -> FieldParameters {
+        let allocated = match self.limits.max_total_bytes {
+            usize::MAX => None,
+            _ => Some(Arc::new(AtomicUsize::new(0))),
+        };
+        FieldParameters {
+            state: DecodeState {
+                offset: 0,
+                allocated,
+            },
+            ..self.clone()
+        }
+    }
+
+    // offset returns the offset in the input of the bytes being unmarshaled
+    // under params. Errors are reported at offsets from it.
+    pub fn offset(&self) -> usize {
+        self.state.offset
+    }
+
+    // at returns params for bytes found at the given offset in the input. A
+    // decoder gives each component it unmarshals the offset of the component.
+    pub fn at(mut self, offset: usize) -> FieldParameters {
+        self.state.offset = offset;
+        self
+    }
+
+    // within returns params for a component of the value that parent is for.
+    // The encoding rules, limits and state of the unmarshaling carry over, and
+    // the component is nested one level deeper.
+    pub fn within(self, parent: &FieldParameters) -> FieldParameters {
+        FieldParameters {
+            rules: parent.rules,
+            limits: parent.limits,
+            depth: parent.depth + 1,
+            state: parent.state.clone(),
+            ..self
+        }
+    }
+
+    // charge accounts for n bytes allocated for the decoded value, and checks
+    // the total against the limit.
+    pub fn charge(&self, n: usize) -> Result<(), String> {
+        let max = self.limits.max_total_bytes;
+        if max == usize::MAX {
+            return Ok(());
+        }
+        let total = match &self.state.allocated {
+            Some(allocated) => allocated.fetch_add(n, Ordering::Relaxed).saturating_add(n),
+            None => n,
+        };
+        if total > max {
+            return Err(format!(
+                "{} bytes allocated exceeds the limit of {}",
+                total, max
+            ));
+        }
+        Ok(())
+    }
+
+    // layers returns every layer of tagging, outermost first.
+    pub fn layers(&self) -> Vec<Tag> {
+        let mut layers = self.tags.clone();
+        if let Some(number) = self.tag {
+            layers.push(Tag {
+                class: self.tag_class(),
+                number,
+                explicit: self.explicit,
+            });
+        }
+        layers
+    }
+
+    // split_tags reduces the layers of tagging to the EXPLICIT tags wrapping
+    // the value, outermost first, and the parameters for the value itself,
+    // which carry at most a single IMPLICIT tag. An IMPLICIT tag replaces the
+    // tag of the layer below it, so [0] IMPLICIT [1] EXPLICIT INTEGER is
+    // encoded as [0] EXPLICIT INTEGER.
+    pub fn split_tags(&self) -> (Vec<Tag>, FieldParameters) {
+        let mut explicit = vec![];
+        let mut implicit: Option<Tag> = None;
+        for layer in self.layers() {
+            let layer = match implicit.take() {
+                Some(outer) => Tag {
+                    explicit: layer.explicit,
+                    ..outer
+                },
+                None => layer,
+            };
+            if layer.explicit {
+                explicit.push(layer);
+            } else {
+                implicit = Some(layer);
+            }
+        }
+
+        let params = FieldParameters {
+            explicit: false,
+            application: implicit.is_some_and(|t| t.class == CLASS_APPLICATION),
+            private: implicit.is_some_and(|t| t.class == CLASS_PRIVATE),
+            tag: implicit.map(|t| t.number),
+            tags: vec![],
+            ..self.clone()
+        };
+        (explicit, params)
+    }
+
+    // as_explicit returns params with every layer of tagging made EXPLICIT.
+    // This is how a CHOICE is tagged, since it has no tag of its own to replace
+    // (X.680 31.2.7).
+    pub fn as_explicit(&self) -> FieldParameters {
+        let tags = self
+            .layers()
+            .into_iter()
+            .map(|tag| Tag {
+                explicit: true,
+                ..tag
+            })
+            .collect();
         FieldParameters {
-            optional: false,
             explicit: false,
             application: false,
             private: false,
-            default_value: None,
             tag: None,
-            string_type: 0,
-            time_type: 0,
-            set: false,
-            omit_empty: false,
+            tags,
+            ..self.clone()
         }
     }
+
+    // matches_tag reports whether the given tag is the one expected for a
+    // value whose type has the given UNIVERSAL tag and form, taking the
+    // tagging in params into account. Under an EXPLICIT tag, only the
+    // outermost tag is checked.
+    pub fn matches_tag(
+        &self,
+        tag_and_length: &TagAndLength,
+        universal_tag: i32,
+        is_compound: bool,
+    ) -> bool {
+        if self.explicit || !self.tags.is_empty() {
+            let (tags, params) = self.split_tags();
+            match tags.first() {
+                Some(tag) => return tag.matches(tag_and_length),
+                None => return params.matches_tag(tag_and_length, universal_tag, is_compound),
+            }
+        }
+
+        tag_and_length.is_compound == is_compound
+            && match self.tag {
+                Some(tag) => tag_and_length.class == self.tag_class() && tag_and_length.tag == tag,
+                None => {
+                    tag_and_length.class == CLASS_UNIVERSAL && tag_and_length.tag == universal_tag
+                }
+            }
+    }
+
+    // matches_string_tag is matches_tag for a string type, which BER and CER
+    // also permit to be constructed from segments.
+    pub fn matches_string_tag(&self, tag_and_length: &TagAndLength, universal_tag: i32) -> bool {
+        self.matches_tag(tag_and_length, universal_tag, false)
+            || (self.rules != EncodingRules::Der
+                && self.matches_tag(tag_and_length, universal_tag, true))
+    }
+}
+
+impl Tag {
+    // explicit returns the context-specific EXPLICIT tag [number].
+    pub fn explicit(number: i32) -> Tag {
+        Tag {
+            class: CLASS_CONTEXT_SPECIFIC,
+            number,
+            explicit: true,
+        }
+    }
+
+    // implicit returns the context-specific IMPLICIT tag [number].
+    pub fn implicit(number: i32) -> Tag {
+        Tag {
+            class: CLASS_CONTEXT_SPECIFIC,
+            number,
+            explicit: false,
+        }
+    }
+
+    // matches reports whether the given tag is this EXPLICIT tag.
+    pub fn matches(&self, tag_and_length: &TagAndLength) -> bool {
+        tag_and_length.is_compound
+            && tag_and_length.class == self.class
+            && tag_and_length.tag == self.number
+    }
 }
 
 #[derive(Debug, Default, PartialEq)]
@@ -82,4 +423,52 @@ pub struct TagAndLength {
     pub tag: i32,
     pub length: usize,
     pub is_compound: bool,
+    pub indefinite: bool, // true iff the contents end with end-of-contents octets (BER).
+}
+
+// Identifier is the class, number and form of a tag, as found in the
+// identifier octets of an encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Identifier {
+    pub class: i32,
+    pub tag: i32,
+    pub is_compound: bool,
+}
+
+impl Identifier {
+    // universal returns the identifier of an untagged value of a type with the
+    // given UNIVERSAL tag and form.
+    pub fn universal(tag: i32, is_compound: bool) -> Identifier {
+        Identifier {
+            class: CLASS_UNIVERSAL,
+            tag,
+            is_compound,
+        }
+    }
+}
+
+impl From<&TagAndLength> for Identifier {
+    fn from(tag_and_length: &TagAndLength) -> Identifier {
+        Identifier {
+            class: tag_and_length.class,
+            tag: tag_and_length.tag,
+            is_compound: tag_and_length.is_compound,
+        }
+    }
+}
+
+// Identifiers are written in ASN.1 notation, such as [APPLICATION 3].
+impl fmt::Display for Identifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.class {
+            CLASS_UNIVERSAL => write!(f, "[UNIVERSAL {}]", self.tag)?,
+            CLASS_APPLICATION => write!(f, "[APPLICATION {}]", self.tag)?,
+            CLASS_PRIVATE => write!(f, "[PRIVATE {}]", self.tag)?,
+            _ => write!(f, "[{}]", self.tag)?,
+        }
+        if self.is_compound {
+            write!(f, " constructed")?;
+        }
+        Ok(())
+    }
 }
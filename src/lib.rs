@@ -1,16 +1,42 @@
+// Lets the derived code refer to ::encoding_asn1 from within this crate too.
+extern crate self as encoding_asn1;
+
+#[cfg(feature = "codec")]
+pub mod codec;
 pub mod common;
 pub mod marshal;
+pub mod reader;
+pub mod stream;
 pub mod types;
 pub mod unmarshal;
+pub mod writer;
 pub use common::TagAndLength;
 pub use encoding_asn1_derive::Marshal;
 pub use encoding_asn1_derive::Unmarshal;
 pub use marshal::marshal;
+pub use marshal::marshal_cer;
 pub use marshal::Encoder;
 pub use marshal::Marshaler;
+pub use reader::Reader;
+pub use types::Application;
+pub use types::BitString;
+pub use types::BitStringRef;
+pub use types::Explicit;
+pub use types::Implicit;
+pub use types::ObjectIdentifierRef;
 pub use types::RawValue;
+pub use types::RawValueRef;
+pub use types::SequenceOf;
+pub use types::SetOf;
+pub use types::Value;
 pub use unmarshal::parse_int32;
 pub use unmarshal::parse_tag_and_length;
 pub use unmarshal::unmarshal;
+pub use unmarshal::unmarshal_ber;
+pub use unmarshal::unmarshal_cer;
+pub use unmarshal::unmarshal_exact;
+pub use unmarshal::unmarshal_many;
 pub use unmarshal::unmarshal_with_params;
 pub use unmarshal::Unmarshaler;
+pub use unmarshal::UnmarshalerOwned;
+pub use writer::Writer;
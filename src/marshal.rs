@@ -1,6 +1,19 @@
 use crate::common;
+use crate::unmarshal;
 pub use encoding_asn1_derive::Marshal;
+use std::borrow::Cow;
+use thiserror::Error;
 
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("constraint violation: {0}")]
+    ConstraintViolation(String),
+
+    #[error("structural error: {0}")]
+    Structural(String),
+}
+
+#[allow(clippy::len_without_is_empty)]
 pub trait Encoder {
     fn len(&self) -> usize {
         self.encode().len()
@@ -36,7 +49,7 @@ fn base128_int_length(mut n: i64) -> i32 {
         n >>= 7;
     }
 
-    return l;
+    l
 }
 
 fn encode_int_using_base128(n: i64) -> Vec<u8> {
@@ -94,7 +107,9 @@ impl Encoder for common::TagAndLength {
             v.push(b);
         }
 
-        if self.length >= 128 {
+        if self.indefinite {
+            v.push(0x80);
+        } else if self.length >= 128 {
             let l = length_length(self.length as i32);
             v.push(0x80 | l as u8);
             v.append(&mut encode_length(self.length as i32));
@@ -121,7 +136,7 @@ impl Encoder for i32 {
             i >>= 8;
         }
 
-        return n;
+        n
     }
 
     fn encode(&self) -> Vec<u8> {
@@ -148,89 +163,285 @@ impl Encoder for Vec<u8> {
 }
 
 pub trait Marshaler {
-    fn marshal(&self) -> Vec<u8> {
+    fn marshal(&self) -> Result<Vec<u8>, Error> {
         self.marshal_with_params(&common::FieldParameters::default())
     }
-    fn marshal_with_params(&self, params: &common::FieldParameters) -> Vec<u8>;
+    fn marshal_with_params(&self, params: &common::FieldParameters) -> Result<Vec<u8>, Error>;
+
+    // marshal_components returns the encodings of the members of a SEQUENCE
+    // type, without an outer tag and length. This is what COMPONENTS OF
+    // inlines into the enclosing SEQUENCE, so it is an error for any other
+    // type, as it is when unmarshaling.
+    fn marshal_components(&self, _params: &common::FieldParameters) -> Result<Vec<u8>, Error> {
+        Err(Error::Structural(
+            "COMPONENTS OF requires a SEQUENCE type".to_string(),
+        ))
+    }
+}
+
+impl Marshaler for bool {
+    fn marshal_with_params(&self, params: &common::FieldParameters) -> Result<Vec<u8>, Error> {
+        let body = match self {
+            true => vec![0xff],
+            false => vec![0x00],
+        };
+        Ok(encode_tagged(params, common::TAG_BOOLEAN, false, body))
+    }
 }
 
 impl Marshaler for i32 {
-    fn marshal_with_params(&self, params: &common::FieldParameters) -> Vec<u8> {
-        let mut class = common::CLASS_UNIVERSAL;
-        let mut tag = common::TAG_INTEGER;
-        if let Some(v) = params.tag {
-            if params.application {
-                class = common::CLASS_APPLICATION;
-            } else if params.private {
-                class = common::CLASS_PRIVATE
-            } else {
-                class = common::CLASS_CONTEXT_SPECIFIC
-            }
+    fn marshal_with_params(&self, params: &common::FieldParameters) -> Result<Vec<u8>, Error> {
+        params
+            .constraints
+            .check_range(*self as i64)
+            .map_err(Error::ConstraintViolation)?;
+
+        Ok(encode_tagged(
+            params,
+            common::TAG_INTEGER,
+            false,
+            self.encode(),
+        ))
+    }
+}
 
-            if params.explicit {
-                let mut t = TaggedEncoder {
-                    tag: common::TagAndLength {
-                        class: class,
-                        is_compound: true,
-                        length: 0,
-                        tag: v,
-                    },
-                    body: TaggedEncoder {
-                        tag: common::TagAndLength {
-                            class: 0,
-                            is_compound: false,
-                            length: self.len(),
-                            tag: common::TAG_INTEGER,
-                        },
-                        body: self.encode(),
-                    },
-                };
-
-                t.tag.length = t.body.len();
-
-                return t.encode();
-            }
+impl Marshaler for [u8] {
+    fn marshal_with_params(&self, params: &common::FieldParameters) -> Result<Vec<u8>, Error> {
+        params
+            .constraints
+            .check_size(self.len())
+            .map_err(Error::ConstraintViolation)?;
+
+        Ok(encode_string(
+            params,
+            common::TAG_OCTET_STRING,
+            self.to_vec(),
+        ))
+    }
+}
 
-            // implicit tag.
-            tag = v;
-        }
+impl Marshaler for Vec<u8> {
+    fn marshal_with_params(&self, params: &common::FieldParameters) -> Result<Vec<u8>, Error> {
+        self.as_slice().marshal_with_params(params)
+    }
+}
 
-        let t = TaggedEncoder {
-            tag: common::TagAndLength {
-                class: class,
-                is_compound: false,
-                length: self.len(),
-                tag: tag,
-            },
-            body: self.encode(),
+// str is marshaled as a UTF8String, unless params.string_type selects another
+// character string type.
+impl Marshaler for str {
+    fn marshal_with_params(&self, params: &common::FieldParameters) -> Result<Vec<u8>, Error> {
+        params
+            .constraints
+            .check_size(self.chars().count())
+            .and_then(|_| params.constraints.check_alphabet(self))
+            .map_err(Error::ConstraintViolation)?;
+
+        let tag = match params.string_type {
+            0 => common::TAG_UTF8_STRING,
+            t => {
+                if t != common::TAG_UTF8_STRING && !self.is_ascii() {
+                    return Err(Error::ConstraintViolation(format!(
+                        "{:?} contains characters not permitted in string type {}",
+                        self, t
+                    )));
+                }
+                t
+            }
         };
 
-        t.encode()
+        Ok(encode_string(params, tag, self.as_bytes().to_vec()))
     }
 }
 
-impl Marshaler for Vec<u8> {
-    fn marshal_with_params(&self, _params: &common::FieldParameters) -> Vec<u8> {
-        let t = TaggedEncoder {
-            tag: common::TagAndLength {
-                class: 0,
-                is_compound: false,
-                length: self.len(),
-                tag: common::TAG_OCTET_STRING,
-            },
-            body: self.encode(),
-        };
+impl Marshaler for String {
+    fn marshal_with_params(&self, params: &common::FieldParameters) -> Result<Vec<u8>, Error> {
+        self.as_str().marshal_with_params(params)
+    }
+}
+
+// Borrowed values, as decoded by the borrowing Unmarshaler impls, are
+// marshaled as the values they point to.
+impl<T: Marshaler + ?Sized> Marshaler for &T {
+    fn marshal_with_params(&self, params: &common::FieldParameters) -> Result<Vec<u8>, Error> {
+        (**self).marshal_with_params(params)
+    }
 
-        t.encode()
+    fn marshal_components(&self, params: &common::FieldParameters) -> Result<Vec<u8>, Error> {
+        (**self).marshal_components(params)
     }
 }
 
-pub fn marshal<M: Marshaler>(m: &M) -> Vec<u8> {
+impl<T: Marshaler + ToOwned + ?Sized> Marshaler for Cow<'_, T> {
+    fn marshal_with_params(&self, params: &common::FieldParameters) -> Result<Vec<u8>, Error> {
+        (**self).marshal_with_params(params)
+    }
+
+    fn marshal_components(&self, params: &common::FieldParameters) -> Result<Vec<u8>, Error> {
+        (**self).marshal_components(params)
+    }
+}
+
+// An absent OPTIONAL value is simply left out of the encoding.
+impl<T: Marshaler> Marshaler for Option<T> {
+    fn marshal_with_params(&self, params: &common::FieldParameters) -> Result<Vec<u8>, Error> {
+        match self {
+            Some(v) => v.marshal_with_params(params),
+            None => Ok(vec![]),
+        }
+    }
+}
+
+// set_components concatenates the encodings of the components of a SET in the
+// canonical order of their tags, as DER requires (X.690 10.3): UNIVERSAL,
+// APPLICATION, context-specific and PRIVATE classes in turn, each in ascending
+// order of tag number. A component may hold several encodings, as COMPONENTS
+// OF does, which are ordered individually.
+pub fn set_components(components: Vec<Vec<u8>>) -> Vec<u8> {
+    let mut elements = vec![];
+    for component in &components {
+        let mut bytes = &component[..];
+        while !bytes.is_empty() {
+            match unmarshal::parse_header(bytes, common::EncodingRules::Ber) {
+                Ok((t, _, rest, _)) => {
+                    let end = bytes.len() - rest.len();
+                    elements.push(((t.class, t.tag), &bytes[..end]));
+                    bytes = rest;
+                }
+                _ => {
+                    elements.push(((i32::MAX, i32::MAX), bytes));
+                    break;
+                }
+            }
+        }
+    }
+    elements.sort_by_key(|(tag, _)| *tag);
+    elements
+        .iter()
+        .flat_map(|(_, e)| e.iter().copied())
+        .collect()
+}
+
+pub fn marshal<M: Marshaler>(m: &M) -> Result<Vec<u8>, Error> {
     m.marshal()
 }
 
-pub fn marshal_with_params<M: Marshaler>(m: &M, params: &common::FieldParameters) -> Vec<u8> {
-    m.marshal_with_params(params)
+// marshal_cer is marshal under CER rather than DER, for values too large to
+// buffer while their lengths are computed.
+pub fn marshal_cer<M: Marshaler>(m: &M) -> Result<Vec<u8>, Error> {
+    let params = common::FieldParameters {
+        rules: common::EncodingRules::Cer,
+        ..common::FieldParameters::default()
+    };
+    marshal_with_params(m, &params)
+}
+
+// marshal_with_params marshals m under params, wrapping it in any EXPLICIT
+// tags. This is how the fields of a structure are marshaled.
+pub fn marshal_with_params<M: Marshaler + ?Sized>(
+    m: &M,
+    params: &common::FieldParameters,
+) -> Result<Vec<u8>, Error> {
+    let (tags, params) = params.split_tags();
+    let bytes = m.marshal_with_params(&params)?;
+    // An absent OPTIONAL value has nothing to tag.
+    if bytes.is_empty() {
+        return Ok(bytes);
+    }
+    Ok(wrap_explicit(&tags, params.rules, bytes))
+}
+
+// encode_tagged encodes the content octets of a value whose type has the
+// given UNIVERSAL tag and form, applying the tagging in params. This is how
+// every type applies FieldParameters when marshaling.
+pub fn encode_tagged(
+    params: &common::FieldParameters,
+    universal_tag: i32,
+    is_compound: bool,
+    body: Vec<u8>,
+) -> Vec<u8> {
+    let (tags, params) = params.split_tags();
+    let (class, tag) = match params.tag {
+        Some(tag) => (params.tag_class(), tag),
+        None => (common::CLASS_UNIVERSAL, universal_tag),
+    };
+
+    let identifier = common::Identifier {
+        class,
+        tag,
+        is_compound,
+    };
+    wrap_explicit(
+        &tags,
+        params.rules,
+        encode_tlv(identifier, params.rules, body),
+    )
+}
+
+// encode_string is encode_tagged for a string type. CER encodes strings of
+// more than 1000 octets as constructed, in segments of 1000 octets (X.690
+// 9.2).
+pub fn encode_string(
+    params: &common::FieldParameters,
+    universal_tag: i32,
+    body: Vec<u8>,
+) -> Vec<u8> {
+    if params.rules != common::EncodingRules::Cer || body.len() <= CER_SEGMENT_LENGTH {
+        return encode_tagged(params, universal_tag, false, body);
+    }
+
+    let segment = common::Identifier::universal(common::TAG_OCTET_STRING, false);
+    let segments = body
+        .chunks(CER_SEGMENT_LENGTH)
+        .flat_map(|chunk| encode_tlv(segment, params.rules, chunk.to_vec()))
+        .collect();
+    encode_tagged(params, universal_tag, true, segments)
+}
+
+// CER_SEGMENT_LENGTH is the length of the segments of a constructed string
+// under CER.
+pub const CER_SEGMENT_LENGTH: usize = 1000;
+
+// encode_tlv encodes a value with the given identifier and content octets.
+// Under CER, constructed values have an indefinite length and end with
+// end-of-contents octets (X.690 9.1).
+pub fn encode_tlv(
+    identifier: common::Identifier,
+    rules: common::EncodingRules,
+    body: Vec<u8>,
+) -> Vec<u8> {
+    let indefinite = identifier.is_compound && rules == common::EncodingRules::Cer;
+    let mut v = TaggedEncoder {
+        tag: common::TagAndLength {
+            class: identifier.class,
+            tag: identifier.tag,
+            is_compound: identifier.is_compound,
+            length: body.len(),
+            indefinite,
+        },
+        body,
+    }
+    .encode();
+    if indefinite {
+        v.extend_from_slice(&[0x00, 0x00]);
+    }
+    v
+}
+
+// wrap_explicit wraps an encoding in the given EXPLICIT tags, outermost first.
+pub fn wrap_explicit(
+    tags: &[common::Tag],
+    rules: common::EncodingRules,
+    mut bytes: Vec<u8>,
+) -> Vec<u8> {
+    for tag in tags.iter().rev() {
+        let identifier = common::Identifier {
+            class: tag.class,
+            tag: tag.number,
+            is_compound: true,
+        };
+        bytes = encode_tlv(identifier, rules, bytes);
+    }
+    bytes
 }
 
 #[cfg(test)]
@@ -267,32 +478,36 @@ mod tests {
 
     #[test]
     fn it_works() {
-        assert_eq!(marshal(&10), vec![0x02, 0x01, 0x0a]);
-        assert_eq!(marshal(&127), vec![0x02, 0x01, 0x7f]);
-        assert_eq!(marshal(&128), vec![0x02, 0x02, 0x00, 0x80]);
-        assert_eq!(marshal(&-128), vec![0x02, 0x01, 0x80]);
-        assert_eq!(marshal(&-129), vec![0x02, 0x02, 0xff, 0x7f]);
+        assert_eq!(marshal(&10).unwrap(), vec![0x02, 0x01, 0x0a]);
+        assert_eq!(marshal(&127).unwrap(), vec![0x02, 0x01, 0x7f]);
+        assert_eq!(marshal(&128).unwrap(), vec![0x02, 0x02, 0x00, 0x80]);
+        assert_eq!(marshal(&-128).unwrap(), vec![0x02, 0x01, 0x80]);
+        assert_eq!(marshal(&-129).unwrap(), vec![0x02, 0x02, 0xff, 0x7f]);
         assert_eq!(
-            marshal(&IntStruct { a: 64 }),
+            marshal(&IntStruct { a: 64 }).unwrap(),
             vec![0x30, 0x03, 0x02, 0x01, 0x40]
         );
         assert_eq!(
-            marshal(&TwoIntStruct { a: 64, b: 65 }),
+            marshal(&TwoIntStruct { a: 64, b: 65 }).unwrap(),
             vec![0x30, 0x06, 0x02, 0x01, 0x40, 0x02, 0x01, 0x41]
         );
         assert_eq!(
             marshal(&NestedStruct {
                 a: IntStruct { a: 127 }
-            }),
+            })
+            .unwrap(),
             vec![0x30, 0x05, 0x30, 0x03, 0x02, 0x01, 0x7f]
         );
-        assert_eq!(marshal(&vec![1, 2, 3]), vec![0x04, 0x03, 0x01, 0x02, 0x03]);
         assert_eq!(
-            marshal(&ImplicitTagTest { a: 64 }),
+            marshal(&vec![1, 2, 3]).unwrap(),
+            vec![0x04, 0x03, 0x01, 0x02, 0x03]
+        );
+        assert_eq!(
+            marshal(&ImplicitTagTest { a: 64 }).unwrap(),
             vec![0x30, 0x03, 0x85, 0x01, 0x40]
         );
         assert_eq!(
-            marshal(&ExplicitTagTest { a: 64 }),
+            marshal(&ExplicitTagTest { a: 64 }).unwrap(),
             vec![0x30, 0x05, 0xa5, 0x03, 0x02, 0x01, 0x40]
         );
         assert_eq!(
@@ -302,7 +517,8 @@ mod tests {
                 is_compound: false,
                 bytes: vec![0x01, 0x02, 0x03],
                 full_bytes: vec![],
-            }),
+            })
+            .unwrap(),
             vec![0x81, 0x03, 0x01, 0x02, 0x03]
         );
     }
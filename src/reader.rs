@@ -0,0 +1,209 @@
+use crate::common;
+use crate::types;
+use crate::unmarshal;
+use crate::unmarshal::{Error, ErrorKind, Unmarshaler};
+
+// Reader is a cursor over a sequence of encodings, for decoders written by
+// hand where the derives can't express a structure. Like the derived decoders,
+// it keeps every read within the bounds of the enclosing value, rejects
+// leftover bytes, applies the encoding rules and limits it was given and
+// counts nesting depth. Errors are located within the whole input.
+//
+// For example, Message ::= SEQUENCE { id INTEGER, note [0] EXPLICIT UTF8String
+// OPTIONAL } is read with:
+//
+//   let mut r = Reader::new(bytes);
+//   let (id, note) = r.read_sequence(|r| {
+//       Ok((r.read::<i32>()?, r.read_optional::<String>(Tag::explicit(0))?))
+//   })?;
+//   r.finish()?;
+#[derive(Debug, Clone)]
+pub struct Reader<'a> {
+    input: Option<&'a [u8]>, // the whole input, if the Reader was given it.
+    rest: &'a [u8],          // the encodings not read yet.
+    end: usize,              // the offset in the input of the end of the encodings.
+    params: common::FieldParameters, // the parameters the encodings are read under.
+}
+
+impl<'a> Reader<'a> {
+    // new returns a Reader over bytes under DER.
+    pub fn new(bytes: &'a [u8]) -> Reader<'a> {
+        Reader {
+            input: Some(bytes),
+            rest: bytes,
+            end: bytes.len(),
+            params: common::FieldParameters::default(),
+        }
+    }
+
+    // with_params returns a Reader over the components of the value that
+    // params is for, as a hand-written Unmarshaler is given them. The
+    // encoding rules, limits, nesting depth and offset carry over.
+    pub fn with_params(bytes: &'a [u8], params: &common::FieldParameters) -> Reader<'a> {
+        Reader {
+            input: None,
+            rest: bytes,
+            end: params.offset() + bytes.len(),
+            params: common::FieldParameters::default().within(params),
+        }
+    }
+
+    pub fn with_rules(self, rules: common::EncodingRules) -> Reader<'a> {
+        Reader {
+            params: self.params.with_rules(rules),
+            ..self
+        }
+    }
+
+    // with_limits sets the limits that the encodings are read under. They
+    // hold for all the reads together, as for a single value.
+    pub fn with_limits(self, limits: common::Limits) -> Reader<'a> {
+        Reader {
+            params: self.params.with_limits(limits).start(),
+            ..self
+        }
+    }
+
+    // is_empty reports whether every encoding has been read.
+    pub fn is_empty(&self) -> bool {
+        self.rest.is_empty()
+    }
+
+    // remaining returns the encodings not read yet.
+    pub fn remaining(&self) -> &'a [u8] {
+        self.rest
+    }
+
+    // peek_tag returns the identifier of the next encoding without reading
+    // it, or None if there are no more.
+    pub fn peek_tag(&self) -> Result<Option<common::Identifier>, Error> {
+        if self.rest.is_empty() {
+            return Ok(None);
+        }
+        let (tag_and_length, _) =
+            unmarshal::parse_tag_and_length_with_rules(self.rest, self.params.rules)
+                .map_err(|e| self.locate(e.shifted(self.offset())))?;
+        Ok(Some(common::Identifier::from(&tag_and_length)))
+    }
+
+    // read_tlv reads the next encoding, whatever its tag.
+    pub fn read_tlv(&mut self) -> Result<types::RawValueRef<'a>, Error> {
+        self.read()
+    }
+
+    // read reads the next encoding as a T.
+    pub fn read<T: Unmarshaler<'a>>(&mut self) -> Result<T, Error> {
+        let params = self.params.clone();
+        self.read_with_params(&params)
+    }
+
+    // read_optional reads a T under the given tag if the next encoding has
+    // that tag, and returns None otherwise.
+    pub fn read_optional<T: Unmarshaler<'a>>(
+        &mut self,
+        tag: common::Tag,
+    ) -> Result<Option<T>, Error> {
+        let params = common::FieldParameters {
+            optional: true,
+            tags: vec![tag],
+            ..self.params.clone()
+        };
+        self.read_with_params(&params)
+    }
+
+    // read_sequence reads a SEQUENCE, calling f with a Reader over its
+    // components. Any components f leaves unread are an error.
+    pub fn read_sequence<T>(
+        &mut self,
+        f: impl FnOnce(&mut Reader<'a>) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let expected = common::Identifier::universal(common::TAG_SEQUENCE, true);
+        self.read_constructed(&self.params.clone(), expected, f)
+    }
+
+    // read_set is read_sequence for a SET. The components are read in the
+    // order in which they are encoded.
+    pub fn read_set<T>(
+        &mut self,
+        f: impl FnOnce(&mut Reader<'a>) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let expected = common::Identifier::universal(common::TAG_SET, true);
+        self.read_constructed(&self.params.clone(), expected, f)
+    }
+
+    // read_explicit reads a value under the context-specific EXPLICIT tag
+    // [number], calling f with a Reader over the encodings within it.
+    pub fn read_explicit<T>(
+        &mut self,
+        number: i32,
+        f: impl FnOnce(&mut Reader<'a>) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let params = common::FieldParameters {
+            tag: Some(number),
+            ..self.params.clone()
+        };
+        let expected = common::Identifier {
+            class: common::CLASS_CONTEXT_SPECIFIC,
+            tag: number,
+            is_compound: true,
+        };
+        self.read_constructed(&params, expected, f)
+    }
+
+    // finish checks that every encoding has been read.
+    pub fn finish(self) -> Result<(), Error> {
+        if !self.rest.is_empty() {
+            return Err(self.locate(Error::new(ErrorKind::TrailingData, self.offset())));
+        }
+        Ok(())
+    }
+
+    // offset returns the offset in the input of the next encoding.
+    fn offset(&self) -> usize {
+        self.end - self.rest.len()
+    }
+
+    // locate locates an error within the whole input, if the Reader has it.
+    fn locate(&self, err: Error) -> Error {
+        match self.input {
+            Some(input) => err.locate(input),
+            None => err,
+        }
+    }
+
+    fn read_with_params<T: Unmarshaler<'a>>(
+        &mut self,
+        params: &common::FieldParameters,
+    ) -> Result<T, Error> {
+        let params = params.clone().at(self.offset());
+        let (v, rest) =
+            unmarshal::unmarshal_component(self.rest, &params).map_err(|e| self.locate(e))?;
+        self.rest = rest;
+        Ok(v)
+    }
+
+    fn read_constructed<T>(
+        &mut self,
+        params: &common::FieldParameters,
+        expected: common::Identifier,
+        f: impl FnOnce(&mut Reader<'a>) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let accepts = |tag_and_length: &common::TagAndLength, params: &common::FieldParameters| {
+            params.matches_tag(tag_and_length, expected.tag, true)
+        };
+        let params = params.clone().at(self.offset());
+        let (_, contents, rest, offset) =
+            unmarshal::parse_tagged(self.rest, &params, Some(expected), accepts)
+                .map_err(|e| self.locate(e))?;
+        let mut r = Reader {
+            input: self.input,
+            rest: contents,
+            end: offset + contents.len(),
+            params: common::FieldParameters::default().within(&params),
+        };
+        let v = f(&mut r).map_err(|e| self.locate(e))?;
+        r.finish()?;
+        self.rest = rest;
+        Ok(v)
+    }
+}
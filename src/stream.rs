@@ -0,0 +1,281 @@
+use crate::common;
+use crate::marshal;
+use crate::unmarshal;
+use std::io::Read;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Decode(#[from] unmarshal::Error),
+    #[error(transparent)]
+    Encode(#[from] marshal::Error),
+    #[error("frame of at least {length} bytes exceeds the maximum of {max}")]
+    FrameTooLarge { length: usize, max: usize },
+}
+
+// Status is the result of working on input that may be incomplete: either the
+// value, or the number of bytes that are needed at least before trying again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status<T> {
+    Ready(T),
+    NeedMore(usize),
+}
+
+// header_needed returns how many more bytes the tag and length at the start
+// of bytes need, or None if they are complete.
+fn header_needed(bytes: &[u8]) -> Option<usize> {
+    let mut offset = 1;
+    if bytes.is_empty() {
+        return Some(2);
+    }
+    if bytes[0] & 0x1f == 0x1f {
+        // A long form tag number ends with the first byte without the high
+        // bit. parse_tag_and_length rejects those that are too long.
+        loop {
+            match bytes.get(offset) {
+                None => return Some(2),
+                Some(b) => {
+                    offset += 1;
+                    if b & 0x80 == 0 || offset > 6 {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+    let b = match bytes.get(offset) {
+        None => return Some(1),
+        Some(b) => *b,
+    };
+    let num_bytes = match b & 0x80 {
+        0 => 0,
+        _ => (b & 0x7f) as usize,
+    };
+    match bytes.len() - offset - 1 {
+        n if n < num_bytes => Some(num_bytes - n),
+        _ => None,
+    }
+}
+
+// frame_length returns the length of the TLV at the start of bytes, tag and
+// length included, or how many more bytes it needs at least. Under BER and
+// CER an indefinite length is measured by walking its contents up to the
+// matching end-of-contents octets.
+pub fn frame_length(
+    bytes: &[u8],
+    rules: common::EncodingRules,
+) -> Result<Status<usize>, unmarshal::Error> {
+    frame_length_with_limits(bytes, rules, &common::Limits::default())
+}
+
+// frame_length_with_limits is frame_length under the given limits. Lengths
+// beyond the maximum element length are rejected from the header, before
+// their contents have arrived, as is nesting of indefinite lengths beyond the
+// maximum depth.
+pub fn frame_length_with_limits(
+    bytes: &[u8],
+    rules: common::EncodingRules,
+    limits: &common::Limits,
+) -> Result<Status<usize>, unmarshal::Error> {
+    let mut offset = 0;
+    let mut depth = 0;
+    loop {
+        let rest = &bytes[offset..];
+        if depth > 0 && rest.starts_with(&[0x00, 0x00]) {
+            offset += 2;
+            depth -= 1;
+            if depth == 0 {
+                return Ok(Status::Ready(offset));
+            }
+            continue;
+        }
+        if let Some(n) = header_needed(rest) {
+            return Ok(Status::NeedMore(n));
+        }
+
+        let start = offset;
+        let (tag_and_length, contents) = unmarshal::parse_tag_and_length_with_rules(rest, rules)
+            .map_err(|e| e.shifted(start))?;
+        offset += rest.len() - contents.len();
+        if tag_and_length.indefinite {
+            depth += 1;
+            if depth > limits.max_depth {
+                return Err(unmarshal::Error::new(
+                    unmarshal::ErrorKind::LimitExceeded(format!(
+                        "nesting depth exceeds the limit of {}",
+                        limits.max_depth
+                    )),
+                    start,
+                ));
+            }
+            continue;
+        }
+        if tag_and_length.length > limits.max_element_length {
+            return Err(unmarshal::Error::new(
+                unmarshal::ErrorKind::LimitExceeded(format!(
+                    "element length {} exceeds the limit of {}",
+                    tag_and_length.length, limits.max_element_length
+                )),
+                start,
+            ));
+        }
+        if tag_and_length.length > contents.len() {
+            return Ok(Status::NeedMore(tag_and_length.length - contents.len()));
+        }
+        offset += tag_and_length.length;
+        if depth == 0 {
+            return Ok(Status::Ready(offset));
+        }
+    }
+}
+
+// Decoder reads TLVs one at a time from a reader. It reads the header first
+// and then exactly the contents it announces, so it never reads past the end
+// of the current TLV and the reader can be handed back between messages.
+pub struct Decoder<R> {
+    reader: R,
+    rules: common::EncodingRules,
+    limits: common::Limits,
+    buf: Vec<u8>,
+}
+
+impl<R: Read> Decoder<R> {
+    pub fn new(reader: R) -> Decoder<R> {
+        Decoder {
+            reader,
+            rules: common::EncodingRules::default(),
+            limits: common::Limits::default(),
+            buf: vec![],
+        }
+    }
+
+    pub fn with_rules(self, rules: common::EncodingRules) -> Decoder<R> {
+        Decoder { rules, ..self }
+    }
+
+    // with_limits sets the limits that each TLV is read and unmarshaled under.
+    pub fn with_limits(self, limits: common::Limits) -> Decoder<R> {
+        Decoder { limits, ..self }
+    }
+
+    // read_tlv reads the next TLV and returns its encoding. It returns None
+    // if the reader is at its end before the first byte of the TLV.
+    pub fn read_tlv(&mut self) -> Result<Option<&[u8]>, Error> {
+        self.buf.clear();
+        loop {
+            let n = match frame_length_with_limits(&self.buf, self.rules, &self.limits)
+                .map_err(|e| e.locate(&self.buf))?
+            {
+                Status::Ready(_) => return Ok(Some(&self.buf)),
+                Status::NeedMore(n) => n,
+            };
+            // take grows the buffer as the bytes arrive, rather than trusting
+            // the length to allocate up front.
+            let read = (&mut self.reader)
+                .take(n as u64)
+                .read_to_end(&mut self.buf)?;
+            if read == 0 && self.buf.is_empty() {
+                return Ok(None);
+            }
+            if read < n {
+                return Err(
+                    unmarshal::Error::new(unmarshal::ErrorKind::Truncated, self.buf.len())
+                        .locate(&self.buf)
+                        .into(),
+                );
+            }
+        }
+    }
+
+    // decode reads the next TLV and unmarshals it. It returns None if the
+    // reader is at its end.
+    pub fn decode<'a, T: unmarshal::Unmarshaler<'a>>(&'a mut self) -> Result<Option<T>, Error> {
+        let (rules, limits) = (self.rules, self.limits);
+        let bytes = match self.read_tlv()? {
+            Some(bytes) => bytes,
+            None => return Ok(None),
+        };
+        Ok(Some(unmarshal_frame(bytes, rules, limits)?))
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+// Parser is a push parser for input that arrives in pieces, as it does from
+// non-blocking I/O. The input is pushed into the parser, which says how many
+// more bytes it needs at least when it can't produce the next value yet.
+#[derive(Debug, Default)]
+pub struct Parser {
+    rules: common::EncodingRules,
+    limits: common::Limits,
+    buf: Vec<u8>,
+}
+
+impl Parser {
+    pub fn new() -> Parser {
+        Parser::default()
+    }
+
+    pub fn with_rules(self, rules: common::EncodingRules) -> Parser {
+        Parser { rules, ..self }
+    }
+
+    // with_limits sets the limits that each TLV is parsed and unmarshaled
+    // under.
+    pub fn with_limits(self, limits: common::Limits) -> Parser {
+        Parser { limits, ..self }
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    // buffered returns the number of bytes pushed and not yet parsed.
+    pub fn buffered(&self) -> usize {
+        self.buf.len()
+    }
+
+    // next_tlv returns the encoding of the next TLV once all of it has been
+    // pushed, and removes it from the parser.
+    pub fn next_tlv(&mut self) -> Result<Status<Vec<u8>>, unmarshal::Error> {
+        match frame_length_with_limits(&self.buf, self.rules, &self.limits)
+            .map_err(|e| e.locate(&self.buf))?
+        {
+            Status::Ready(n) => Ok(Status::Ready(self.buf.drain(..n).collect())),
+            Status::NeedMore(n) => Ok(Status::NeedMore(n)),
+        }
+    }
+
+    // parse unmarshals the next TLV once all of it has been pushed.
+    pub fn parse<T: unmarshal::UnmarshalerOwned>(&mut self) -> Result<Status<T>, unmarshal::Error> {
+        match self.next_tlv()? {
+            Status::Ready(bytes) => Ok(Status::Ready(unmarshal_frame(
+                &bytes,
+                self.rules,
+                self.limits,
+            )?)),
+            Status::NeedMore(n) => Ok(Status::NeedMore(n)),
+        }
+    }
+}
+
+// unmarshal_frame unmarshals a value that makes up the whole of a TLV.
+pub(crate) fn unmarshal_frame<'a, T: unmarshal::Unmarshaler<'a>>(
+    bytes: &'a [u8],
+    rules: common::EncodingRules,
+    limits: common::Limits,
+) -> Result<T, unmarshal::Error> {
+    let params = common::FieldParameters::default()
+        .with_rules(rules)
+        .with_limits(limits);
+    unmarshal::unmarshal_exact_with_params(bytes, &params)
+}
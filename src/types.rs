@@ -2,10 +2,11 @@ use crate::common;
 use crate::marshal;
 use crate::marshal::Encoder;
 use crate::unmarshal;
+use std::borrow::Cow;
 
 pub type OctetString = Vec<u8>;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct RawValue {
     pub class: i32,
     pub tag: i32,
@@ -14,44 +15,1313 @@ pub struct RawValue {
     pub full_bytes: Vec<u8>, // includes the tag and length
 }
 
+// RawValueRef is a RawValue that borrows its encoding from the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawValueRef<'a> {
+    pub class: i32,
+    pub tag: i32,
+    pub is_compound: bool,
+    pub bytes: &'a [u8],
+    pub full_bytes: &'a [u8], // includes the tag and length
+}
+
+impl<'a> From<&'a RawValue> for RawValueRef<'a> {
+    fn from(rv: &'a RawValue) -> RawValueRef<'a> {
+        RawValueRef {
+            class: rv.class,
+            tag: rv.tag,
+            is_compound: rv.is_compound,
+            bytes: &rv.bytes,
+            full_bytes: &rv.full_bytes,
+        }
+    }
+}
+
+impl From<RawValueRef<'_>> for RawValue {
+    fn from(rv: RawValueRef<'_>) -> RawValue {
+        RawValue {
+            class: rv.class,
+            tag: rv.tag,
+            is_compound: rv.is_compound,
+            bytes: rv.bytes.to_vec(),
+            full_bytes: rv.full_bytes.to_vec(),
+        }
+    }
+}
+
 impl marshal::Marshaler for RawValue {
-    fn marshal_with_params(&self, _params: &common::FieldParameters) -> Vec<u8> {
-        if self.full_bytes.len() > 0 {
-            return self.full_bytes.to_vec();
+    fn marshal_with_params(
+        &self,
+        params: &common::FieldParameters,
+    ) -> Result<Vec<u8>, marshal::Error> {
+        RawValueRef::from(self).marshal_with_params(params)
+    }
+}
+
+impl<'a> unmarshal::Unmarshaler<'a> for RawValue {
+    fn unmarshal_with_params(
+        bytes: &'a [u8],
+        params: &common::FieldParameters,
+    ) -> Result<(RawValue, &'a [u8]), unmarshal::Error> {
+        let (rv, rest) = RawValueRef::unmarshal_with_params(bytes, params)?;
+        unmarshal::charge(
+            params,
+            rv.bytes.len() + rv.full_bytes.len(),
+            params.offset(),
+        )?;
+        Ok((rv.into(), rest))
+    }
+
+    fn accepts_tag(
+        tag_and_length: &common::TagAndLength,
+        params: &common::FieldParameters,
+    ) -> bool {
+        RawValueRef::accepts_tag(tag_and_length, params)
+    }
+}
+
+// A RawValueRef is marshaled with its own tag, unless params replace it with
+// an IMPLICIT tag.
+impl marshal::Marshaler for RawValueRef<'_> {
+    fn marshal_with_params(
+        &self,
+        params: &common::FieldParameters,
+    ) -> Result<Vec<u8>, marshal::Error> {
+        let (tags, params) = params.split_tags();
+        if !self.full_bytes.is_empty() && params.tag.is_none() {
+            return Ok(marshal::wrap_explicit(
+                &tags,
+                params.rules,
+                self.full_bytes.to_vec(),
+            ));
         }
 
-        let t = marshal::TaggedEncoder {
-            tag: common::TagAndLength {
-                class: self.class,
-                is_compound: self.is_compound,
-                length: self.bytes.len(),
-                tag: self.tag,
+        let identifier = common::Identifier {
+            class: match params.tag {
+                Some(_) => params.tag_class(),
+                None => self.class,
             },
-            body: self.bytes.to_vec(),
+            tag: params.tag.unwrap_or(self.tag),
+            is_compound: self.is_compound,
         };
+        let bytes = marshal::encode_tlv(identifier, params.rules, self.bytes.to_vec());
 
-        t.encode()
+        Ok(marshal::wrap_explicit(&tags, params.rules, bytes))
     }
 }
 
-impl unmarshal::Unmarshaler<RawValue> for RawValue {
-    fn unmarshal_with_params<'a>(
-        bytes: &'a [u8],
-        _params: &common::FieldParameters,
-    ) -> Result<(RawValue, &'a [u8]), unmarshal::Error> {
-        println!("1111 bytes: {:02X?}", bytes);
-        let (tag_and_length, bytes) = unmarshal::parse_tag_and_length(bytes)?;
-        println!("tag_and_length: {:?}", tag_and_length);
-        println!("bytes: {:02X?}", bytes);
+impl<'a, 'b: 'a> unmarshal::Unmarshaler<'b> for RawValueRef<'a> {
+    fn unmarshal_with_params(
+        bytes: &'b [u8],
+        params: &common::FieldParameters,
+    ) -> Result<(RawValueRef<'a>, &'b [u8]), unmarshal::Error> {
+        let (tags, inner) = params.split_tags();
+        if !tags.is_empty() {
+            return unmarshal::unmarshal_component(bytes, params);
+        }
 
-        let rv = RawValue {
+        let (tag_and_length, contents, rest, _) =
+            unmarshal::parse_tagged(bytes, &inner, None, Self::accepts_tag)?;
+        let rv = RawValueRef {
             class: tag_and_length.class,
             tag: tag_and_length.tag,
             is_compound: tag_and_length.is_compound,
-            bytes: bytes[bytes.len() - tag_and_length.length..].to_vec(),
-            full_bytes: bytes.to_vec(),
+            bytes: contents,
+            full_bytes: &bytes[..bytes.len() - rest.len()],
+        };
+
+        Ok((rv, rest))
+    }
+
+    // A RawValueRef accepts any tag, unless params give it one.
+    fn accepts_tag(
+        tag_and_length: &common::TagAndLength,
+        params: &common::FieldParameters,
+    ) -> bool {
+        let (tags, params) = params.split_tags();
+        match (tags.first(), params.tag) {
+            (Some(tag), _) => tag.matches(tag_and_length),
+            (None, Some(tag)) => {
+                tag_and_length.class == params.tag_class() && tag_and_length.tag == tag
+            }
+            (None, None) => true,
+        }
+    }
+}
+
+// BitStringRef is a BIT STRING that borrows its bits from the input. The bits
+// are packed into bytes, most significant bit first, and bit_length says how
+// many of them are used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BitStringRef<'a> {
+    pub bytes: &'a [u8],
+    pub bit_length: usize,
+}
+
+impl BitStringRef<'_> {
+    // at returns the bit at the given index. If the index is out of range,
+    // whether beyond bit_length or beyond the bytes, it returns 0.
+    pub fn at(&self, i: usize) -> u8 {
+        if i >= self.bit_length {
+            return 0;
+        }
+        match self.bytes.get(i / 8) {
+            Some(b) => (b >> (7 - i % 8)) & 1,
+            None => 0,
+        }
+    }
+
+    // right_align returns a copy of the bits with the padding moved to the
+    // front, so that the last bit is the least significant bit of the last
+    // byte.
+    pub fn right_align(&self) -> Vec<u8> {
+        let shift = (8 - self.bit_length % 8) % 8;
+        if shift == 0 || self.bytes.is_empty() {
+            return self.bytes.to_vec();
+        }
+
+        let mut a = vec![self.bytes[0] >> shift];
+        for pair in self.bytes.windows(2) {
+            a.push(pair[0] << (8 - shift) | pair[1] >> shift);
+        }
+        a
+    }
+}
+
+impl marshal::Marshaler for BitStringRef<'_> {
+    fn marshal_with_params(
+        &self,
+        params: &common::FieldParameters,
+    ) -> Result<Vec<u8>, marshal::Error> {
+        params
+            .constraints
+            .check_size(self.bit_length)
+            .map_err(marshal::Error::ConstraintViolation)?;
+
+        let padding = (8 - self.bit_length % 8) % 8;
+        if params.rules != common::EncodingRules::Cer
+            || self.bytes.len() < marshal::CER_SEGMENT_LENGTH
+        {
+            let mut body = vec![padding as u8];
+            body.extend_from_slice(self.bytes);
+            return Ok(marshal::encode_tagged(
+                params,
+                common::TAG_BIT_STRING,
+                false,
+                body,
+            ));
+        }
+
+        // Under CER, each segment is a BIT STRING of 1000 content octets,
+        // and only the last one has padding (X.690 9.2).
+        let segment = common::Identifier::universal(common::TAG_BIT_STRING, false);
+        let chunks: Vec<&[u8]> = self.bytes.chunks(marshal::CER_SEGMENT_LENGTH - 1).collect();
+        let mut segments = vec![];
+        for (i, chunk) in chunks.iter().enumerate() {
+            let mut body = match i + 1 == chunks.len() {
+                true => vec![padding as u8],
+                false => vec![0],
+            };
+            body.extend_from_slice(chunk);
+            segments.extend(marshal::encode_tlv(segment, params.rules, body));
+        }
+        Ok(marshal::encode_tagged(
+            params,
+            common::TAG_BIT_STRING,
+            true,
+            segments,
+        ))
+    }
+}
+
+impl<'a, 'b: 'a> unmarshal::Unmarshaler<'b> for BitStringRef<'a> {
+    fn unmarshal_with_params(
+        bytes: &'b [u8],
+        params: &common::FieldParameters,
+    ) -> Result<(BitStringRef<'a>, &'b [u8]), unmarshal::Error> {
+        let (contents, rest) = bit_string(bytes, params)?;
+        let contents = unmarshal::borrowed(contents, params)?;
+        let bs = BitStringRef {
+            bytes: &contents[1..],
+            bit_length: bit_length(contents),
+        };
+        Ok((bs, rest))
+    }
+
+    fn accepts_tag(
+        tag_and_length: &common::TagAndLength,
+        params: &common::FieldParameters,
+    ) -> bool {
+        accepts_bit_string(tag_and_length, params)
+    }
+}
+
+// bit_string decodes a BIT STRING, returning its content octets, which start
+// with the number of padding bits. The contents are borrowed from the input
+// unless they had to be reassembled from segments.
+fn bit_string<'a>(
+    bytes: &'a [u8],
+    params: &common::FieldParameters,
+) -> Result<(Cow<'a, [u8]>, &'a [u8]), unmarshal::Error> {
+    let expected = common::Identifier::universal(common::TAG_BIT_STRING, false);
+    let (tag_and_length, contents, rest, offset) =
+        unmarshal::parse_tagged(bytes, params, Some(expected), accepts_bit_string)?;
+    let ret = unmarshal::bit_string_contents(&tag_and_length, contents, params.rules)
+        .map_err(|e| e.shifted(offset))?;
+    if let Cow::Owned(v) = &ret {
+        unmarshal::charge(params, v.len(), offset)?;
+    }
+    if ret.is_empty() {
+        return Err(unmarshal::Error::new(
+            unmarshal::ErrorKind::InvalidValue("zero length BIT STRING".to_string()),
+            offset,
+        ));
+    }
+    let padding = ret[0] as usize;
+    if padding > 7 || (ret.len() == 1 && padding > 0) {
+        return Err(unmarshal::Error::new(
+            unmarshal::ErrorKind::InvalidValue("invalid padding bits in BIT STRING".to_string()),
+            offset,
+        ));
+    }
+    // BER lets the padding bits take any value, DER and CER require zeros.
+    let last = ret[ret.len() - 1];
+    if params.rules != common::EncodingRules::Ber && last & ((1 << padding) - 1) != 0 {
+        let at = match ret {
+            Cow::Borrowed(_) => offset + contents.len() - 1,
+            Cow::Owned(_) => offset,
+        };
+        return Err(unmarshal::Error::new(
+            unmarshal::ErrorKind::NonCanonical("BIT STRING padding"),
+            at,
+        ));
+    }
+
+    params
+        .constraints
+        .check_size(bit_length(&ret))
+        .map_err(|e| unmarshal::Error::new(unmarshal::ErrorKind::ConstraintViolation(e), offset))?;
+    Ok((ret, rest))
+}
+
+// bit_length returns the number of bits in the valid content octets of a BIT
+// STRING.
+fn bit_length(contents: &[u8]) -> usize {
+    (contents.len() - 1) * 8 - contents[0] as usize
+}
+
+fn accepts_bit_string(
+    tag_and_length: &common::TagAndLength,
+    params: &common::FieldParameters,
+) -> bool {
+    params.matches_string_tag(tag_and_length, common::TAG_BIT_STRING)
+}
+
+// BitString is a BIT STRING that owns its bits, as one that BER or CER split
+// into segments must, since the segments are concatenated.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BitString {
+    pub bytes: Vec<u8>,
+    pub bit_length: usize,
+}
+
+impl BitString {
+    // at returns the bit at the given index. If the index is out of range,
+    // whether beyond bit_length or beyond the bytes, it returns 0.
+    pub fn at(&self, i: usize) -> u8 {
+        BitStringRef::from(self).at(i)
+    }
+
+    // right_align returns a copy of the bits with the padding moved to the
+    // front.
+    pub fn right_align(&self) -> Vec<u8> {
+        BitStringRef::from(self).right_align()
+    }
+}
+
+impl<'a> From<&'a BitString> for BitStringRef<'a> {
+    fn from(bs: &'a BitString) -> BitStringRef<'a> {
+        BitStringRef {
+            bytes: &bs.bytes,
+            bit_length: bs.bit_length,
+        }
+    }
+}
+
+impl From<BitStringRef<'_>> for BitString {
+    fn from(bs: BitStringRef<'_>) -> BitString {
+        BitString {
+            bytes: bs.bytes.to_vec(),
+            bit_length: bs.bit_length,
+        }
+    }
+}
+
+impl marshal::Marshaler for BitString {
+    fn marshal_with_params(
+        &self,
+        params: &common::FieldParameters,
+    ) -> Result<Vec<u8>, marshal::Error> {
+        BitStringRef::from(self).marshal_with_params(params)
+    }
+}
+
+impl<'a> unmarshal::Unmarshaler<'a> for BitString {
+    fn unmarshal_with_params(
+        bytes: &'a [u8],
+        params: &common::FieldParameters,
+    ) -> Result<(BitString, &'a [u8]), unmarshal::Error> {
+        let (contents, rest) = bit_string(bytes, params)?;
+        let bit_length = bit_length(&contents);
+        let mut contents = unmarshal::into_owned(contents, params)?;
+        contents.remove(0);
+        let bs = BitString {
+            bytes: contents,
+            bit_length,
         };
+        Ok((bs, rest))
+    }
 
-        Ok((rv, &bytes[(tag_and_length.length as usize)..]))
+    fn accepts_tag(
+        tag_and_length: &common::TagAndLength,
+        params: &common::FieldParameters,
+    ) -> bool {
+        accepts_bit_string(tag_and_length, params)
     }
 }
+
+// ObjectIdentifierRef is an OBJECT IDENTIFIER that borrows its content octets
+// from the input. The arcs are decoded on demand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObjectIdentifierRef<'a>(&'a [u8]);
+
+impl<'a> ObjectIdentifierRef<'a> {
+    // parse checks that contents are the content octets of an OBJECT
+    // IDENTIFIER: a non-empty sequence of minimally encoded base 128 integers,
+    // each of which fits in an i32. The offset of an error is given from the
+    // start of contents.
+    pub fn parse(contents: &'a [u8]) -> Result<ObjectIdentifierRef<'a>, unmarshal::Error> {
+        if contents.is_empty() {
+            return Err(unmarshal::Error::new(
+                unmarshal::ErrorKind::InvalidValue("zero length OBJECT IDENTIFIER".to_string()),
+                0,
+            ));
+        }
+        let mut offset = 0;
+        while offset < contents.len() {
+            offset = unmarshal::parse_base128_int(contents, offset)?.1;
+        }
+        Ok(ObjectIdentifierRef(contents))
+    }
+
+    // as_bytes returns the content octets.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.0
+    }
+
+    // arcs returns the arcs of the OBJECT IDENTIFIER. The first subidentifier
+    // holds the first two arcs, as 40 * x + y.
+    pub fn arcs(&self) -> impl Iterator<Item = i32> + 'a {
+        let contents = self.0;
+        let mut offset = 0;
+        let mut second = None;
+        std::iter::from_fn(move || {
+            if let Some(arc) = second.take() {
+                return Some(arc);
+            }
+            if offset >= contents.len() {
+                return None;
+            }
+            let (v, next) = unmarshal::parse_base128_int(contents, offset).ok()?;
+            let first = offset == 0;
+            offset = next;
+            if !first {
+                return Some(v);
+            }
+            let (x, y) = match v {
+                v if v < 80 => (v / 40, v % 40),
+                v => (2, v - 80),
+            };
+            second = Some(y);
+            Some(x)
+        })
+    }
+}
+
+impl std::fmt::Display for ObjectIdentifierRef<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, arc) in self.arcs().enumerate() {
+            if i > 0 {
+                write!(f, ".")?;
+            }
+            write!(f, "{}", arc)?;
+        }
+        Ok(())
+    }
+}
+
+impl marshal::Marshaler for ObjectIdentifierRef<'_> {
+    fn marshal_with_params(
+        &self,
+        params: &common::FieldParameters,
+    ) -> Result<Vec<u8>, marshal::Error> {
+        Ok(marshal::encode_tagged(
+            params,
+            common::TAG_OID,
+            false,
+            self.0.to_vec(),
+        ))
+    }
+}
+
+impl<'a, 'b: 'a> unmarshal::Unmarshaler<'b> for ObjectIdentifierRef<'a> {
+    fn unmarshal_with_params(
+        bytes: &'b [u8],
+        params: &common::FieldParameters,
+    ) -> Result<(ObjectIdentifierRef<'a>, &'b [u8]), unmarshal::Error> {
+        let expected = common::Identifier::universal(common::TAG_OID, false);
+        let (_, contents, rest, offset) =
+            unmarshal::parse_tagged(bytes, params, Some(expected), Self::accepts_tag)?;
+        let oid = ObjectIdentifierRef::parse(contents).map_err(|e| e.shifted(offset))?;
+        Ok((oid, rest))
+    }
+
+    fn accepts_tag(
+        tag_and_length: &common::TagAndLength,
+        params: &common::FieldParameters,
+    ) -> bool {
+        params.matches_tag(tag_and_length, common::TAG_OID, false)
+    }
+}
+
+// Value is a value decoded without a schema, for debugging and for tools that
+// handle arbitrary encodings. Each UNIVERSAL type has a variant of its own. A
+// constructed value under any other tag is decoded as Tagged, on the guess
+// that its contents are encodings, as they are both for an EXPLICIT tag and
+// for an IMPLICIT tag on a SEQUENCE or SET.
+//
+// A Value marshals to exactly the bytes it was unmarshaled from. A value that
+// its variant would encode differently, such as a BER BOOLEAN of 0x01 or a
+// length in more octets than needed, is kept as Unknown, as is a primitive
+// value whose tag doesn't identify its type.
+//
+// Value is displayed as an indented tree in the manner of openssl asn1parse.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Boolean(bool),
+    Integer(Vec<u8>), // the content octets, big-endian two's complement.
+    BitString {
+        bytes: Vec<u8>,
+        bit_length: usize,
+    },
+    OctetString(Vec<u8>),
+    Null,
+    ObjectIdentifier(Vec<u8>), // the content octets.
+    ObjectDescriptor(String),
+    External(Vec<Value>),
+    Real(Vec<u8>),       // the content octets.
+    Enumerated(Vec<u8>), // the content octets, as for Integer.
+    EmbeddedPdv(Vec<Value>),
+    Utf8String(String),
+    RelativeOid(Vec<u8>), // the content octets.
+    Time(String),
+    Sequence(Vec<Value>),
+    Set(Vec<Value>),
+    NumericString(String),
+    PrintableString(String),
+    T61String(Vec<u8>),
+    VideotexString(Vec<u8>),
+    Ia5String(String),
+    UtcTime(String),
+    GeneralizedTime(String),
+    GraphicString(Vec<u8>),
+    VisibleString(String),
+    GeneralString(Vec<u8>),
+    UniversalString(String),
+    CharacterString(Vec<Value>),
+    BmpString(String),
+    Date(String),
+    TimeOfDay(String),
+    DateTime(String),
+    Duration(String),
+    OidIri(String),
+    RelativeOidIri(String),
+    // A constructed value that is not of a constructed UNIVERSAL type: one
+    // under a context-specific, APPLICATION or PRIVATE tag, or under BER a
+    // string in segments.
+    Tagged {
+        class: i32,
+        tag: i32,
+        values: Vec<Value>,
+    },
+    // A constructed value with an indefinite length, as BER and CER allow.
+    Indefinite(Box<Value>),
+    // A value that can't be interpreted, with its encoding.
+    Unknown(RawValue),
+}
+
+impl Value {
+    // identifier returns the identifier of the encoding of the value.
+    pub fn identifier(&self) -> common::Identifier {
+        let universal = |tag| common::Identifier::universal(tag, false);
+        let constructed = |tag| common::Identifier::universal(tag, true);
+        match self {
+            Value::Boolean(_) => universal(common::TAG_BOOLEAN),
+            Value::Integer(_) => universal(common::TAG_INTEGER),
+            Value::BitString { .. } => universal(common::TAG_BIT_STRING),
+            Value::OctetString(_) => universal(common::TAG_OCTET_STRING),
+            Value::Null => universal(common::TAG_NULL),
+            Value::ObjectIdentifier(_) => universal(common::TAG_OID),
+            Value::ObjectDescriptor(_) => universal(common::TAG_OBJECT_DESCRIPTOR),
+            Value::External(_) => constructed(common::TAG_EXTERNAL),
+            Value::Real(_) => universal(common::TAG_REAL),
+            Value::Enumerated(_) => universal(common::TAG_ENUM),
+            Value::EmbeddedPdv(_) => constructed(common::TAG_EMBEDDED_PDV),
+            Value::Utf8String(_) => universal(common::TAG_UTF8_STRING),
+            Value::RelativeOid(_) => universal(common::TAG_RELATIVE_OID),
+            Value::Time(_) => universal(common::TAG_TIME),
+            Value::Sequence(_) => constructed(common::TAG_SEQUENCE),
+            Value::Set(_) => constructed(common::TAG_SET),
+            Value::NumericString(_) => universal(common::TAG_NUMERIC_STRING),
+            Value::PrintableString(_) => universal(common::TAG_PRINTABLE_STRING),
+            Value::T61String(_) => universal(common::TAG_T61_STRING),
+            Value::VideotexString(_) => universal(common::TAG_VIDEOTEX_STRING),
+            Value::Ia5String(_) => universal(common::TAG_IA5_STRING),
+            Value::UtcTime(_) => universal(common::TAG_UTCTIME),
+            Value::GeneralizedTime(_) => universal(common::TAG_GENERALIZED_TIME),
+            Value::GraphicString(_) => universal(common::TAG_GRAPHIC_STRING),
+            Value::VisibleString(_) => universal(common::TAG_VISIBLE_STRING),
+            Value::GeneralString(_) => universal(common::TAG_GENERAL_STRING),
+            Value::UniversalString(_) => universal(common::TAG_UNIVERSAL_STRING),
+            Value::CharacterString(_) => constructed(common::TAG_CHARACTER_STRING),
+            Value::BmpString(_) => universal(common::TAG_BMPSTRING),
+            Value::Date(_) => universal(common::TAG_DATE),
+            Value::TimeOfDay(_) => universal(common::TAG_TIME_OF_DAY),
+            Value::DateTime(_) => universal(common::TAG_DATE_TIME),
+            Value::Duration(_) => universal(common::TAG_DURATION),
+            Value::OidIri(_) => universal(common::TAG_OID_IRI),
+            Value::RelativeOidIri(_) => universal(common::TAG_RELATIVE_OID_IRI),
+            Value::Tagged { class, tag, .. } => common::Identifier {
+                class: *class,
+                tag: *tag,
+                is_compound: true,
+            },
+            Value::Indefinite(v) => v.identifier(),
+            Value::Unknown(rv) => common::Identifier {
+                class: rv.class,
+                tag: rv.tag,
+                is_compound: rv.is_compound,
+            },
+        }
+    }
+
+    // values returns the components of a constructed value, or None if the
+    // value is primitive or Unknown.
+    pub fn values(&self) -> Option<&[Value]> {
+        match self {
+            Value::External(values)
+            | Value::EmbeddedPdv(values)
+            | Value::Sequence(values)
+            | Value::Set(values)
+            | Value::CharacterString(values)
+            | Value::Tagged { values, .. } => Some(values),
+            Value::Indefinite(v) => v.values(),
+            _ => None,
+        }
+    }
+
+    // text returns the characters of a character string or time value whose
+    // characters are known.
+    fn text(&self) -> Option<&str> {
+        match self {
+            Value::ObjectDescriptor(s)
+            | Value::Utf8String(s)
+            | Value::Time(s)
+            | Value::NumericString(s)
+            | Value::PrintableString(s)
+            | Value::Ia5String(s)
+            | Value::UtcTime(s)
+            | Value::GeneralizedTime(s)
+            | Value::VisibleString(s)
+            | Value::UniversalString(s)
+            | Value::BmpString(s)
+            | Value::Date(s)
+            | Value::TimeOfDay(s)
+            | Value::DateTime(s)
+            | Value::Duration(s)
+            | Value::OidIri(s)
+            | Value::RelativeOidIri(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    // contents returns the content octets of the encoding of the value.
+    fn contents(&self) -> Vec<u8> {
+        if let Some(values) = self.values() {
+            return values.iter().flat_map(|v| v.encoding()).collect();
+        }
+        match self {
+            Value::Boolean(b) => vec![if *b { 0xff } else { 0x00 }],
+            Value::BitString { bytes, bit_length } => {
+                let mut contents = vec![((8 - bit_length % 8) % 8) as u8];
+                contents.extend_from_slice(bytes);
+                contents
+            }
+            Value::Null => vec![],
+            Value::UniversalString(s) => s.chars().flat_map(|c| (c as u32).to_be_bytes()).collect(),
+            Value::BmpString(s) => s.encode_utf16().flat_map(|u| u.to_be_bytes()).collect(),
+            Value::Integer(c)
+            | Value::OctetString(c)
+            | Value::ObjectIdentifier(c)
+            | Value::Real(c)
+            | Value::Enumerated(c)
+            | Value::RelativeOid(c)
+            | Value::T61String(c)
+            | Value::VideotexString(c)
+            | Value::GraphicString(c)
+            | Value::GeneralString(c) => c.clone(),
+            Value::Unknown(rv) => rv.bytes.clone(),
+            v => v.text().unwrap_or_default().as_bytes().to_vec(),
+        }
+    }
+
+    // encoding returns the encoding of the value, tag and length included.
+    fn encoding(&self) -> Vec<u8> {
+        if let Value::Unknown(rv) = self {
+            if !rv.full_bytes.is_empty() {
+                return rv.full_bytes.clone();
+            }
+        }
+        let identifier = self.identifier();
+        let indefinite = matches!(self, Value::Indefinite(_));
+        let contents = self.contents();
+        let mut v = common::TagAndLength {
+            class: identifier.class,
+            tag: identifier.tag,
+            is_compound: identifier.is_compound,
+            length: contents.len(),
+            indefinite,
+        }
+        .encode();
+        v.extend_from_slice(&contents);
+        if indefinite {
+            v.extend_from_slice(&[0x00, 0x00]);
+        }
+        v
+    }
+
+    // decode interprets the encoding rv, found at the offset in params,
+    // keeping it as Unknown if the value wouldn't be encoded the same way again.
+    fn decode(
+        rv: RawValueRef<'_>,
+        params: &common::FieldParameters,
+    ) -> Result<Value, unmarshal::Error> {
+        let (tag_and_length, contents) =
+            unmarshal::parse_tag_and_length_with_rules(rv.full_bytes, params.rules)
+                .map_err(|e| e.shifted(params.offset()))?;
+        let header = &rv.full_bytes[..rv.full_bytes.len() - contents.len()];
+        let offset = params.offset() + header.len();
+
+        let value = match rv.is_compound {
+            true => {
+                let end = offset + rv.bytes.len();
+                let params = common::FieldParameters::default().within(params);
+                let mut values = vec![];
+                let mut bytes = rv.bytes;
+                while !bytes.is_empty() {
+                    let params = params.clone().at(end - bytes.len());
+                    let (v, rest) = unmarshal::unmarshal_component(bytes, &params)?;
+                    values.push(v);
+                    bytes = rest;
+                }
+                Some(Value::constructed(rv.class, rv.tag, values))
+            }
+            false => {
+                unmarshal::charge(params, rv.bytes.len(), offset)?;
+                Value::primitive(rv.class, rv.tag, rv.bytes).filter(|v| v.contents() == rv.bytes)
+            }
+        };
+        let value = match (value, tag_and_length.indefinite) {
+            (Some(v), true) => Some(Value::Indefinite(Box::new(v))),
+            (v, _) => v,
+        };
+
+        // The components were checked as they were decoded, so that leaves
+        // the tag and length.
+        let canonical = common::TagAndLength {
+            length: rv.bytes.len(),
+            ..tag_and_length
+        }
+        .encode();
+        match value {
+            Some(v) if canonical == header => Ok(v),
+            _ => Ok(Value::Unknown(rv.into())),
+        }
+    }
+
+    // constructed returns the constructed value with the given tag and
+    // components.
+    fn constructed(class: i32, tag: i32, values: Vec<Value>) -> Value {
+        if class != common::CLASS_UNIVERSAL {
+            return Value::Tagged { class, tag, values };
+        }
+        match tag {
+            common::TAG_EXTERNAL => Value::External(values),
+            common::TAG_EMBEDDED_PDV => Value::EmbeddedPdv(values),
+            common::TAG_SEQUENCE => Value::Sequence(values),
+            common::TAG_SET => Value::Set(values),
+            common::TAG_CHARACTER_STRING => Value::CharacterString(values),
+            _ => Value::Tagged { class, tag, values },
+        }
+    }
+
+    // primitive returns the primitive value with the given tag and content
+    // octets, or None if it can't be interpreted.
+    fn primitive(class: i32, tag: i32, contents: &[u8]) -> Option<Value> {
+        if class != common::CLASS_UNIVERSAL {
+            return None;
+        }
+        let bytes = || contents.to_vec();
+        let text = || String::from_utf8(contents.to_vec()).ok();
+        match tag {
+            common::TAG_BOOLEAN => match contents {
+                [b] => Some(Value::Boolean(*b != 0)),
+                _ => None,
+            },
+            common::TAG_INTEGER if !contents.is_empty() => Some(Value::Integer(bytes())),
+            common::TAG_BIT_STRING => match contents.split_first() {
+                Some((&padding, bits)) if padding < 8 && (padding == 0 || !bits.is_empty()) => {
+                    Some(Value::BitString {
+                        bytes: bits.to_vec(),
+                        bit_length: bits.len() * 8 - padding as usize,
+                    })
+                }
+                _ => None,
+            },
+            common::TAG_OCTET_STRING => Some(Value::OctetString(bytes())),
+            common::TAG_NULL if contents.is_empty() => Some(Value::Null),
+            common::TAG_OID => ObjectIdentifierRef::parse(contents)
+                .ok()
+                .map(|_| Value::ObjectIdentifier(bytes())),
+            common::TAG_OBJECT_DESCRIPTOR => text().map(Value::ObjectDescriptor),
+            common::TAG_REAL => Some(Value::Real(bytes())),
+            common::TAG_ENUM if !contents.is_empty() => Some(Value::Enumerated(bytes())),
+            common::TAG_UTF8_STRING => text().map(Value::Utf8String),
+            common::TAG_RELATIVE_OID => {
+                relative_arcs(contents).map(|_| Value::RelativeOid(bytes()))
+            }
+            common::TAG_TIME => text().map(Value::Time),
+            common::TAG_NUMERIC_STRING => text().map(Value::NumericString),
+            common::TAG_PRINTABLE_STRING => text().map(Value::PrintableString),
+            common::TAG_T61_STRING => Some(Value::T61String(bytes())),
+            common::TAG_VIDEOTEX_STRING => Some(Value::VideotexString(bytes())),
+            common::TAG_IA5_STRING => text().map(Value::Ia5String),
+            common::TAG_UTCTIME => text().map(Value::UtcTime),
+            common::TAG_GENERALIZED_TIME => text().map(Value::GeneralizedTime),
+            common::TAG_GRAPHIC_STRING => Some(Value::GraphicString(bytes())),
+            common::TAG_VISIBLE_STRING => text().map(Value::VisibleString),
+            common::TAG_GENERAL_STRING => Some(Value::GeneralString(bytes())),
+            common::TAG_UNIVERSAL_STRING if contents.len().is_multiple_of(4) => contents
+                .chunks(4)
+                .map(|c| char::from_u32(u32::from_be_bytes([c[0], c[1], c[2], c[3]])))
+                .collect::<Option<String>>()
+                .map(Value::UniversalString),
+            // A BMPString holds UCS-2 characters, so surrogates are invalid.
+            common::TAG_BMPSTRING if contents.len().is_multiple_of(2) => contents
+                .chunks(2)
+                .map(|c| char::from_u32(u16::from_be_bytes([c[0], c[1]]) as u32))
+                .collect::<Option<String>>()
+                .map(Value::BmpString),
+            common::TAG_DATE => text().map(Value::Date),
+            common::TAG_TIME_OF_DAY => text().map(Value::TimeOfDay),
+            common::TAG_DATE_TIME => text().map(Value::DateTime),
+            common::TAG_DURATION => text().map(Value::Duration),
+            common::TAG_OID_IRI => text().map(Value::OidIri),
+            common::TAG_RELATIVE_OID_IRI => text().map(Value::RelativeOidIri),
+            _ => None,
+        }
+    }
+
+    // summary returns what is displayed of the value itself, after its tag.
+    fn summary(&self) -> Option<String> {
+        if let Some(s) = self.text() {
+            return Some(s.to_string());
+        }
+        match self {
+            Value::Boolean(b) => Some(if *b { "TRUE" } else { "FALSE" }.to_string()),
+            Value::Integer(c) | Value::Enumerated(c) => Some(integer_string(c)),
+            Value::BitString { bytes: c, .. } | Value::OctetString(c) | Value::Real(c) => {
+                Some(format!("[HEX DUMP]:{}", hex_string(c)))
+            }
+            Value::ObjectIdentifier(c) => ObjectIdentifierRef::parse(c).ok().map(|o| o.to_string()),
+            Value::RelativeOid(c) => relative_arcs(c).map(|arcs| {
+                arcs.iter()
+                    .map(|arc| arc.to_string())
+                    .collect::<Vec<_>>()
+                    .join(".")
+            }),
+            Value::T61String(c)
+            | Value::VideotexString(c)
+            | Value::GraphicString(c)
+            | Value::GeneralString(c) => Some(String::from_utf8_lossy(c).into_owned()),
+            Value::Unknown(rv) => Some(format!("[HEX DUMP]:{}", hex_string(&rv.bytes))),
+            _ => None,
+        }
+    }
+
+    // fmt_tree writes a line for the value, which is at offset in the
+    // encoding and depth levels deep, followed by lines for its components.
+    fn fmt_tree(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        offset: usize,
+        depth: usize,
+    ) -> std::fmt::Result {
+        let encoding = self.encoding();
+        let (tag_and_length, contents) =
+            unmarshal::parse_tag_and_length_with_rules(&encoding, common::EncodingRules::Ber)
+                .map_err(|_| std::fmt::Error)?;
+        let header_length = encoding.len() - contents.len();
+
+        write!(f, "{:5}:d={:<2} hl={} l=", offset, depth, header_length)?;
+        match tag_and_length.indefinite {
+            true => write!(f, " inf")?,
+            false => write!(f, "{:4}", tag_and_length.length)?,
+        }
+        let form = if tag_and_length.is_compound {
+            "cons"
+        } else {
+            "prim"
+        };
+        write!(f, " {}: {:indent$}", form, "", indent = depth)?;
+        let name = tag_name(self.identifier());
+        match self.summary() {
+            Some(summary) => writeln!(f, "{:<18}:{}", name, summary)?,
+            None => writeln!(f, "{}", name)?,
+        }
+
+        let mut offset = offset + header_length;
+        for v in self.values().unwrap_or_default() {
+            v.fmt_tree(f, offset, depth + 1)?;
+            offset += v.encoding().len();
+        }
+        if tag_and_length.indefinite {
+            let depth = depth + 1;
+            writeln!(
+                f,
+                "{:5}:d={:<2} hl=2 l={:4} prim: {:indent$}EOC",
+                offset,
+                depth,
+                0,
+                "",
+                indent = depth
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// relative_arcs returns the arcs of the content octets of a RELATIVE-OID, or
+// None if they are not a sequence of base 128 integers.
+fn relative_arcs(contents: &[u8]) -> Option<Vec<i32>> {
+    if contents.is_empty() {
+        return None;
+    }
+    let mut arcs = vec![];
+    let mut offset = 0;
+    while offset < contents.len() {
+        let (arc, next) = unmarshal::parse_base128_int(contents, offset).ok()?;
+        arcs.push(arc);
+        offset = next;
+    }
+    Some(arcs)
+}
+
+// integer_string returns an INTEGER in decimal, or in hexadecimal if it is
+// too large for an i128.
+fn integer_string(contents: &[u8]) -> String {
+    if contents.len() > 16 {
+        return format!("0x{}", hex_string(contents));
+    }
+    let negative = contents.first().is_some_and(|b| b & 0x80 != 0);
+    let mut bytes = [if negative { 0xff } else { 0x00 }; 16];
+    bytes[16 - contents.len()..].copy_from_slice(contents);
+    i128::from_be_bytes(bytes).to_string()
+}
+
+fn hex_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+// tag_name returns the name of a tag as openssl asn1parse shows it.
+fn tag_name(identifier: common::Identifier) -> String {
+    let tag = identifier.tag;
+    match identifier.class {
+        common::CLASS_APPLICATION => return format!("appl [ {} ]", tag),
+        common::CLASS_CONTEXT_SPECIFIC => return format!("cont [ {} ]", tag),
+        common::CLASS_PRIVATE => return format!("priv [ {} ]", tag),
+        _ => {}
+    }
+    let name = match tag {
+        common::TAG_BOOLEAN => "BOOLEAN",
+        common::TAG_INTEGER => "INTEGER",
+        common::TAG_BIT_STRING => "BIT STRING",
+        common::TAG_OCTET_STRING => "OCTET STRING",
+        common::TAG_NULL => "NULL",
+        common::TAG_OID => "OBJECT",
+        common::TAG_OBJECT_DESCRIPTOR => "OBJECT DESCRIPTOR",
+        common::TAG_EXTERNAL => "EXTERNAL",
+        common::TAG_REAL => "REAL",
+        common::TAG_ENUM => "ENUMERATED",
+        common::TAG_EMBEDDED_PDV => "EMBEDDED PDV",
+        common::TAG_UTF8_STRING => "UTF8STRING",
+        common::TAG_RELATIVE_OID => "RELATIVE OID",
+        common::TAG_TIME => "TIME",
+        common::TAG_SEQUENCE => "SEQUENCE",
+        common::TAG_SET => "SET",
+        common::TAG_NUMERIC_STRING => "NUMERICSTRING",
+        common::TAG_PRINTABLE_STRING => "PRINTABLESTRING",
+        common::TAG_T61_STRING => "T61STRING",
+        common::TAG_VIDEOTEX_STRING => "VIDEOTEXSTRING",
+        common::TAG_IA5_STRING => "IA5STRING",
+        common::TAG_UTCTIME => "UTCTIME",
+        common::TAG_GENERALIZED_TIME => "GENERALIZEDTIME",
+        common::TAG_GRAPHIC_STRING => "GRAPHICSTRING",
+        common::TAG_VISIBLE_STRING => "VISIBLESTRING",
+        common::TAG_GENERAL_STRING => "GENERALSTRING",
+        common::TAG_UNIVERSAL_STRING => "UNIVERSALSTRING",
+        common::TAG_CHARACTER_STRING => "CHARACTER STRING",
+        common::TAG_BMPSTRING => "BMPSTRING",
+        common::TAG_DATE => "DATE",
+        common::TAG_TIME_OF_DAY => "TIME-OF-DAY",
+        common::TAG_DATE_TIME => "DATE-TIME",
+        common::TAG_DURATION => "DURATION",
+        common::TAG_OID_IRI => "OID-IRI",
+        common::TAG_RELATIVE_OID_IRI => "RELATIVE-OID-IRI",
+        _ => return format!("<ASN1 {}>", tag),
+    };
+    name.to_string()
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.fmt_tree(f, 0, 0)
+    }
+}
+
+// A Value is marshaled as the encoding it was unmarshaled from, unless params
+// replace its tag with an IMPLICIT one.
+impl marshal::Marshaler for Value {
+    fn marshal_with_params(
+        &self,
+        params: &common::FieldParameters,
+    ) -> Result<Vec<u8>, marshal::Error> {
+        let identifier = self.identifier();
+        let contents = self.contents();
+        let full_bytes = self.encoding();
+        RawValueRef {
+            class: identifier.class,
+            tag: identifier.tag,
+            is_compound: identifier.is_compound,
+            bytes: &contents,
+            full_bytes: &full_bytes,
+        }
+        .marshal_with_params(params)
+    }
+}
+
+impl<'a> unmarshal::Unmarshaler<'a> for Value {
+    fn unmarshal_with_params(
+        bytes: &'a [u8],
+        params: &common::FieldParameters,
+    ) -> Result<(Value, &'a [u8]), unmarshal::Error> {
+        // The encoding is decoded at the offset of the value inside any
+        // EXPLICIT tags.
+        if !params.split_tags().0.is_empty() {
+            return unmarshal::unmarshal_component(bytes, params);
+        }
+        let (rv, rest) = RawValueRef::unmarshal_with_params(bytes, params)?;
+        unmarshal::charge(params, std::mem::size_of::<Value>(), params.offset())?;
+        Ok((Value::decode(rv, params)?, rest))
+    }
+
+    // A Value accepts any tag, unless params give it one.
+    fn accepts_tag(
+        tag_and_length: &common::TagAndLength,
+        params: &common::FieldParameters,
+    ) -> bool {
+        RawValueRef::accepts_tag(tag_and_length, params)
+    }
+}
+
+// SequenceOf is a SEQUENCE OF T. It is a distinct type from Vec<T> because
+// Vec<u8> is an OCTET STRING.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SequenceOf<T>(pub Vec<T>);
+
+impl<T: marshal::Marshaler> marshal::Marshaler for SequenceOf<T> {
+    fn marshal_with_params(
+        &self,
+        params: &common::FieldParameters,
+    ) -> Result<Vec<u8>, marshal::Error> {
+        params
+            .constraints
+            .check_size(self.0.len())
+            .map_err(marshal::Error::ConstraintViolation)?;
+
+        let element_params = common::FieldParameters::default().with_rules(params.rules);
+        let mut body = vec![];
+        for element in &self.0 {
+            body.append(&mut marshal::marshal_with_params(element, &element_params)?);
+        }
+
+        Ok(marshal::encode_tagged(
+            params,
+            common::TAG_SEQUENCE,
+            true,
+            body,
+        ))
+    }
+}
+
+impl<'a, T: unmarshal::Unmarshaler<'a>> unmarshal::Unmarshaler<'a> for SequenceOf<T> {
+    fn unmarshal_with_params(
+        bytes: &'a [u8],
+        params: &common::FieldParameters,
+    ) -> Result<(SequenceOf<T>, &'a [u8]), unmarshal::Error> {
+        let expected = common::Identifier::universal(common::TAG_SEQUENCE, true);
+        let (_, body, rest, offset) =
+            unmarshal::parse_tagged(bytes, params, Some(expected), Self::accepts_tag)?;
+        let elements = unmarshal_elements(body, offset, params)?;
+        Ok((
+            SequenceOf(elements.into_iter().map(|(v, _)| v).collect()),
+            rest,
+        ))
+    }
+
+    fn accepts_tag(
+        tag_and_length: &common::TagAndLength,
+        params: &common::FieldParameters,
+    ) -> bool {
+        params.matches_tag(tag_and_length, common::TAG_SEQUENCE, true)
+    }
+}
+
+// SetOf is a SET OF T. Under DER and CER its elements are encoded in ascending
+// order of their encodings (X.690 11.6), whatever their order in the Vec.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SetOf<T>(pub Vec<T>);
+
+impl<T: marshal::Marshaler> marshal::Marshaler for SetOf<T> {
+    fn marshal_with_params(
+        &self,
+        params: &common::FieldParameters,
+    ) -> Result<Vec<u8>, marshal::Error> {
+        params
+            .constraints
+            .check_size(self.0.len())
+            .map_err(marshal::Error::ConstraintViolation)?;
+
+        let element_params = common::FieldParameters::default().with_rules(params.rules);
+        let mut elements = vec![];
+        for element in &self.0 {
+            elements.push(marshal::marshal_with_params(element, &element_params)?);
+        }
+        elements.sort();
+
+        Ok(marshal::encode_tagged(
+            params,
+            common::TAG_SET,
+            true,
+            elements.concat(),
+        ))
+    }
+}
+
+impl<'a, T: unmarshal::Unmarshaler<'a>> unmarshal::Unmarshaler<'a> for SetOf<T> {
+    fn unmarshal_with_params(
+        bytes: &'a [u8],
+        params: &common::FieldParameters,
+    ) -> Result<(SetOf<T>, &'a [u8]), unmarshal::Error> {
+        let expected = common::Identifier::universal(common::TAG_SET, true);
+        let (_, body, rest, offset) =
+            unmarshal::parse_tagged(bytes, params, Some(expected), Self::accepts_tag)?;
+        let elements = unmarshal_elements(body, offset, params)?;
+
+        if params.rules != common::EncodingRules::Ber {
+            // The encodings of the elements follow each other from offset.
+            let mut at = offset;
+            for pair in elements.windows(2) {
+                at += pair[0].1.len();
+                if pair[0].1 > pair[1].1 {
+                    return Err(unmarshal::Error::new(
+                        unmarshal::ErrorKind::NonCanonical("SET OF ordering"),
+                        at,
+                    ));
+                }
+            }
+        }
+
+        Ok((SetOf(elements.into_iter().map(|(v, _)| v).collect()), rest))
+    }
+
+    fn accepts_tag(
+        tag_and_length: &common::TagAndLength,
+        params: &common::FieldParameters,
+    ) -> bool {
+        params.matches_tag(tag_and_length, common::TAG_SET, true)
+    }
+}
+
+// unmarshal_elements unmarshals the elements of a SEQUENCE OF or SET OF from
+// its content octets, found at the given offset, returning each with its
+// encoding.
+fn unmarshal_elements<'a, T: unmarshal::Unmarshaler<'a>>(
+    body: &'a [u8],
+    offset: usize,
+    params: &common::FieldParameters,
+) -> Result<Vec<(T, &'a [u8])>, unmarshal::Error> {
+    let element_params = common::FieldParameters::default().within(params);
+    let end = offset + body.len();
+    let mut elements = vec![];
+    let mut contents = body;
+    while !contents.is_empty() {
+        let offset = end - contents.len();
+        if elements.len() == params.limits.max_elements {
+            return Err(unmarshal::Error::new(
+                unmarshal::ErrorKind::LimitExceeded(format!(
+                    "number of elements exceeds the limit of {}",
+                    params.limits.max_elements
+                )),
+                offset,
+            ));
+        }
+        unmarshal::charge(params, std::mem::size_of::<T>(), offset)?;
+        let (element, bytes) =
+            T::unmarshal_with_params(contents, &element_params.clone().at(offset))
+                .map_err(|e| e.in_field(&elements.len().to_string()))?;
+        if bytes.len() == contents.len() {
+            return Err(unmarshal::Error::new(
+                unmarshal::ErrorKind::Structural("SEQUENCE OF element has no encoding".to_string()),
+                offset,
+            ));
+        }
+        elements.push((element, &contents[..contents.len() - bytes.len()]));
+        contents = bytes;
+    }
+
+    params
+        .constraints
+        .check_size(elements.len())
+        .map_err(|e| unmarshal::Error::new(unmarshal::ErrorKind::ConstraintViolation(e), offset))?;
+
+    Ok(elements)
+}
+
+// Explicit, Implicit and Application tag a value in its type rather than in
+// FieldParameters, so that tagging can be used for the elements of a SEQUENCE
+// OF, the alternatives of a CHOICE or in code that doesn't use the derives.
+// For example, [0] EXPLICIT INTEGER is Explicit<i32, 0>, [APPLICATION 3]
+// EXPLICIT INTEGER is Explicit<i32, 3, CLASS_APPLICATION> and [1] IMPLICIT
+// [APPLICATION 2] INTEGER is Implicit<Application<i32, 2>, 1>.
+//
+// The wrappers nest within any tagging given by FieldParameters.
+
+// Explicit is T with an EXPLICIT tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Explicit<T, const N: i32, const CLASS: i32 = { common::CLASS_CONTEXT_SPECIFIC }>(pub T);
+
+// Implicit is T with an IMPLICIT tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Implicit<T, const N: i32, const CLASS: i32 = { common::CLASS_CONTEXT_SPECIFIC }>(pub T);
+
+// Application is T with an IMPLICIT APPLICATION tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Application<T, const N: i32>(pub T);
+
+// tagged returns params with an additional innermost layer of tagging.
+fn tagged(params: &common::FieldParameters, tag: common::Tag) -> common::FieldParameters {
+    let mut tags = params.layers();
+    tags.push(tag);
+    common::FieldParameters {
+        explicit: false,
+        application: false,
+        private: false,
+        tag: None,
+        tags,
+        ..params.clone()
+    }
+}
+
+macro_rules! tagged_impls {
+    ($name:ident <$($params:ident),*>, $class:expr, $explicit:expr) => {
+        impl<T: marshal::Marshaler, $(const $params: i32),*> marshal::Marshaler
+            for $name<T, $($params),*>
+        {
+            fn marshal_with_params(
+                &self,
+                params: &common::FieldParameters,
+            ) -> Result<Vec<u8>, marshal::Error> {
+                let tag = common::Tag {
+                    class: $class,
+                    number: N,
+                    explicit: $explicit,
+                };
+                marshal::marshal_with_params(&self.0, &tagged(params, tag))
+            }
+        }
+
+        impl<'a, T: unmarshal::Unmarshaler<'a>, $(const $params: i32),*>
+            unmarshal::Unmarshaler<'a> for $name<T, $($params),*>
+        {
+            fn unmarshal_with_params(
+                bytes: &'a [u8],
+                params: &common::FieldParameters,
+            ) -> Result<($name<T, $($params),*>, &'a [u8]), unmarshal::Error> {
+                let tag = common::Tag {
+                    class: $class,
+                    number: N,
+                    explicit: $explicit,
+                };
+                let (v, bytes) = unmarshal::unmarshal_component(bytes, &tagged(params, tag))?;
+                Ok(($name(v), bytes))
+            }
+
+            fn accepts_tag(
+                tag_and_length: &common::TagAndLength,
+                params: &common::FieldParameters,
+            ) -> bool {
+                let tag = common::Tag {
+                    class: $class,
+                    number: N,
+                    explicit: $explicit,
+                };
+                unmarshal::accepts_tag::<T>(tag_and_length, &tagged(params, tag))
+            }
+        }
+
+        impl<T, $(const $params: i32),*> From<T> for $name<T, $($params),*> {
+            fn from(v: T) -> Self {
+                $name(v)
+            }
+        }
+
+        impl<T, $(const $params: i32),*> std::ops::Deref for $name<T, $($params),*> {
+            type Target = T;
+
+            fn deref(&self) -> &T {
+                &self.0
+            }
+        }
+
+        impl<T, $(const $params: i32),*> std::ops::DerefMut for $name<T, $($params),*> {
+            fn deref_mut(&mut self) -> &mut T {
+                &mut self.0
+            }
+        }
+    };
+}
+
+tagged_impls!(Explicit<N, CLASS>, CLASS, true);
+tagged_impls!(Implicit<N, CLASS>, CLASS, false);
+tagged_impls!(Application<N>, common::CLASS_APPLICATION, false);
@@ -1,19 +1,193 @@
 use crate::common;
+use crate::marshal;
 pub use encoding_asn1_derive::Unmarshal;
+use std::borrow::Cow;
+use std::fmt;
 use thiserror::Error;
 
-#[derive(Error, Debug)]
-pub enum Error {
-    #[error("structural error")]
-    StructuralError(String),
+// ErrorKind is the kind of problem found in an encoding.
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ErrorKind {
+    #[error("data truncated")]
+    Truncated,
 
-    #[error("Syntax error")]
-    SyntaxError(String),
+    #[error("unexpected tag {found}{}", expected_tag(.expected))]
+    UnexpectedTag {
+        expected: Option<common::Identifier>,
+        found: common::Identifier,
+    },
+
+    #[error("non-minimal {0}")]
+    NonMinimal(&'static str),
+
+    #[error("non-canonical {0}")]
+    NonCanonical(&'static str),
+
+    #[error("length too large")]
+    LengthOverflow,
+
+    #[error("indefinite length found (not DER)")]
+    IndefiniteLength,
+
+    #[error("invalid value: {0}")]
+    InvalidValue(String),
+
+    #[error("trailing data")]
+    TrailingData,
+
+    #[error("constraint violation: {0}")]
+    ConstraintViolation(String),
+
+    #[error("structural error: {0}")]
+    Structural(String),
+
+    #[error("limit exceeded: {0}")]
+    LimitExceeded(String),
+}
+
+fn expected_tag(expected: &Option<common::Identifier>) -> String {
+    match expected {
+        Some(expected) => format!(", expected {}", expected),
+        None => String::new(),
+    }
+}
+
+// Error is a problem found when unmarshaling, together with where in the
+// input it was found: the byte offset and the path of fields leading to the
+// value, such as Message.body.Request.num.
+#[derive(Debug, Clone)]
+pub struct Error(Box<Inner>);
+
+// Inner is boxed to keep results small.
+#[derive(Debug, Clone)]
+struct Inner {
+    kind: ErrorKind,
+    offset: Option<usize>, // the offset of the offending byte in the input.
+    path: Vec<String>,     // the fields leading to the value, outermost first.
+    type_name: Option<&'static str>, // the type the path starts from.
+    context: Vec<u8>,      // the input around offset.
+    context_offset: usize, // the offset of context in the input.
+}
+
+impl Error {
+    // new returns an error of the given kind, found at the given offset in the
+    // input. A decoder finds the offset from FieldParameters::offset, and a
+    // function given bytes without parameters reports it from the start of
+    // bytes.
+    pub fn new(kind: ErrorKind, offset: usize) -> Error {
+        let mut err = Error::from(kind);
+        err.0.offset = Some(offset);
+        err
+    }
+
+    pub fn kind(&self) -> &ErrorKind {
+        &self.0.kind
+    }
+
+    // offset returns the offset in the input of the byte where the problem
+    // was found, if known.
+    pub fn offset(&self) -> Option<usize> {
+        self.0.offset
+    }
+
+    // path returns the path of fields leading to the value in which the
+    // problem was found, starting from the outermost derived type.
+    pub fn path(&self) -> String {
+        self.0
+            .type_name
+            .into_iter()
+            .chain(self.0.path.iter().map(|s| s.as_str()))
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+
+    // in_field records that the error was found in the named field or CHOICE
+    // alternative. It is called by the derives.
+    pub fn in_field(mut self, name: &str) -> Error {
+        self.0.path.insert(0, name.to_string());
+        self
+    }
+
+    // in_type records that the error was found in the named type. It is
+    // called by the derives; the outermost type wins.
+    pub fn in_type(mut self, name: &'static str) -> Error {
+        self.0.type_name = Some(name);
+        self
+    }
+
+    // shifted moves the offset of the error on by n. It locates an error
+    // reported from the start of bytes that are found n bytes into the input,
+    // such as one from parse_tag_and_length.
+    pub fn shifted(mut self, n: usize) -> Error {
+        self.0.offset = self.0.offset.map(|offset| offset + n);
+        self
+    }
+
+    // locate keeps the bytes of input around the offset of the error for
+    // display. input is the whole input that the offset is in.
+    pub fn locate(mut self, input: &[u8]) -> Error {
+        if let Some(offset) = self.0.offset.filter(|offset| *offset <= input.len()) {
+            self.0.context_offset = offset.saturating_sub(8);
+            self.0.context = input[self.0.context_offset..input.len().min(offset + 8)].to_vec();
+        }
+        self
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Error {
+        Error(Box::new(Inner {
+            kind,
+            offset: None,
+            path: vec![],
+            type_name: None,
+            context: vec![],
+            context_offset: 0,
+        }))
+    }
 }
 
+// An Error is displayed with its offset and path, followed by a hexdump of the
+// input around the offending byte:
+//
+//   unexpected tag [UNIVERSAL 4], expected [UNIVERSAL 2] at offset 5 in Message.num
+//     0000: 30 06 02 01 01 04 01 02
+//                          ^^
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.kind)?;
+        if let Some(offset) = self.0.offset {
+            write!(f, " at offset {}", offset)?;
+        }
+        let path = self.path();
+        if !path.is_empty() {
+            write!(f, " in {}", path)?;
+        }
+
+        // An error that hasn't been located in its input has no hexdump.
+        if let Some(offset) = self.0.offset.filter(|_| !self.0.context.is_empty()) {
+            let hex: Vec<String> = self
+                .0
+                .context
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect();
+            write!(f, "\n  {:04x}: {}", self.0.context_offset, hex.join(" "))?;
+            write!(
+                f,
+                "\n        {}^^",
+                "   ".repeat(offset - self.0.context_offset)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {}
+
 // parseBase128Int parses a base-128 encoded int from the given offset in the
 // given byte slice. It returns the value and the new offset.
-fn parse_base128_int(bytes: &[u8], init_offset: usize) -> Result<(i32, usize), Error> {
+pub(crate) fn parse_base128_int(bytes: &[u8], init_offset: usize) -> Result<(i32, usize), Error> {
     let mut offset = init_offset;
     let mut ret64: i64 = 0;
     //for shifted := 0; offset < len(bytes); shifted++ {
@@ -22,8 +196,9 @@ fn parse_base128_int(bytes: &[u8], init_offset: usize) -> Result<(i32, usize), E
         // 5 * 7 bits per byte == 35 bits of data
         // Thus the representation is either non-minimal or too large for an int32
         if shifted == 5 {
-            return Err(Error::StructuralError(
-                "base 128 integer too large".to_string(),
+            return Err(Error::new(
+                ErrorKind::InvalidValue("base 128 integer too large".to_string()),
+                init_offset,
             ));
         }
         ret64 <<= 7;
@@ -31,8 +206,9 @@ fn parse_base128_int(bytes: &[u8], init_offset: usize) -> Result<(i32, usize), E
         // integers should be minimally encoded, so the leading octet should
         // never be 0x80
         if shifted == 0 && b == 0x80 {
-            return Err(Error::SyntaxError(
-                "integer is not minimally encoded".to_string(),
+            return Err(Error::new(
+                ErrorKind::NonMinimal("base 128 integer"),
+                offset,
             ));
         }
         ret64 |= (b & 0x7f) as i64;
@@ -41,24 +217,43 @@ fn parse_base128_int(bytes: &[u8], init_offset: usize) -> Result<(i32, usize), E
             let ret = ret64 as i32;
             // Ensure that the returned value fits in an int on all platforms
             if ret64 > i32::MAX.into() {
-                return Err(Error::SyntaxError("base 128 integer too large".to_string()));
+                return Err(Error::new(
+                    ErrorKind::InvalidValue("base 128 integer too large".to_string()),
+                    init_offset,
+                ));
             }
             return Ok((ret, offset));
         }
 
         shifted += 1;
     }
-    return Err(Error::SyntaxError("truncated base 128 integer".to_string()));
+    Err(Error::new(ErrorKind::Truncated, offset))
 }
 
 // parseTagAndLength parses an ASN.1 tag and length pair from the given offset
-// into a byte slice. It returns the parsed data and the new offset. SET and
-// SET OF (tag 17) are mapped to SEQUENCE and SEQUENCE OF (tag 16) since we
-// don't distinguish between ordered and unordered objects in this code.
+// into a byte slice. It returns the parsed data and the new offset. The offset
+// of an error is given from the start of bytes.
 pub fn parse_tag_and_length(bytes: &[u8]) -> Result<(common::TagAndLength, &[u8]), Error> {
+    parse_tag_and_length_with_rules(bytes, common::EncodingRules::Der)
+}
+
+// parse_tag_and_length_with_rules is parse_tag_and_length under the given
+// encoding rules. BER permits lengths that are not minimally encoded and
+// indefinite lengths, for which indefinite is set and length is zero. CER
+// requires indefinite lengths for constructed encodings.
+pub fn parse_tag_and_length_with_rules(
+    bytes: &[u8],
+    rules: common::EncodingRules,
+) -> Result<(common::TagAndLength, &[u8]), Error> {
+    let canonical = rules != common::EncodingRules::Ber;
     let mut ret = common::TagAndLength::default();
     let mut offset = 0;
 
+    // parseTagAndLength should not be called without at least a single
+    // byte to read. Thus this check is for robustness:
+    if offset >= bytes.len() {
+        return Err(Error::new(ErrorKind::Truncated, offset));
+    }
     let mut b = bytes[offset];
     offset += 1;
     ret.class = (b >> 6) as i32;
@@ -73,10 +268,14 @@ pub fn parse_tag_and_length(bytes: &[u8]) -> Result<(common::TagAndLength, &[u8]
         offset = tmp.1;
         // Tags should be encoded in minimal form.
         if ret.tag < 0x1f {
-            return Err(Error::SyntaxError("non-minimal tag".to_string()));
+            return Err(Error::new(ErrorKind::NonMinimal("tag"), 0));
         }
     }
 
+    if offset >= bytes.len() {
+        return Err(Error::new(ErrorKind::Truncated, offset));
+    }
+    let length_offset = offset;
     b = bytes[offset];
     offset += 1;
     if b & 0x80 == 0 {
@@ -84,90 +283,965 @@ pub fn parse_tag_and_length(bytes: &[u8]) -> Result<(common::TagAndLength, &[u8]
         ret.length = (b & 0x7f) as usize;
     } else {
         // Bottom 7 bits give the number of length bytes to follow.
-        let num_bytes = (b & 0x7f) as i32;
+        let num_bytes = (b & 0x7f) as usize;
         if num_bytes == 0 {
-            //err = SyntaxError{"indefinite length found (not DER)"}
-            //return
+            if rules == common::EncodingRules::Der {
+                return Err(Error::new(ErrorKind::IndefiniteLength, length_offset));
+            }
+            // The contents end with end-of-contents octets, and are only
+            // found by parsing them.
+            if !ret.is_compound {
+                return Err(Error::new(
+                    ErrorKind::InvalidValue("indefinite length of primitive encoding".to_string()),
+                    length_offset,
+                ));
+            }
+            ret.indefinite = true;
+            return Ok((ret, &bytes[offset..]));
         }
         ret.length = 0;
-        //for i := 0; i < numBytes; i++ {
-        for _i in 0..num_bytes {
-            //if offset >= len(bytes) {
-            //    err = SyntaxError{"truncated tag or length"}
-            //    return
-            //}
+        for _ in 0..num_bytes {
+            if offset >= bytes.len() {
+                return Err(Error::new(ErrorKind::Truncated, offset));
+            }
             b = bytes[offset];
             offset += 1;
-            //if ret.length >= 1<<23 {
-            // We can't shift ret.length up without
-            // overflowing.
-            //    err = StructuralError{"length too large"}
-            //    return
-            //}
+            if ret.length >= 1 << 23 {
+                // We can't shift ret.length up without
+                // overflowing.
+                return Err(Error::new(ErrorKind::LengthOverflow, length_offset));
+            }
             ret.length <<= 8;
             ret.length |= b as usize;
-            //if ret.length == 0 {
-            // DER requires that lengths be minimal.
-            //    err = StructuralError{"superfluous leading zeros in length"}
-            //    return
-            //}
+            if canonical && ret.length == 0 {
+                // DER requires that lengths be minimal.
+                return Err(Error::new(ErrorKind::NonMinimal("length"), length_offset));
+            }
         }
         // Short lengths must be encoded in short form.
-        if ret.length < 0x80 {
-            //err = StructuralError{"non-minimal length"}
-            //return
+        if canonical && ret.length < 0x80 {
+            return Err(Error::new(ErrorKind::NonMinimal("length"), length_offset));
         }
     }
 
+    if rules == common::EncodingRules::Cer && ret.is_compound {
+        return Err(Error::new(
+            ErrorKind::NonCanonical("definite length of constructed encoding"),
+            length_offset,
+        ));
+    }
+
     Ok((ret, &bytes[offset..]))
 }
 
-pub trait Unmarshaler<T> {
-    fn unmarshal(bytes: &[u8]) -> Result<(T, &[u8]), Error> {
+// Unmarshaler is implemented by types that can be unmarshaled from input
+// that lives for 'a. Types that borrow from the input, such as &'a [u8], tie
+// their lifetime to it; owned types implement it for every 'a.
+pub trait Unmarshaler<'a>: Sized {
+    fn unmarshal(bytes: &'a [u8]) -> Result<(Self, &'a [u8]), Error> {
         Self::unmarshal_with_params(bytes, &common::FieldParameters::default())
+            .map_err(|e| e.locate(bytes))
     }
-    fn unmarshal_with_params<'a>(
+    fn unmarshal_with_params(
         bytes: &'a [u8],
         params: &common::FieldParameters,
-    ) -> Result<(T, &'a [u8]), Error>;
+    ) -> Result<(Self, &'a [u8]), Error>;
+
+    // from_der unmarshals a value from DER input that holds nothing else.
+    fn from_der(bytes: &'a [u8]) -> Result<Self, Error> {
+        unmarshal_exact(bytes)
+    }
+
+    // unmarshal_components parses the members of a SEQUENCE type directly from
+    // the given bytes, without an outer tag and length. This is what COMPONENTS
+    // OF requires of the inlined type.
+    fn unmarshal_components(
+        _bytes: &'a [u8],
+        params: &common::FieldParameters,
+    ) -> Result<(Self, &'a [u8]), Error> {
+        Err(Error::new(
+            ErrorKind::Structural("COMPONENTS OF requires a SEQUENCE type".to_string()),
+            params.offset(),
+        ))
+    }
+
+    // accepts_tag reports whether an element starting with the given tag can
+    // be decoded as this type under params. It tells apart the members of a SET
+    // and whether an OPTIONAL value is present.
+    fn accepts_tag(
+        _tag_and_length: &common::TagAndLength,
+        _params: &common::FieldParameters,
+    ) -> bool {
+        true
+    }
 }
 
-pub fn unmarshal<T: Unmarshaler<T>>(bytes: &[u8]) -> Result<(T, &[u8]), Error> {
+// UnmarshalerOwned is implemented by types that can be unmarshaled from input
+// of any lifetime, because they don't borrow from it.
+pub trait UnmarshalerOwned: for<'a> Unmarshaler<'a> {}
+
+impl<T> UnmarshalerOwned for T where T: for<'a> Unmarshaler<'a> {}
+
+pub fn unmarshal<'a, T: Unmarshaler<'a>>(bytes: &'a [u8]) -> Result<(T, &'a [u8]), Error> {
     T::unmarshal(bytes)
 }
 
-pub fn unmarshal_with_params<'a, T: Unmarshaler<T>>(
+// unmarshal_ber is unmarshal under BER rather than DER, for data from peers
+// that don't produce canonical encodings.
+pub fn unmarshal_ber<'a, T: Unmarshaler<'a>>(bytes: &'a [u8]) -> Result<(T, &'a [u8]), Error> {
+    let params = common::FieldParameters {
+        rules: common::EncodingRules::Ber,
+        ..common::FieldParameters::default()
+    };
+    unmarshal_with_params(bytes, &params)
+}
+
+// unmarshal_cer is unmarshal under CER rather than DER.
+pub fn unmarshal_cer<'a, T: Unmarshaler<'a>>(bytes: &'a [u8]) -> Result<(T, &'a [u8]), Error> {
+    let params = common::FieldParameters {
+        rules: common::EncodingRules::Cer,
+        ..common::FieldParameters::default()
+    };
+    unmarshal_with_params(bytes, &params)
+}
+
+// unmarshal_exact is unmarshal for input that must hold exactly one value, as
+// the input covered by a signature does. Any bytes left after the value are a
+// TrailingData error, rather than being handed back for the caller to forget.
+pub fn unmarshal_exact<'a, T: Unmarshaler<'a>>(bytes: &'a [u8]) -> Result<T, Error> {
+    unmarshal_exact_with_params(bytes, &common::FieldParameters::default())
+}
+
+// unmarshal_exact_with_params is unmarshal_exact under params.
+pub fn unmarshal_exact_with_params<'a, T: Unmarshaler<'a>>(
+    bytes: &'a [u8],
+    params: &common::FieldParameters,
+) -> Result<T, Error> {
+    let (v, rest) = unmarshal_with_params(bytes, params)?;
+    if !rest.is_empty() {
+        return Err(Error::new(ErrorKind::TrailingData, bytes.len() - rest.len()).locate(bytes));
+    }
+    Ok(v)
+}
+
+// unmarshal_many returns an iterator over the values of a concatenation of
+// DER encodings, such as a bundle of certificates.
+pub fn unmarshal_many<'a, T: Unmarshaler<'a>>(bytes: &'a [u8]) -> UnmarshalMany<'a, T> {
+    unmarshal_many_with_params(bytes, &common::FieldParameters::default())
+}
+
+// unmarshal_many_with_params is unmarshal_many under params.
+pub fn unmarshal_many_with_params<'a, T: Unmarshaler<'a>>(
+    bytes: &'a [u8],
+    params: &common::FieldParameters,
+) -> UnmarshalMany<'a, T> {
+    UnmarshalMany {
+        input: bytes,
+        rest: bytes,
+        params: params.clone(),
+        failed: false,
+        _marker: std::marker::PhantomData,
+    }
+}
+
+// UnmarshalMany is the iterator returned by unmarshal_many. Errors are located
+// within the whole input, and the iterator ends after the first of them,
+// since the values after a malformed one can't be found.
+pub struct UnmarshalMany<'a, T> {
+    input: &'a [u8],
+    rest: &'a [u8],
+    params: common::FieldParameters,
+    failed: bool,
+    _marker: std::marker::PhantomData<fn() -> T>,
+}
+
+impl<'a, T> UnmarshalMany<'a, T> {
+    // remaining returns the input that has not been unmarshaled yet.
+    pub fn remaining(&self) -> &'a [u8] {
+        self.rest
+    }
+}
+
+impl<'a, T: Unmarshaler<'a>> Iterator for UnmarshalMany<'a, T> {
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Result<T, Error>> {
+        if self.rest.is_empty() || self.failed {
+            return None;
+        }
+        let params = self.params.start().at(self.input.len() - self.rest.len());
+        let ret = unmarshal_explicit(self.rest, &params).and_then(|(v, rest)| {
+            if rest.len() == self.rest.len() {
+                return Err(Error::new(
+                    ErrorKind::Structural("value has no encoding".to_string()),
+                    params.offset(),
+                ));
+            }
+            Ok((v, rest))
+        });
+        match ret {
+            Ok((v, rest)) => {
+                self.rest = rest;
+                Some(Ok(v))
+            }
+            Err(e) => {
+                self.failed = true;
+                Some(Err(e.locate(self.input)))
+            }
+        }
+    }
+}
+
+// unmarshal_with_params is unmarshal under params, peeling off any EXPLICIT
+// tags first. The offset of an error is given relative to bytes.
+pub fn unmarshal_with_params<'a, T: Unmarshaler<'a>>(
+    bytes: &'a [u8],
+    params: &common::FieldParameters,
+) -> Result<(T, &'a [u8]), Error> {
+    unmarshal_explicit(bytes, &params.start()).map_err(|e| e.locate(bytes))
+}
+
+// unmarshal_component unmarshals a T under params as a component of the value
+// being unmarshaled, peeling off any EXPLICIT tags first. This is how the
+// fields of a structure are unmarshaled: unlike unmarshal_with_params, the
+// state of the unmarshaling in params carries over.
+pub fn unmarshal_component<'a, T: Unmarshaler<'a>>(
     bytes: &'a [u8],
     params: &common::FieldParameters,
 ) -> Result<(T, &'a [u8]), Error> {
-    T::unmarshal_with_params(bytes, params)
+    unmarshal_explicit(bytes, params)
+}
+
+fn unmarshal_explicit<'a, T: Unmarshaler<'a>>(
+    bytes: &'a [u8],
+    params: &common::FieldParameters,
+) -> Result<(T, &'a [u8]), Error> {
+    let (tags, inner) = params.split_tags();
+    let outer = match tags.first() {
+        Some(tag) => tag,
+        None => return T::unmarshal_with_params(bytes, &inner),
+    };
+
+    // An absent OPTIONAL value is decoded from no bytes at all.
+    if params.optional {
+        let present = match bytes.is_empty() {
+            true => false,
+            false => outer.matches(
+                &parse_tag_and_length_with_rules(bytes, params.rules)
+                    .map_err(|e| e.shifted(params.offset()))?
+                    .0,
+            ),
+        };
+        if !present {
+            let (v, _) = T::unmarshal_with_params(&bytes[..0], &inner)?;
+            return Ok((v, bytes));
+        }
+    }
+
+    // An EXPLICIT tag always holds an encoding, so a present OPTIONAL value
+    // can't be decoded from empty contents as though it were absent.
+    let (contents, offset, rest) =
+        peel_tags(bytes, &tags, params.rules).map_err(|e| e.shifted(params.offset()))?;
+    let offset = params.offset() + offset;
+    if contents.is_empty() {
+        return Err(Error::new(ErrorKind::Truncated, offset));
+    }
+    let (v, trailing) = T::unmarshal_with_params(contents, &inner.at(offset))?;
+    if !trailing.is_empty() {
+        return Err(Error::new(
+            ErrorKind::TrailingData,
+            offset + contents.len() - trailing.len(),
+        ));
+    }
+    Ok((v, rest))
+}
+
+// accepts_tag reports whether an element starting with the given tag can be
+// unmarshaled as a T under params, taking any EXPLICIT tags into account.
+pub fn accepts_tag<'a, T: Unmarshaler<'a>>(
+    tag_and_length: &common::TagAndLength,
+    params: &common::FieldParameters,
+) -> bool {
+    let (tags, inner) = params.split_tags();
+    match tags.first() {
+        Some(tag) => tag.matches(tag_and_length),
+        None => T::accepts_tag(tag_and_length, &inner),
+    }
+}
+
+// peel_tags parses the given EXPLICIT tags, outermost first, returning the
+// contents of the innermost one, their offset from the start of bytes and the
+// bytes following the outermost one.
+fn peel_tags<'a>(
+    bytes: &'a [u8],
+    tags: &[common::Tag],
+    rules: common::EncodingRules,
+) -> Result<(&'a [u8], usize, &'a [u8]), Error> {
+    let mut contents = bytes;
+    let mut offset = 0;
+    let mut rest = None;
+    for tag in tags {
+        let (tag_and_length, body, trailing, header_length) =
+            parse_header(contents, rules).map_err(|e| e.shifted(offset))?;
+        if !tag.matches(&tag_and_length) {
+            return Err(Error::new(
+                ErrorKind::UnexpectedTag {
+                    expected: Some(common::Identifier {
+                        class: tag.class,
+                        tag: tag.number,
+                        is_compound: true,
+                    }),
+                    found: common::Identifier::from(&tag_and_length),
+                },
+                offset,
+            ));
+        }
+        match rest {
+            None => rest = Some(trailing),
+            Some(_) if !trailing.is_empty() => {
+                return Err(Error::new(
+                    ErrorKind::TrailingData,
+                    offset + contents.len() - trailing.len(),
+                ))
+            }
+            Some(_) => {}
+        }
+        contents = body;
+        offset += header_length;
+    }
+    Ok((contents, offset, rest.unwrap_or(&bytes[bytes.len()..])))
+}
+
+// Element is an element parsed from the input: its tag and length, its content
+// octets, the bytes following it and the offset of the content octets.
+pub type Element<'a> = (common::TagAndLength, &'a [u8], &'a [u8], usize);
+
+// parse_header parses a tag and length, checking that the contents are not
+// truncated. It returns the element, with the offset of the content octets
+// from the start of bytes, which is the length of the header. The length of an
+// indefinite length encoding is set to that of its contents, which don't
+// include the end-of-contents octets.
+pub(crate) fn parse_header(
+    bytes: &[u8],
+    rules: common::EncodingRules,
+) -> Result<Element<'_>, Error> {
+    let (mut tag_and_length, body) = parse_tag_and_length_with_rules(bytes, rules)?;
+    let header_length = bytes.len() - body.len();
+    if tag_and_length.indefinite {
+        tag_and_length.length =
+            indefinite_length(body, rules).map_err(|e| e.shifted(header_length))?;
+        let (contents, rest) = body.split_at(tag_and_length.length);
+        return Ok((tag_and_length, contents, &rest[2..], header_length));
+    }
+    if tag_and_length.length > body.len() {
+        return Err(Error::new(ErrorKind::Truncated, bytes.len()));
+    }
+    let (contents, rest) = body.split_at(tag_and_length.length);
+    Ok((tag_and_length, contents, rest, header_length))
+}
+
+// indefinite_length returns the length of the contents of an indefinite
+// length encoding, which end at the matching end-of-contents octets. Nested
+// indefinite length encodings are counted rather than recursed into, so that
+// deep nesting can't exhaust the stack.
+fn indefinite_length(body: &[u8], rules: common::EncodingRules) -> Result<usize, Error> {
+    let mut offset = 0;
+    let mut depth = 1;
+    loop {
+        let bytes = &body[offset..];
+        if bytes.starts_with(&[0x00, 0x00]) {
+            depth -= 1;
+            if depth == 0 {
+                return Ok(offset);
+            }
+            offset += 2;
+            continue;
+        }
+
+        let (tag_and_length, contents) =
+            parse_tag_and_length_with_rules(bytes, rules).map_err(|e| e.shifted(offset))?;
+        offset += bytes.len() - contents.len();
+        if tag_and_length.indefinite {
+            depth += 1;
+        } else if tag_and_length.length > contents.len() {
+            return Err(Error::new(ErrorKind::Truncated, body.len()));
+        } else {
+            offset += tag_and_length.length;
+        }
+    }
+}
+
+// string_contents returns the content octets of a string value. Under BER a
+// string may be constructed from segments, each an OCTET STRING that may in
+// turn be constructed, which are concatenated. CER requires strings of more
+// than 1000 octets to be constructed from primitive segments of 1000 octets,
+// the last of which may be shorter, and shorter strings to be primitive. The
+// offset of an error is given from the start of contents.
+pub fn string_contents<'a>(
+    tag_and_length: &common::TagAndLength,
+    contents: &'a [u8],
+    rules: common::EncodingRules,
+) -> Result<Cow<'a, [u8]>, Error> {
+    if !tag_and_length.is_compound {
+        return primitive_string(contents, rules);
+    }
+    let segments = segments(contents, common::TAG_OCTET_STRING, rules)?;
+    Ok(Cow::Owned(
+        segments
+            .into_iter()
+            .flat_map(|(_, segment)| segment)
+            .copied()
+            .collect(),
+    ))
+}
+
+// bit_string_contents is string_contents for a BIT STRING, whose segments are
+// BIT STRINGs (X.690 8.6.4). Each segment starts with its number of padding
+// bits, which must be zero for every segment but the last, so the content
+// octets returned start with the padding of the last segment.
+pub fn bit_string_contents<'a>(
+    tag_and_length: &common::TagAndLength,
+    contents: &'a [u8],
+    rules: common::EncodingRules,
+) -> Result<Cow<'a, [u8]>, Error> {
+    if !tag_and_length.is_compound {
+        return primitive_string(contents, rules);
+    }
+    let segments = segments(contents, common::TAG_BIT_STRING, rules)?;
+    let mut ret = vec![0];
+    for (i, (offset, segment)) in segments.iter().enumerate() {
+        let padding = match segment.first() {
+            Some(padding) => *padding,
+            None => {
+                return Err(Error::new(
+                    ErrorKind::InvalidValue("zero length BIT STRING segment".to_string()),
+                    *offset,
+                ))
+            }
+        };
+        if padding != 0 && i + 1 < segments.len() {
+            return Err(Error::new(
+                ErrorKind::InvalidValue("invalid padding bits in BIT STRING".to_string()),
+                *offset,
+            ));
+        }
+        ret[0] = padding;
+        ret.extend_from_slice(&segment[1..]);
+    }
+    Ok(Cow::Owned(ret))
+}
+
+// primitive_string returns the content octets of a primitive string, which
+// CER requires to be segmented beyond 1000 octets.
+fn primitive_string(contents: &[u8], rules: common::EncodingRules) -> Result<Cow<'_, [u8]>, Error> {
+    if rules == common::EncodingRules::Cer && contents.len() > marshal::CER_SEGMENT_LENGTH {
+        return Err(Error::new(
+            ErrorKind::NonCanonical("string segmentation"),
+            0,
+        ));
+    }
+    Ok(Cow::Borrowed(contents))
+}
+
+// segments returns the content octets of the primitive segments of a
+// constructed string, in order, each with its offset from the start of
+// contents. The segments have the given UNIVERSAL tag and under BER may in
+// turn be constructed.
+fn segments(
+    contents: &[u8],
+    segment_tag: i32,
+    rules: common::EncodingRules,
+) -> Result<Vec<(usize, &[u8])>, Error> {
+    let cer = rules == common::EncodingRules::Cer;
+    let mut ret = vec![];
+    let mut stack = vec![(0, contents)];
+    while let Some((offset, bytes)) = stack.pop() {
+        if bytes.is_empty() {
+            continue;
+        }
+        let (segment, segment_contents, rest, header_length) =
+            parse_header(bytes, rules).map_err(|e| e.shifted(offset))?;
+        if segment.class != common::CLASS_UNIVERSAL || segment.tag != segment_tag {
+            return Err(Error::new(
+                ErrorKind::UnexpectedTag {
+                    expected: Some(common::Identifier::universal(segment_tag, false)),
+                    found: common::Identifier::from(&segment),
+                },
+                offset,
+            ));
+        }
+        // Every segment but the last is full.
+        let sized = match rest.is_empty() {
+            true => (1..=marshal::CER_SEGMENT_LENGTH).contains(&segment_contents.len()),
+            false => segment_contents.len() == marshal::CER_SEGMENT_LENGTH,
+        };
+        if cer && (segment.is_compound || !sized) {
+            return Err(Error::new(
+                ErrorKind::NonCanonical("string segmentation"),
+                offset,
+            ));
+        }
+        stack.push((offset + bytes.len() - rest.len(), rest));
+        let segment_contents = (offset + header_length, segment_contents);
+        match segment.is_compound {
+            true => stack.push(segment_contents),
+            false => ret.push(segment_contents),
+        }
+    }
+    let length: usize = ret.iter().map(|(_, segment)| segment.len()).sum();
+    if cer && length <= marshal::CER_SEGMENT_LENGTH {
+        return Err(Error::new(
+            ErrorKind::NonCanonical("string segmentation"),
+            0,
+        ));
+    }
+    Ok(ret)
+}
+
+// parse_tagged parses the tagging in params around a value, checking the tag
+// of the value itself with accepts, which is usually the accepts_tag of its
+// type, and reporting expected, the tag of the type when untagged, if it
+// doesn't match. It returns the element holding the value, with the offset of
+// its content octets in the input, which is where an error in them is
+// reported. This is how every type applies FieldParameters when unmarshaling.
+pub fn parse_tagged<'a>(
+    bytes: &'a [u8],
+    params: &common::FieldParameters,
+    expected: Option<common::Identifier>,
+    accepts: impl Fn(&common::TagAndLength, &common::FieldParameters) -> bool,
+) -> Result<Element<'a>, Error> {
+    let offset = params.offset();
+    if params.depth > params.limits.max_depth {
+        return Err(Error::new(
+            ErrorKind::LimitExceeded(format!(
+                "nesting depth exceeds the limit of {}",
+                params.limits.max_depth
+            )),
+            offset,
+        ));
+    }
+
+    let (tags, params) = params.split_tags();
+    let (bytes, offset, outer_rest) = match tags.is_empty() {
+        true => (bytes, offset, None),
+        false => {
+            let (contents, contents_offset, rest) =
+                peel_tags(bytes, &tags, params.rules).map_err(|e| e.shifted(offset))?;
+            (contents, offset + contents_offset, Some(rest))
+        }
+    };
+
+    // A definite length is checked before the contents are looked at, as a
+    // hostile length needn't be backed by any. An indefinite one is only known
+    // once the contents have been walked.
+    let max_length = params.limits.max_element_length;
+    let check_length = |tag_and_length: &common::TagAndLength| match tag_and_length.length {
+        n if n > max_length => Err(Error::new(
+            ErrorKind::LimitExceeded(format!(
+                "element length {} exceeds the limit of {}",
+                n, max_length
+            )),
+            offset,
+        )),
+        _ => Ok(()),
+    };
+    if max_length != usize::MAX {
+        check_length(
+            &parse_tag_and_length_with_rules(bytes, params.rules)
+                .map_err(|e| e.shifted(offset))?
+                .0,
+        )?;
+    }
+    let (tag_and_length, contents, rest, header_length) =
+        parse_header(bytes, params.rules).map_err(|e| e.shifted(offset))?;
+    check_length(&tag_and_length)?;
+    if !accepts(&tag_and_length, &params) {
+        let expected = match params.tag {
+            Some(tag) => Some(common::Identifier {
+                class: params.tag_class(),
+                tag,
+                is_compound: expected.map_or(tag_and_length.is_compound, |e| e.is_compound),
+            }),
+            None => expected,
+        };
+        return Err(Error::new(
+            ErrorKind::UnexpectedTag {
+                expected,
+                found: common::Identifier::from(&tag_and_length),
+            },
+            offset,
+        ));
+    }
+    let contents_offset = offset + header_length;
+    match outer_rest {
+        None => Ok((tag_and_length, contents, rest, contents_offset)),
+        Some(_) if !rest.is_empty() => Err(Error::new(
+            ErrorKind::TrailingData,
+            offset + bytes.len() - rest.len(),
+        )),
+        Some(outer_rest) => Ok((tag_and_length, contents, outer_rest, contents_offset)),
+    }
+}
+
+// check_integer returns nil if the given bytes are a valid DER-encoded
+// INTEGER and an error otherwise.
+fn check_integer(bytes: &[u8]) -> Result<(), Error> {
+    if bytes.is_empty() {
+        return Err(Error::new(
+            ErrorKind::InvalidValue("empty integer".to_string()),
+            0,
+        ));
+    }
+    if bytes.len() == 1 {
+        return Ok(());
+    }
+    if (bytes[0] == 0 && bytes[1] & 0x80 == 0) || (bytes[0] == 0xff && bytes[1] & 0x80 == 0x80) {
+        return Err(Error::new(ErrorKind::NonMinimal("integer"), 0));
+    }
+    Ok(())
 }
 
-pub fn parse_int32(bytes: &[u8]) -> i32 {
-    let mut ret = 0;
-    for bytes_read in 0..bytes.len() {
+// parse_int32 treats the given bytes as a big-endian, signed integer and
+// returns the result. The offset of an error is given from the start of bytes.
+pub fn parse_int32(bytes: &[u8]) -> Result<i32, Error> {
+    check_integer(bytes)?;
+    if bytes.len() > 4 {
+        // We'll overflow an int32 in this case.
+        return Err(Error::new(
+            ErrorKind::InvalidValue("integer too large".to_string()),
+            0,
+        ));
+    }
+
+    let mut ret: i64 = 0;
+    for b in bytes {
         ret <<= 8;
-        ret |= bytes[bytes_read] as i32;
+        ret |= *b as i64;
     }
 
     // Shift up and down in order to sign extend the result.
-    ret <<= 32 - (bytes.len() as u8) * 8;
-    ret >>= 32 - (bytes.len() as u8) * 8;
-    ret
+    ret <<= 64 - (bytes.len() as u8) * 8;
+    ret >>= 64 - (bytes.len() as u8) * 8;
+    Ok(ret as i32)
 }
 
-impl Unmarshaler<i32> for i32 {
-    fn unmarshal_with_params<'a>(
+impl<'a> Unmarshaler<'a> for bool {
+    fn unmarshal_with_params(
         bytes: &'a [u8],
-        _params: &common::FieldParameters,
+        params: &common::FieldParameters,
+    ) -> Result<(bool, &'a [u8]), Error> {
+        let expected = common::Identifier::universal(common::TAG_BOOLEAN, false);
+        let (_, contents, bytes, offset) =
+            parse_tagged(bytes, params, Some(expected), Self::accepts_tag)?;
+        if contents.len() != 1 {
+            return Err(Error::new(
+                ErrorKind::InvalidValue("invalid boolean".to_string()),
+                offset,
+            ));
+        }
+
+        // DER demands that "If the encoding represents the boolean value TRUE,
+        // its single contents octet shall have all eight bits set to one."
+        // Thus only 0 and 255 are valid encoded values.
+        let ret = match (contents[0], params.rules) {
+            (0x00, _) => false,
+            (0xff, _) | (_, common::EncodingRules::Ber) => true,
+            _ => return Err(Error::new(ErrorKind::NonCanonical("BOOLEAN"), offset)),
+        };
+        Ok((ret, bytes))
+    }
+
+    fn accepts_tag(
+        tag_and_length: &common::TagAndLength,
+        params: &common::FieldParameters,
+    ) -> bool {
+        params.matches_tag(tag_and_length, common::TAG_BOOLEAN, false)
+    }
+}
+
+impl<'a> Unmarshaler<'a> for i32 {
+    fn unmarshal_with_params(
+        bytes: &'a [u8],
+        params: &common::FieldParameters,
     ) -> Result<(i32, &'a [u8]), Error> {
-        println!("bytes: {:02X?}", bytes);
+        let expected = common::Identifier::universal(common::TAG_INTEGER, false);
+        let (_, contents, bytes, offset) =
+            parse_tagged(bytes, params, Some(expected), Self::accepts_tag)?;
+        let ret = parse_int32(contents).map_err(|e| e.shifted(offset))?;
+        params
+            .constraints
+            .check_range(ret as i64)
+            .map_err(|e| Error::new(ErrorKind::ConstraintViolation(e), offset))?;
+        Ok((ret, bytes))
+    }
+
+    fn accepts_tag(
+        tag_and_length: &common::TagAndLength,
+        params: &common::FieldParameters,
+    ) -> bool {
+        params.matches_tag(tag_and_length, common::TAG_INTEGER, false)
+    }
+}
+
+// octet_string decodes an OCTET STRING, borrowing its contents from the input
+// unless they had to be reassembled from segments.
+fn octet_string<'a>(
+    bytes: &'a [u8],
+    params: &common::FieldParameters,
+) -> Result<(Cow<'a, [u8]>, &'a [u8]), Error> {
+    let expected = common::Identifier::universal(common::TAG_OCTET_STRING, false);
+    let (tag_and_length, contents, bytes, offset) =
+        parse_tagged(bytes, params, Some(expected), accepts_octet_string)?;
+    let ret =
+        string_contents(&tag_and_length, contents, params.rules).map_err(|e| e.shifted(offset))?;
+    if let Cow::Owned(v) = &ret {
+        charge(params, v.len(), offset)?;
+    }
+    params
+        .constraints
+        .check_size(ret.len())
+        .map_err(|e| Error::new(ErrorKind::ConstraintViolation(e), offset))?;
+    Ok((ret, bytes))
+}
+
+fn accepts_octet_string(
+    tag_and_length: &common::TagAndLength,
+    params: &common::FieldParameters,
+) -> bool {
+    params.matches_string_tag(tag_and_length, common::TAG_OCTET_STRING)
+}
+
+// character_string decodes a UTF8String, or the string type selected by
+// params.string_type, borrowing it from the input where it can.
+fn character_string<'a>(
+    bytes: &'a [u8],
+    params: &common::FieldParameters,
+) -> Result<(Cow<'a, str>, &'a [u8]), Error> {
+    let expected = common::Identifier::universal(
+        match params.string_type {
+            0 => common::TAG_UTF8_STRING,
+            t => t,
+        },
+        false,
+    );
+    let (tag_and_length, contents, bytes, offset) =
+        parse_tagged(bytes, params, Some(expected), accepts_character_string)?;
+    let body =
+        string_contents(&tag_and_length, contents, params.rules).map_err(|e| e.shifted(offset))?;
+    if let Cow::Owned(v) = &body {
+        charge(params, v.len(), offset)?;
+    }
+    // Under an IMPLICIT tag, the string type is only known from params.
+    let tag = match tag_and_length.class {
+        common::CLASS_UNIVERSAL => tag_and_length.tag,
+        _ => params.string_type,
+    };
+    if !matches!(tag, 0 | common::TAG_UTF8_STRING) && !body.is_ascii() {
+        return Err(Error::new(
+            ErrorKind::InvalidValue("string contains non-ASCII characters".to_string()),
+            offset,
+        ));
+    }
+    let ret = match body {
+        Cow::Borrowed(b) => std::str::from_utf8(b).map(Cow::Borrowed).ok(),
+        Cow::Owned(v) => String::from_utf8(v).map(Cow::Owned).ok(),
+    }
+    .ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidValue("invalid UTF-8 string".to_string()),
+            offset,
+        )
+    })?;
+    params
+        .constraints
+        .check_size(ret.chars().count())
+        .and_then(|_| params.constraints.check_alphabet(&ret))
+        .map_err(|e| Error::new(ErrorKind::ConstraintViolation(e), offset))?;
+    Ok((ret, bytes))
+}
 
-        let (tag_and_length, bytes) = parse_tag_and_length(bytes)?;
-        println!("tag_and_length: {:?}", tag_and_length);
-        println!("bytes: {:02X?}", bytes);
-        let ret = parse_int32(&bytes[..tag_and_length.length as usize]);
-        Ok((ret, &bytes[(tag_and_length.length as usize)..]))
+fn accepts_character_string(
+    tag_and_length: &common::TagAndLength,
+    params: &common::FieldParameters,
+) -> bool {
+    match params.string_type {
+        0 => [
+            common::TAG_UTF8_STRING,
+            common::TAG_NUMERIC_STRING,
+            common::TAG_PRINTABLE_STRING,
+            common::TAG_T61_STRING,
+            common::TAG_IA5_STRING,
+            common::TAG_GENERAL_STRING,
+        ]
+        .iter()
+        .any(|t| params.matches_string_tag(tag_and_length, *t)),
+        t => params.matches_string_tag(tag_and_length, t),
+    }
+}
+
+// charge accounts for n bytes allocated for a decoded value found at the given
+// offset.
+pub fn charge(params: &common::FieldParameters, n: usize, offset: usize) -> Result<(), Error> {
+    params
+        .charge(n)
+        .map_err(|e| Error::new(ErrorKind::LimitExceeded(e), offset))
+}
+
+// into_owned returns the owned contents of a string, accounting for the copy
+// of borrowed contents.
+pub(crate) fn into_owned<B: ?Sized + ToOwned + AsRef<[u8]>>(
+    contents: Cow<'_, B>,
+    params: &common::FieldParameters,
+) -> Result<B::Owned, Error> {
+    if let Cow::Borrowed(b) = contents {
+        charge(params, b.as_ref().len(), params.offset())?;
+    }
+    Ok(contents.into_owned())
+}
+
+// borrowed returns the borrowed contents of a string, which a BER constructed
+// encoding can't provide since its segments must be concatenated.
+pub(crate) fn borrowed<'a, B: ?Sized + ToOwned>(
+    contents: Cow<'a, B>,
+    params: &common::FieldParameters,
+) -> Result<&'a B, Error> {
+    match contents {
+        Cow::Borrowed(b) => Ok(b),
+        Cow::Owned(_) => Err(Error::new(
+            ErrorKind::InvalidValue("constructed string can't be borrowed".to_string()),
+            params.offset(),
+        )),
+    }
+}
+
+impl<'a> Unmarshaler<'a> for Vec<u8> {
+    fn unmarshal_with_params(
+        bytes: &'a [u8],
+        params: &common::FieldParameters,
+    ) -> Result<(Vec<u8>, &'a [u8]), Error> {
+        let (v, rest) = octet_string(bytes, params)?;
+        Ok((into_owned(v, params)?, rest))
+    }
+
+    fn accepts_tag(
+        tag_and_length: &common::TagAndLength,
+        params: &common::FieldParameters,
+    ) -> bool {
+        accepts_octet_string(tag_and_length, params)
+    }
+}
+
+// A borrowed OCTET STRING points into the input, so it must be primitive.
+impl<'a, 'b: 'a> Unmarshaler<'b> for &'a [u8] {
+    fn unmarshal_with_params(
+        bytes: &'b [u8],
+        params: &common::FieldParameters,
+    ) -> Result<(&'a [u8], &'b [u8]), Error> {
+        let (v, rest) = octet_string(bytes, params)?;
+        Ok((borrowed(v, params)?, rest))
+    }
+
+    fn accepts_tag(
+        tag_and_length: &common::TagAndLength,
+        params: &common::FieldParameters,
+    ) -> bool {
+        accepts_octet_string(tag_and_length, params)
+    }
+}
+
+// Cow borrows the OCTET STRING where it can, and owns a reassembled one.
+impl<'a, 'b: 'a> Unmarshaler<'b> for Cow<'a, [u8]> {
+    fn unmarshal_with_params(
+        bytes: &'b [u8],
+        params: &common::FieldParameters,
+    ) -> Result<(Cow<'a, [u8]>, &'b [u8]), Error> {
+        octet_string(bytes, params)
+    }
+
+    fn accepts_tag(
+        tag_and_length: &common::TagAndLength,
+        params: &common::FieldParameters,
+    ) -> bool {
+        accepts_octet_string(tag_and_length, params)
+    }
+}
+
+impl<'a> Unmarshaler<'a> for String {
+    fn unmarshal_with_params(
+        bytes: &'a [u8],
+        params: &common::FieldParameters,
+    ) -> Result<(String, &'a [u8]), Error> {
+        let (v, rest) = character_string(bytes, params)?;
+        Ok((into_owned(v, params)?, rest))
+    }
+
+    fn accepts_tag(
+        tag_and_length: &common::TagAndLength,
+        params: &common::FieldParameters,
+    ) -> bool {
+        accepts_character_string(tag_and_length, params)
+    }
+}
+
+impl<'a, 'b: 'a> Unmarshaler<'b> for &'a str {
+    fn unmarshal_with_params(
+        bytes: &'b [u8],
+        params: &common::FieldParameters,
+    ) -> Result<(&'a str, &'b [u8]), Error> {
+        let (v, rest) = character_string(bytes, params)?;
+        Ok((borrowed(v, params)?, rest))
+    }
+
+    fn accepts_tag(
+        tag_and_length: &common::TagAndLength,
+        params: &common::FieldParameters,
+    ) -> bool {
+        accepts_character_string(tag_and_length, params)
+    }
+}
+
+impl<'a, 'b: 'a> Unmarshaler<'b> for Cow<'a, str> {
+    fn unmarshal_with_params(
+        bytes: &'b [u8],
+        params: &common::FieldParameters,
+    ) -> Result<(Cow<'a, str>, &'b [u8]), Error> {
+        character_string(bytes, params)
+    }
+
+    fn accepts_tag(
+        tag_and_length: &common::TagAndLength,
+        params: &common::FieldParameters,
+    ) -> bool {
+        accepts_character_string(tag_and_length, params)
+    }
+}
+
+// An OPTIONAL value is absent when the input is exhausted or when the next
+// element carries a tag that the value's type does not accept.
+impl<'a, T: Unmarshaler<'a>> Unmarshaler<'a> for Option<T> {
+    fn unmarshal_with_params(
+        bytes: &'a [u8],
+        params: &common::FieldParameters,
+    ) -> Result<(Option<T>, &'a [u8]), Error> {
+        if bytes.is_empty() {
+            return Ok((None, bytes));
+        }
+
+        let (tag_and_length, _) = parse_tag_and_length_with_rules(bytes, params.rules)
+            .map_err(|e| e.shifted(params.offset()))?;
+        if !T::accepts_tag(&tag_and_length, params) {
+            return Ok((None, bytes));
+        }
+
+        let (v, bytes) = T::unmarshal_with_params(bytes, params)?;
+        Ok((Some(v), bytes))
+    }
+
+    fn accepts_tag(
+        tag_and_length: &common::TagAndLength,
+        params: &common::FieldParameters,
+    ) -> bool {
+        T::accepts_tag(tag_and_length, params)
     }
 }
 
@@ -182,14 +1256,18 @@ mod tests {
 
     #[test]
     fn it_works() {
-        assert_eq!(parse_int32(&vec![0x00]), 0);
-        assert_eq!(parse_int32(&vec![0x7f]), 127);
-        assert_eq!(parse_int32(&vec![0x00, 0x80]), 128);
-        assert_eq!(parse_int32(&vec![0x01, 0x00]), 256);
-        assert_eq!(parse_int32(&vec![0x80]), -128);
-        assert_eq!(parse_int32(&vec![0xff, 0x7f]), -129);
-        assert_eq!(parse_int32(&vec![0xff]), -1);
-        assert_eq!(parse_int32(&vec![0x80, 0x00, 0x00, 0x00]), -2147483648);
+        assert_eq!(parse_int32(&[0x00]).unwrap(), 0);
+        assert_eq!(parse_int32(&[0x7f]).unwrap(), 127);
+        assert_eq!(parse_int32(&[0x00, 0x80]).unwrap(), 128);
+        assert_eq!(parse_int32(&[0x01, 0x00]).unwrap(), 256);
+        assert_eq!(parse_int32(&[0x80]).unwrap(), -128);
+        assert_eq!(parse_int32(&[0xff, 0x7f]).unwrap(), -129);
+        assert_eq!(parse_int32(&[0xff]).unwrap(), -1);
+        assert_eq!(parse_int32(&[0x80, 0x00, 0x00, 0x00]).unwrap(), -2147483648);
+        assert!(parse_int32(&[]).is_err());
+        assert!(parse_int32(&[0x00, 0x7f]).is_err());
+        assert!(parse_int32(&[0xff, 0x80]).is_err());
+        assert!(parse_int32(&[0x01, 0x00, 0x00, 0x00, 0x00]).is_err());
 
         struct TagAndLengthTest {
             bytes: Vec<u8>,
@@ -221,6 +1299,7 @@ mod tests {
                     tag: 2,
                     length: 0,
                     is_compound: false,
+                    indefinite: false,
                 },
             },
             TagAndLengthTest {
@@ -230,6 +1309,7 @@ mod tests {
                     tag: 30,
                     length: 0,
                     is_compound: true,
+                    indefinite: false,
                 },
             },
             TagAndLengthTest {
@@ -239,6 +1319,7 @@ mod tests {
                     tag: 31,
                     length: 0,
                     is_compound: false,
+                    indefinite: false,
                 },
             },
             TagAndLengthTest {
@@ -248,6 +1329,7 @@ mod tests {
                     tag: 128,
                     length: 0,
                     is_compound: false,
+                    indefinite: false,
                 },
             },
             TagAndLengthTest {
@@ -257,6 +1339,7 @@ mod tests {
                     tag: 0x4001,
                     length: 0,
                     is_compound: false,
+                    indefinite: false,
                 },
             },
             TagAndLengthTest {
@@ -266,6 +1349,7 @@ mod tests {
                     tag: 0,
                     length: 128,
                     is_compound: false,
+                    indefinite: false,
                 },
             },
             TagAndLengthTest {
@@ -275,6 +1359,7 @@ mod tests {
                     tag: 0,
                     length: 256,
                     is_compound: false,
+                    indefinite: false,
                 },
             },
             TagAndLengthTest {
@@ -284,6 +1369,7 @@ mod tests {
                     tag: 0,
                     length: 0x7fffffff,
                     is_compound: true,
+                    indefinite: false,
                 },
             },
             TagAndLengthTest {
@@ -293,6 +1379,7 @@ mod tests {
                     tag: i32::MAX,
                     length: 0,
                     is_compound: false,
+                    indefinite: false,
                 },
             },
         ];
@@ -302,12 +1389,46 @@ mod tests {
             assert_eq!(tl, test.out);
         }
 
+        let (tl, _) =
+            parse_tag_and_length_with_rules(&[0x30, 0x80], common::EncodingRules::Ber).unwrap();
+        assert!(tl.indefinite && tl.is_compound);
+        assert!(parse_tag_and_length(&[0x30, 0x80]).is_err());
+
+        let bad_tag_and_length_data: Vec<&[u8]> = vec![
+            // truncated
+            &[],
+            &[0xa0],
+            &[0x1f, 0x85],
+            &[0xa0, 0x82, 0x01],
+            // indefinite length
+            &[0xa0, 0x80],
+            // superfluous zeros in the length
+            &[0xa0, 0x82, 0x00, 0xff],
+            // non-minimal length
+            &[0xa0, 0x81, 0x7f],
+            // length too large
+            &[0xa0, 0x84, 0x80, 0x00, 0x00, 0x00],
+            &[0xa0, 0x85, 0x01, 0x00, 0x00, 0x00, 0x00],
+            // non-minimal tag
+            &[0x1f, 0x1e, 0x00],
+            // tag too large
+            &[0x1f, 0x88, 0x80, 0x80, 0x80, 0x00, 0x00],
+        ];
+        for bytes in bad_tag_and_length_data {
+            assert!(parse_tag_and_length(bytes).is_err(), "{:02x?}", bytes);
+        }
+
         let bytes = vec![0x02, 0x01, 0x42];
         let i = i32::unmarshal(&bytes).unwrap();
         assert_eq!(i.0, 0x42);
 
+        let bytes = vec![0x04, 0x02, 0x01, 0x02, 0x02, 0x01, 0x42];
+        let (v, bytes) = Vec::<u8>::unmarshal(&bytes).unwrap();
+        assert_eq!(v, vec![0x01, 0x02]);
+        assert_eq!(bytes, &[0x02, 0x01, 0x42]);
+
         let bytes = vec![0x30, 0x03, 0x02, 0x01, 0x40];
-        let is = IntStruct::unmarshal(&bytes);
-        println!("is: {:?}", is);
+        let (is, _) = IntStruct::unmarshal(&bytes).unwrap();
+        assert_eq!(is.a, 0x40);
     }
 }
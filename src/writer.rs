@@ -0,0 +1,154 @@
+use crate::common;
+use crate::marshal;
+use crate::marshal::{Error, Marshaler};
+use crate::types;
+
+// Writer builds an encoding by hand, for encoders written by hand where the
+// derives can't express a structure. It is the counterpart of Reader: values
+// are written one after another, and the constructed values around them are
+// given their lengths once their contents are complete. The encoding is DER
+// unless other rules are given.
+//
+// For example, Message ::= SEQUENCE { id INTEGER, note [0] EXPLICIT UTF8String
+// OPTIONAL } is written with:
+//
+//   let mut w = Writer::new();
+//   w.sequence(|w| {
+//       w.write(&id)?;
+//       w.explicit(0, |w| w.write(note))
+//   })?;
+//   let bytes = w.finish();
+#[derive(Debug, Default, Clone)]
+pub struct Writer {
+    rules: common::EncodingRules, // the encoding rules in use.
+    encodings: Vec<Vec<u8>>,      // the encodings written so far, in order.
+}
+
+impl Writer {
+    // new returns a Writer that encodes under DER.
+    pub fn new() -> Writer {
+        Writer::default()
+    }
+
+    pub fn with_rules(self, rules: common::EncodingRules) -> Writer {
+        Writer { rules, ..self }
+    }
+
+    // write writes the encoding of v.
+    pub fn write<T: Marshaler + ?Sized>(&mut self, v: &T) -> Result<(), Error> {
+        let params = common::FieldParameters::default().with_rules(self.rules);
+        self.push(marshal::marshal_with_params(v, &params)?);
+        Ok(())
+    }
+
+    // implicit writes the encoding of v with its tag replaced by the
+    // context-specific IMPLICIT tag [number].
+    pub fn implicit<T: Marshaler + ?Sized>(&mut self, number: i32, v: &T) -> Result<(), Error> {
+        let params = common::FieldParameters {
+            tag: Some(number),
+            ..common::FieldParameters::default().with_rules(self.rules)
+        };
+        self.push(marshal::marshal_with_params(v, &params)?);
+        Ok(())
+    }
+
+    // write_raw writes bytes as they are, which must be whole encodings.
+    pub fn write_raw(&mut self, bytes: &[u8]) {
+        self.push(bytes.to_vec());
+    }
+
+    // sequence writes a SEQUENCE whose components are those f writes.
+    pub fn sequence(
+        &mut self,
+        f: impl FnOnce(&mut Writer) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let body = self.nested(f)?.concat();
+        let identifier = common::Identifier::universal(common::TAG_SEQUENCE, true);
+        self.push(marshal::encode_tlv(identifier, self.rules, body));
+        Ok(())
+    }
+
+    // set writes a SET whose components are those f writes, in the canonical
+    // order of their tags rather than the order they are written in.
+    pub fn set(&mut self, f: impl FnOnce(&mut Writer) -> Result<(), Error>) -> Result<(), Error> {
+        let body = marshal::set_components(self.nested(f)?);
+        let identifier = common::Identifier::universal(common::TAG_SET, true);
+        self.push(marshal::encode_tlv(identifier, self.rules, body));
+        Ok(())
+    }
+
+    // explicit writes the encodings f writes within the context-specific
+    // EXPLICIT tag [number]. If f writes nothing, as for an absent OPTIONAL
+    // value, nothing is written, since an EXPLICIT tag always holds an
+    // encoding.
+    pub fn explicit(
+        &mut self,
+        number: i32,
+        f: impl FnOnce(&mut Writer) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let body = self.nested(f)?.concat();
+        if body.is_empty() {
+            return Ok(());
+        }
+        let identifier = common::Identifier {
+            class: common::CLASS_CONTEXT_SPECIFIC,
+            tag: number,
+            is_compound: true,
+        };
+        self.push(marshal::encode_tlv(identifier, self.rules, body));
+        Ok(())
+    }
+
+    // octet_string_containing writes an OCTET STRING whose contents are the
+    // encodings f writes, as X.509 extensions carry their values.
+    pub fn octet_string_containing(
+        &mut self,
+        f: impl FnOnce(&mut Writer) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let contents = self.nested(f)?.concat();
+        self.write(&contents[..])
+    }
+
+    // bit_string_containing writes a BIT STRING whose contents are the
+    // encodings f writes, as X.509 subject public keys are carried.
+    pub fn bit_string_containing(
+        &mut self,
+        f: impl FnOnce(&mut Writer) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let contents = self.nested(f)?.concat();
+        self.write(&types::BitStringRef {
+            bytes: &contents,
+            bit_length: contents.len() * 8,
+        })
+    }
+
+    // is_empty reports whether nothing has been written.
+    pub fn is_empty(&self) -> bool {
+        self.encodings.is_empty()
+    }
+
+    // finish returns the encodings written, one after another.
+    pub fn finish(self) -> Vec<u8> {
+        self.encodings.concat()
+    }
+
+    // nested returns the encodings f writes to a Writer under the same rules.
+    fn nested(
+        &self,
+        f: impl FnOnce(&mut Writer) -> Result<(), Error>,
+    ) -> Result<Vec<Vec<u8>>, Error> {
+        let mut w = Writer {
+            rules: self.rules,
+            encodings: vec![],
+        };
+        f(&mut w)?;
+        Ok(w.encodings)
+    }
+
+    // push appends an encoding. An absent OPTIONAL value has none.
+    fn push(&mut self, bytes: Vec<u8>) {
+        if !bytes.is_empty() {
+            self.encodings.push(bytes);
+        }
+    }
+}
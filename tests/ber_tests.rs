@@ -0,0 +1,182 @@
+/*
+World-Schema DEFINITIONS IMPLICIT TAGS ::=
+BEGIN
+Entry ::= SEQUENCE {
+  id INTEGER,
+  name [0] EXPLICIT UTF8String,
+  data [1] OCTET STRING OPTIONAL
+}
+END
+*/
+use encoding_asn1::unmarshal::ErrorKind;
+use encoding_asn1::{BitString, BitStringRef, Marshal, RawValue, SequenceOf, Unmarshal};
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+struct Entry {
+    id: i32,
+    #[asn1(explicit, tag = 0)]
+    name: String,
+    #[asn1(tag = 1)]
+    data: Option<Vec<u8>>,
+}
+
+fn kind<'a, T: encoding_asn1::Unmarshaler<'a>>(bytes: &'a [u8]) -> ErrorKind {
+    match encoding_asn1::unmarshal_ber::<T>(bytes) {
+        Ok(_) => panic!("{:02x?} decoded", bytes),
+        Err(e) => e.kind().clone(),
+    }
+}
+
+#[test]
+fn indefinite_length() {
+    // The SEQUENCE and the EXPLICIT tag both have indefinite lengths.
+    let bytes = [
+        0x30, 0x80, 0x02, 0x01, 0x07, 0xa0, 0x80, 0x0c, 0x01, 0x61, 0x00, 0x00, 0x00, 0x00, 0x05,
+        0x00,
+    ];
+    let (entry, rest) = encoding_asn1::unmarshal_ber::<Entry>(&bytes).unwrap();
+    assert_eq!(
+        entry,
+        Entry {
+            id: 7,
+            name: "a".to_string(),
+            data: None,
+        }
+    );
+    assert_eq!(rest, &[0x05, 0x00]);
+
+    assert_eq!(
+        encoding_asn1::unmarshal::<Entry>(&bytes)
+            .unwrap_err()
+            .kind(),
+        &ErrorKind::IndefiniteLength
+    );
+
+    let bytes = [0x30, 0x80, 0x02, 0x01, 0x01, 0x02, 0x01, 0x02, 0x00, 0x00];
+    assert_eq!(
+        encoding_asn1::unmarshal_ber::<SequenceOf<i32>>(&bytes)
+            .unwrap()
+            .0,
+        SequenceOf(vec![1, 2])
+    );
+
+    // A RawValue keeps the end-of-contents octets.
+    let (rv, rest) = encoding_asn1::unmarshal_ber::<RawValue>(&bytes).unwrap();
+    assert_eq!(rv.bytes, &bytes[2..8]);
+    assert_eq!(rv.full_bytes, bytes);
+    assert!(rest.is_empty());
+}
+
+#[test]
+fn constructed_strings() {
+    // Segments, one of them itself constructed with an indefinite length.
+    let bytes = [
+        0x24, 0x80, 0x04, 0x02, 0x01, 0x02, 0x24, 0x80, 0x04, 0x01, 0x03, 0x00, 0x00, 0x04, 0x00,
+        0x00, 0x00,
+    ];
+    assert_eq!(
+        encoding_asn1::unmarshal_ber::<Vec<u8>>(&bytes).unwrap().0,
+        vec![1, 2, 3]
+    );
+    assert!(matches!(
+        encoding_asn1::unmarshal::<Vec<u8>>(&bytes)
+            .unwrap_err()
+            .kind(),
+        ErrorKind::IndefiniteLength
+    ));
+
+    let bytes = [0x2c, 0x08, 0x04, 0x02, b'h', b'i', 0x04, 0x02, b'!', b'!'];
+    assert_eq!(
+        encoding_asn1::unmarshal_ber::<String>(&bytes).unwrap().0,
+        "hi!!"
+    );
+    assert!(matches!(
+        encoding_asn1::unmarshal::<String>(&bytes)
+            .unwrap_err()
+            .kind(),
+        ErrorKind::UnexpectedTag { .. }
+    ));
+
+    // A constructed string under an IMPLICIT tag.
+    let bytes = [
+        0x30, 0x80, 0x02, 0x01, 0x07, 0xa0, 0x03, 0x0c, 0x01, 0x61, 0xa1, 0x06, 0x04, 0x01, 0x01,
+        0x04, 0x01, 0x02, 0x00, 0x00,
+    ];
+    assert_eq!(
+        encoding_asn1::unmarshal_ber::<Entry>(&bytes).unwrap().0,
+        Entry {
+            id: 7,
+            name: "a".to_string(),
+            data: Some(vec![1, 2]),
+        }
+    );
+
+    // Segments must be OCTET STRINGs.
+    let bytes = [0x24, 0x03, 0x02, 0x01, 0x01];
+    assert!(matches!(
+        kind::<Vec<u8>>(&bytes),
+        ErrorKind::UnexpectedTag { .. }
+    ));
+}
+
+#[test]
+fn constructed_bit_strings() {
+    let bytes = [
+        0x23, 0x80, 0x03, 0x02, 0x00, 0xff, 0x03, 0x02, 0x00, 0x0f, 0x00, 0x00,
+    ];
+    assert_eq!(
+        encoding_asn1::unmarshal_ber::<BitString>(&bytes).unwrap().0,
+        BitString {
+            bytes: vec![0xff, 0x0f],
+            bit_length: 16,
+        }
+    );
+
+    // The padding is that of the last segment.
+    let bytes = [0x23, 0x08, 0x03, 0x02, 0x00, 0xff, 0x03, 0x02, 0x04, 0xf0];
+    let (bs, _) = encoding_asn1::unmarshal_ber::<BitString>(&bytes).unwrap();
+    assert_eq!(bs.bit_length, 12);
+    assert_eq!(bs.right_align(), vec![0x0f, 0xff]);
+    assert_eq!(
+        encoding_asn1::marshal(&bs).unwrap(),
+        vec![0x03, 0x03, 0x04, 0xff, 0xf0]
+    );
+
+    // Only the last segment may have padding.
+    let bytes = [0x23, 0x08, 0x03, 0x02, 0x04, 0xf0, 0x03, 0x02, 0x00, 0xff];
+    assert!(matches!(
+        kind::<BitString>(&bytes),
+        ErrorKind::InvalidValue(_)
+    ));
+    // Segments must be BIT STRINGs.
+    let bytes = [0x23, 0x04, 0x04, 0x02, 0x00, 0xff];
+    assert!(matches!(
+        kind::<BitString>(&bytes),
+        ErrorKind::UnexpectedTag { .. }
+    ));
+    // Reassembled bits can't be borrowed, and DER has no constructed form.
+    let bytes = [0x23, 0x04, 0x03, 0x02, 0x00, 0xff];
+    assert!(matches!(
+        kind::<BitStringRef>(&bytes),
+        ErrorKind::InvalidValue(_)
+    ));
+    assert!(encoding_asn1::unmarshal::<BitString>(&bytes).is_err());
+}
+
+#[test]
+fn malformed() {
+    // No end-of-contents octets.
+    assert_eq!(
+        kind::<SequenceOf<i32>>(&[0x30, 0x80, 0x02, 0x01, 0x01]),
+        ErrorKind::Truncated
+    );
+    assert_eq!(
+        kind::<SequenceOf<i32>>(&[0x30, 0x80, 0x30, 0x80, 0x00, 0x00]),
+        ErrorKind::Truncated
+    );
+    // Primitive encodings can't have an indefinite length.
+    assert!(matches!(
+        kind::<i32>(&[0x02, 0x80, 0x01, 0x00, 0x00]),
+        ErrorKind::InvalidValue(_)
+    ));
+}
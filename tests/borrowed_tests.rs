@@ -0,0 +1,158 @@
+/*
+World-Schema DEFINITIONS IMPLICIT TAGS ::=
+BEGIN
+Record ::= SEQUENCE {
+  algorithm OBJECT IDENTIFIER,
+  name UTF8String,
+  key BIT STRING,
+  data [0] OCTET STRING,
+  extra ANY,
+  comment [1] EXPLICIT IA5String OPTIONAL
+}
+END
+*/
+use encoding_asn1::unmarshal::ErrorKind;
+use encoding_asn1::{
+    BitString, BitStringRef, Marshal, ObjectIdentifierRef, RawValue, RawValueRef, Unmarshal,
+    Unmarshaler,
+};
+use std::borrow::Cow;
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+struct Record<'a> {
+    algorithm: ObjectIdentifierRef<'a>,
+    name: &'a str,
+    key: BitStringRef<'a>,
+    #[asn1(tag = 0)]
+    data: &'a [u8],
+    extra: RawValueRef<'a>,
+    #[asn1(explicit, tag = 1, ia5)]
+    comment: Option<&'a str>,
+}
+
+// within reports whether s lies inside bytes.
+fn within(s: &[u8], bytes: &[u8]) -> bool {
+    let range = bytes.as_ptr_range();
+    range.start <= s.as_ptr() && s.as_ptr_range().end <= range.end
+}
+
+#[test]
+fn record() {
+    let bytes = [
+        0x30, 0x1c, // Record
+        0x06, 0x06, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, // 1.2.840.113549
+        0x0c, 0x02, b'h', b'i', // name
+        0x03, 0x02, 0x04, 0xf0, // key
+        0x80, 0x02, 0x01, 0x02, // data
+        0x02, 0x01, 0x07, // extra
+        0xa1, 0x03, 0x16, 0x01, b'!', // comment
+    ];
+    let (record, rest) = encoding_asn1::unmarshal::<Record>(&bytes).unwrap();
+    assert!(rest.is_empty());
+
+    assert_eq!(record.algorithm.to_string(), "1.2.840.113549");
+    assert_eq!(
+        record.algorithm.arcs().collect::<Vec<_>>(),
+        vec![1, 2, 840, 113549]
+    );
+    assert_eq!(record.name, "hi");
+    assert_eq!(record.key.bit_length, 4);
+    assert_eq!(record.key.at(0), 1);
+    assert_eq!(record.key.at(4), 0);
+    assert_eq!(record.key.at(8), 0);
+    assert_eq!(record.key.right_align(), vec![0x0f]);
+    assert_eq!(record.data, &[0x01, 0x02]);
+    assert_eq!(record.extra.tag, 2);
+    assert_eq!(record.extra.full_bytes, &[0x02, 0x01, 0x07]);
+    assert_eq!(record.comment, Some("!"));
+
+    // Every field points into the input.
+    for field in [
+        record.algorithm.as_bytes(),
+        record.name.as_bytes(),
+        record.key.bytes,
+        record.data,
+        record.extra.bytes,
+        record.extra.full_bytes,
+        record.comment.unwrap().as_bytes(),
+    ] {
+        assert!(within(field, &bytes), "{:02x?}", field);
+    }
+
+    assert_eq!(encoding_asn1::marshal(&record).unwrap(), bytes);
+}
+
+#[test]
+fn bit_string_at() {
+    // bit_length claims more bits than bytes holds.
+    let bs = BitStringRef {
+        bytes: &[0xff],
+        bit_length: 16,
+    };
+    assert_eq!(bs.at(7), 1);
+    assert_eq!(bs.at(8), 0);
+    assert_eq!(bs.at(100), 0);
+    assert_eq!(BitString::from(bs).at(12), 0);
+}
+
+#[test]
+fn constructed_strings() {
+    // A constructed string has to be reassembled, so it can't be borrowed.
+    let bytes = [0x24, 0x06, 0x04, 0x01, 0x01, 0x04, 0x01, 0x02];
+    let err = encoding_asn1::unmarshal_ber::<&[u8]>(&bytes).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::InvalidValue(_)));
+
+    let (data, _) = encoding_asn1::unmarshal_ber::<Cow<[u8]>>(&bytes).unwrap();
+    assert!(matches!(data, Cow::Owned(_)));
+    assert_eq!(data, &[0x01, 0x02][..]);
+
+    let bytes = [0x0c, 0x02, b'h', b'i'];
+    let (name, _) = encoding_asn1::unmarshal::<Cow<str>>(&bytes).unwrap();
+    assert!(matches!(name, Cow::Borrowed("hi")));
+}
+
+#[test]
+fn raw_values() {
+    let bytes = [0x30, 0x03, 0x02, 0x01, 0x07];
+    let (rv, _) = RawValueRef::unmarshal(&bytes).unwrap();
+    assert_eq!(rv.bytes, &bytes[2..]);
+    assert!(within(rv.full_bytes, &bytes));
+
+    let owned = RawValue::from(rv);
+    assert_eq!(RawValueRef::from(&owned), rv);
+    assert_eq!(encoding_asn1::marshal(&owned).unwrap(), bytes);
+}
+
+#[test]
+fn malformed() {
+    // Non-zero padding bits are only allowed under BER.
+    let bytes = [0x03, 0x02, 0x04, 0xf1];
+    let err = encoding_asn1::unmarshal::<BitStringRef>(&bytes).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::NonCanonical("BIT STRING padding"));
+    assert_eq!(
+        encoding_asn1::unmarshal_ber::<BitStringRef>(&bytes)
+            .unwrap()
+            .0
+            .bit_length,
+        4
+    );
+
+    for bytes in [
+        &[0x03, 0x00][..],
+        &[0x03, 0x01, 0x01],
+        &[0x03, 0x02, 0x08, 0x00],
+        &[0x06, 0x00],
+        &[0x06, 0x02, 0x2a, 0x86],
+        &[0x06, 0x02, 0x80, 0x01],
+    ] {
+        assert!(
+            encoding_asn1::unmarshal::<BitStringRef>(bytes).is_err()
+                && encoding_asn1::unmarshal::<ObjectIdentifierRef>(bytes).is_err(),
+            "{:02x?}",
+            bytes
+        );
+    }
+
+    let err = encoding_asn1::unmarshal::<&str>(&[0x0c, 0x01, 0xff]).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::InvalidValue(_)));
+}
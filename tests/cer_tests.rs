@@ -0,0 +1,192 @@
+/*
+World-Schema DEFINITIONS IMPLICIT TAGS ::=
+BEGIN
+Document ::= SEQUENCE {
+  version INTEGER,
+  body [0] EXPLICIT Body,
+  ids SET OF INTEGER
+}
+Options ::= SET {
+  name [1] EXPLICIT UTF8String,
+  retries [0] INTEGER
+}
+Body ::= CHOICE {
+  text [0] UTF8String,
+  data [1] OCTET STRING
+}
+END
+*/
+use encoding_asn1::unmarshal::ErrorKind;
+use encoding_asn1::{BitString, Marshal, SetOf, Unmarshal};
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+struct Document {
+    version: i32,
+    #[asn1(explicit, tag = 0)]
+    body: Body,
+    ids: SetOf<i32>,
+}
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+enum Body {
+    #[asn1(tag = 0)]
+    Text(String),
+    #[asn1(tag = 1)]
+    Data(Vec<u8>),
+}
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+#[asn1(set)]
+struct Options {
+    #[asn1(explicit, tag = 1)]
+    name: String,
+    #[asn1(tag = 0)]
+    retries: i32,
+}
+
+fn kind<'a, T: encoding_asn1::Unmarshaler<'a>>(bytes: &'a [u8]) -> ErrorKind {
+    match encoding_asn1::unmarshal_cer::<T>(bytes) {
+        Ok(_) => panic!("{:02x?} decoded", bytes),
+        Err(e) => e.kind().clone(),
+    }
+}
+
+#[test]
+fn constructed() {
+    let doc = Document {
+        version: 1,
+        body: Body::Text("hi".to_string()),
+        ids: SetOf(vec![1, 2]),
+    };
+    let bytes = encoding_asn1::marshal_cer(&doc).unwrap();
+    assert_eq!(
+        bytes,
+        vec![
+            0x30, 0x80, // Document
+            0x02, 0x01, 0x01, // version
+            0xa0, 0x80, // [0] EXPLICIT
+            0xa0, 0x80, 0x0c, 0x02, b'h', b'i', 0x00, 0x00, // text
+            0x00, 0x00, // end of [0] EXPLICIT
+            0x31, 0x80, 0x02, 0x01, 0x01, 0x02, 0x01, 0x02, 0x00, 0x00, // ids
+            0x00, 0x00, // end of Document
+        ]
+    );
+
+    assert_eq!(
+        encoding_asn1::unmarshal_cer::<Document>(&bytes).unwrap().0,
+        doc
+    );
+    assert_eq!(
+        encoding_asn1::unmarshal_ber::<Document>(&bytes).unwrap().0,
+        doc
+    );
+    assert!(encoding_asn1::unmarshal::<Document>(&bytes).is_err());
+
+    // The DER encoding isn't CER.
+    let der = encoding_asn1::marshal(&doc).unwrap();
+    assert_eq!(
+        kind::<Document>(&der),
+        ErrorKind::NonCanonical("definite length of constructed encoding")
+    );
+}
+
+#[test]
+fn strings() {
+    let data: Vec<u8> = (0..2500).map(|i| i as u8).collect();
+    let bytes = encoding_asn1::marshal_cer(&data).unwrap();
+    assert_eq!(&bytes[..2], &[0x24, 0x80]);
+    assert_eq!(&bytes[2..6], &[0x04, 0x82, 0x03, 0xe8]);
+    assert_eq!(&bytes[1006..1010], &[0x04, 0x82, 0x03, 0xe8]);
+    assert_eq!(&bytes[2010..2014], &[0x04, 0x82, 0x01, 0xf4]);
+    assert_eq!(&bytes[2514..], &[0x00, 0x00]);
+    assert_eq!(
+        encoding_asn1::unmarshal_cer::<Vec<u8>>(&bytes).unwrap().0,
+        data
+    );
+    assert_eq!(
+        encoding_asn1::unmarshal_ber::<Vec<u8>>(&bytes).unwrap().0,
+        data
+    );
+
+    // Exactly 1000 octets is primitive.
+    let data = vec![0x61; 1000];
+    let bytes = encoding_asn1::marshal_cer(&data).unwrap();
+    assert_eq!(bytes, encoding_asn1::marshal(&data).unwrap());
+
+    // 1001 octets in one primitive encoding.
+    let data = vec![0x61; 1001];
+    let der = encoding_asn1::marshal(&data).unwrap();
+    assert_eq!(
+        kind::<Vec<u8>>(&der),
+        ErrorKind::NonCanonical("string segmentation")
+    );
+
+    // A constructed encoding of a short string.
+    let bytes = [0x24, 0x80, 0x04, 0x01, 0x61, 0x00, 0x00];
+    assert_eq!(
+        kind::<Vec<u8>>(&bytes),
+        ErrorKind::NonCanonical("string segmentation")
+    );
+
+    // Segments other than the last must be 1000 octets.
+    let mut bytes = vec![0x2c, 0x80, 0x04, 0x82, 0x01, 0xf4];
+    bytes.extend(vec![0x61; 500]);
+    bytes.extend([0x04, 0x82, 0x02, 0x58]);
+    bytes.extend(vec![0x61; 600]);
+    bytes.extend([0x00, 0x00]);
+    assert_eq!(
+        kind::<String>(&bytes),
+        ErrorKind::NonCanonical("string segmentation")
+    );
+    assert_eq!(
+        encoding_asn1::unmarshal_ber::<String>(&bytes).unwrap().0,
+        "a".repeat(1100)
+    );
+}
+
+#[test]
+fn bit_strings() {
+    // Segments of 1000 octets, each starting with its padding.
+    let bs = BitString {
+        bytes: (0..1500).map(|i| i as u8 & 0xf0).collect(),
+        bit_length: 1500 * 8 - 4,
+    };
+    let bytes = encoding_asn1::marshal_cer(&bs).unwrap();
+    assert_eq!(&bytes[..2], &[0x23, 0x80]);
+    assert_eq!(&bytes[2..7], &[0x03, 0x82, 0x03, 0xe8, 0x00]);
+    assert_eq!(&bytes[1006..1011], &[0x03, 0x82, 0x01, 0xf6, 0x04]);
+    assert_eq!(
+        encoding_asn1::unmarshal_cer::<BitString>(&bytes).unwrap().0,
+        bs
+    );
+
+    // 999 octets of bits fit in a primitive encoding.
+    let bs = BitString {
+        bytes: vec![0xff; 999],
+        bit_length: 999 * 8,
+    };
+    let bytes = encoding_asn1::marshal_cer(&bs).unwrap();
+    assert_eq!(bytes, encoding_asn1::marshal(&bs).unwrap());
+    assert_eq!(
+        encoding_asn1::unmarshal_cer::<BitString>(&bytes).unwrap().0,
+        bs
+    );
+}
+
+#[test]
+fn set() {
+    let options = Options {
+        name: "a".to_string(),
+        retries: 3,
+    };
+    let bytes = encoding_asn1::marshal_cer(&options).unwrap();
+    // The members are in the order of their tags.
+    assert_eq!(
+        bytes,
+        vec![0x31, 0x80, 0x80, 0x01, 0x03, 0xa1, 0x80, 0x0c, 0x01, 0x61, 0x00, 0x00, 0x00, 0x00]
+    );
+    assert_eq!(
+        encoding_asn1::unmarshal_cer::<Options>(&bytes).unwrap().0,
+        options
+    );
+}
@@ -0,0 +1,194 @@
+/*
+World-Schema DEFINITIONS IMPLICIT TAGS ::=
+BEGIN
+Nothing ::= NULL
+Point ::= SEQUENCE {
+  x INTEGER,
+  y INTEGER
+}
+Message ::= SEQUENCE {
+  a [APPLICATION 3] Point,
+  b [PRIVATE 1] EXPLICIT Point,
+  c [APPLICATION 4] Choice,
+  d [PRIVATE 2] OCTET STRING
+}
+Choice ::= CHOICE {
+  x [APPLICATION 0] INTEGER,
+  y [PRIVATE 0] UTF8String
+}
+END
+*/
+use encoding_asn1::common::{self, FieldParameters};
+use encoding_asn1::{Marshal, Marshaler, SequenceOf, Unmarshal, Unmarshaler, UnmarshalerOwned};
+use std::fmt::Debug;
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+#[asn1(null)]
+struct Nothing;
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+#[asn1(set)]
+struct PointSet {
+    #[asn1(tag = 0)]
+    x: i32,
+    #[asn1(tag = 1)]
+    y: i32,
+}
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+enum Choice {
+    #[asn1(application, tag = 0)]
+    X(i32),
+    #[asn1(private, tag = 0)]
+    Y(String),
+}
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+struct Message {
+    #[asn1(application, tag = 3)]
+    a: Point,
+    #[asn1(private, explicit, tag = 1)]
+    b: Point,
+    #[asn1(application, tag = 4)]
+    c: Choice,
+    #[asn1(private, tag = 2)]
+    d: Vec<u8>,
+}
+
+const CLASSES: [(i32, bool, bool); 3] = [
+    (common::CLASS_APPLICATION, true, false),
+    (common::CLASS_CONTEXT_SPECIFIC, false, false),
+    (common::CLASS_PRIVATE, false, true),
+];
+
+// check marshals and unmarshals v untagged and then with IMPLICIT and
+// EXPLICIT tags of every class, checking the identifier octets.
+fn check<T>(v: T, universal_tag: u8, is_compound: bool)
+where
+    T: Marshaler + UnmarshalerOwned + PartialEq + Debug,
+{
+    let compound = if is_compound { 0x20 } else { 0 };
+
+    let bytes = v.marshal().unwrap();
+    assert_eq!(bytes[0], compound | universal_tag, "{:?}", v);
+    let (w, rest) = T::unmarshal(&bytes).unwrap();
+    assert_eq!(w, v);
+    assert!(rest.is_empty());
+
+    for (class, application, private) in CLASSES {
+        for explicit in [false, true] {
+            let params = FieldParameters {
+                application,
+                private,
+                explicit,
+                tag: Some(5),
+                ..Default::default()
+            };
+            let bytes = v.marshal_with_params(&params).unwrap();
+            if explicit {
+                assert_eq!(bytes[0], (class << 6) as u8 | 0x20 | 5, "{:?}", v);
+                assert_eq!(bytes[2], compound | universal_tag, "{:?}", v);
+            } else {
+                assert_eq!(bytes[0], (class << 6) as u8 | compound | 5, "{:?}", v);
+            }
+
+            let (w, rest) = T::unmarshal_with_params(&bytes, &params).unwrap();
+            assert_eq!(w, v);
+            assert!(rest.is_empty());
+
+            // Neither untagged nor another class is accepted.
+            assert!(T::unmarshal(&bytes).is_err(), "{:?}", v);
+            let other = FieldParameters {
+                application: !application,
+                private: false,
+                ..params
+            };
+            assert!(T::unmarshal_with_params(&bytes, &other).is_err(), "{:?}", v);
+        }
+    }
+}
+
+#[test]
+fn primitives() {
+    check(-129, common::TAG_INTEGER as u8, false);
+    check(vec![1, 2, 3], common::TAG_OCTET_STRING as u8, false);
+    check("hello".to_string(), common::TAG_UTF8_STRING as u8, false);
+    check(Nothing, common::TAG_NULL as u8, false);
+}
+
+#[test]
+fn constructed() {
+    check(Point { x: 1, y: 2 }, common::TAG_SEQUENCE as u8, true);
+    check(PointSet { x: 1, y: 2 }, common::TAG_SET as u8, true);
+    check(SequenceOf(vec![1, 2]), common::TAG_SEQUENCE as u8, true);
+}
+
+#[test]
+fn raw_value() {
+    let rv = encoding_asn1::RawValue {
+        class: common::CLASS_UNIVERSAL,
+        tag: common::TAG_INTEGER,
+        is_compound: false,
+        bytes: vec![0x07],
+        full_bytes: vec![],
+    };
+    for (class, application, private) in CLASSES {
+        let params = FieldParameters {
+            application,
+            private,
+            tag: Some(5),
+            ..Default::default()
+        };
+        let bytes = rv.marshal_with_params(&params).unwrap();
+        assert_eq!(bytes, vec![(class << 6) as u8 | 5, 0x01, 0x07]);
+
+        let params = FieldParameters {
+            explicit: true,
+            ..params
+        };
+        let bytes = rv.marshal_with_params(&params).unwrap();
+        assert_eq!(
+            bytes,
+            vec![(class << 6) as u8 | 0x20 | 5, 0x03, 0x02, 0x01, 0x07]
+        );
+        let (w, _) = encoding_asn1::RawValue::unmarshal_with_params(&bytes, &params).unwrap();
+        assert_eq!(w.tag, common::TAG_INTEGER);
+        assert_eq!(w.bytes, vec![0x07]);
+        assert_eq!(w.full_bytes, vec![0x02, 0x01, 0x07]);
+    }
+}
+
+#[test]
+fn derived() {
+    let m = Message {
+        a: Point { x: 1, y: 2 },
+        b: Point { x: 3, y: 4 },
+        c: Choice::Y("z".to_string()),
+        d: vec![5],
+    };
+    let bytes = encoding_asn1::marshal(&m).unwrap();
+    assert_eq!(
+        bytes,
+        vec![
+            0x30, 0x1c, //
+            0x63, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x02, //
+            0xe1, 0x08, 0x30, 0x06, 0x02, 0x01, 0x03, 0x02, 0x01, 0x04, //
+            0x64, 0x05, 0xe0, 0x03, 0x0c, 0x01, 0x7a, //
+            0xc2, 0x01, 0x05,
+        ]
+    );
+    let (n, _) = encoding_asn1::unmarshal::<Message>(&bytes).unwrap();
+    assert_eq!(n, m);
+
+    let c = Choice::X(6);
+    let bytes = encoding_asn1::marshal(&c).unwrap();
+    assert_eq!(bytes, vec![0x60, 0x03, 0x02, 0x01, 0x06]);
+    let (d, _) = encoding_asn1::unmarshal::<Choice>(&bytes).unwrap();
+    assert_eq!(d, c);
+}
@@ -0,0 +1,146 @@
+/*
+World-Schema DEFINITIONS IMPLICIT TAGS ::=
+BEGIN
+Request ::= SEQUENCE {
+  id INTEGER,
+  name UTF8String
+}
+Response ::= SEQUENCE {
+  id INTEGER,
+  ok BOOLEAN
+}
+END
+*/
+#![cfg(feature = "codec")]
+use encoding_asn1::codec::Codec;
+use encoding_asn1::common::EncodingRules;
+use encoding_asn1::stream;
+use encoding_asn1::{Marshal, Unmarshal};
+use futures_util::{SinkExt, StreamExt};
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::codec::{Decoder, Framed};
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+struct Request {
+    id: i32,
+    name: String,
+}
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+struct Response {
+    id: i32,
+    ok: bool,
+}
+
+#[tokio::test]
+async fn loopback() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut framed = Framed::new(socket, Codec::<Request>::new());
+        while let Some(request) = framed.next().await {
+            let request = request.unwrap();
+            let response = Response {
+                id: request.id,
+                ok: !request.name.is_empty(),
+            };
+            framed.send(response).await.unwrap();
+        }
+    });
+
+    let socket = TcpStream::connect(addr).await.unwrap();
+    let mut framed = Framed::new(socket, Codec::<Response>::new());
+    for (id, name) in [(1, "a"), (2, ""), (3, "long name")] {
+        let request = Request {
+            id,
+            name: name.to_string(),
+        };
+        framed.send(&request).await.unwrap();
+        assert_eq!(
+            framed.next().await.unwrap().unwrap(),
+            Response {
+                id,
+                ok: !name.is_empty()
+            }
+        );
+    }
+    drop(framed);
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn split_writes() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let bytes = encoding_asn1::marshal(&Request {
+        id: 7,
+        name: "x".repeat(200),
+    })
+    .unwrap();
+    let client = tokio::spawn(async move {
+        let mut socket = TcpStream::connect(addr).await.unwrap();
+        // The message arrives in pieces, the header split in two.
+        for chunk in [&bytes[..1], &bytes[1..3], &bytes[3..100], &bytes[100..]] {
+            socket.write_all(chunk).await.unwrap();
+            socket.flush().await.unwrap();
+            tokio::task::yield_now().await;
+        }
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    let mut framed = Framed::new(socket, Codec::<Request>::new());
+    let request = framed.next().await.unwrap().unwrap();
+    assert_eq!(request.id, 7);
+    assert_eq!(request.name.len(), 200);
+    assert!(framed.next().await.is_none());
+    client.await.unwrap();
+}
+
+#[tokio::test]
+async fn frame_too_large() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let client = tokio::spawn(async move {
+        let mut socket = TcpStream::connect(addr).await.unwrap();
+        // A header claiming 2 GB of contents, none of which are sent.
+        socket
+            .write_all(&[0x30, 0x84, 0x7f, 0xff, 0xff, 0xff])
+            .await
+            .unwrap();
+        socket
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    let codec = Codec::<Request>::new().with_rules(EncodingRules::Ber);
+    let mut framed = Framed::new(socket, codec.with_max_frame_length(1024));
+    match framed.next().await.unwrap() {
+        Err(stream::Error::FrameTooLarge { max, .. }) => assert_eq!(max, 1024),
+        other => panic!("{:?}", other),
+    }
+    drop(client.await.unwrap());
+}
+
+#[test]
+fn decode() {
+    let mut codec = Codec::<Request>::new();
+    let bytes = encoding_asn1::marshal(&Request {
+        id: 1,
+        name: "a".to_string(),
+    })
+    .unwrap();
+    let mut buf = bytes::BytesMut::from(&bytes[..4]);
+    assert!(codec.decode(&mut buf).unwrap().is_none());
+    // A stream that ends in the middle of a frame.
+    assert!(matches!(
+        codec.decode_eof(&mut buf),
+        Err(stream::Error::Decode(_))
+    ));
+
+    buf.extend_from_slice(&bytes[4..]);
+    buf.extend_from_slice(&[0x05, 0x00]);
+    // A NULL is not a Request.
+    assert!(codec.decode(&mut buf).unwrap().is_some());
+    assert!(codec.decode(&mut buf).is_err());
+}
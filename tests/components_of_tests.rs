@@ -0,0 +1,93 @@
+/*
+World-Schema DEFINITIONS IMPLICIT TAGS ::=
+BEGIN
+Header ::= SEQUENCE {
+  version INTEGER,
+  id INTEGER
+}
+Message ::= SEQUENCE {
+  COMPONENTS OF Header,
+  num INTEGER
+}
+END
+*/
+use encoding_asn1::{common, marshal, unmarshal, Marshal, Unmarshal, Unmarshaler};
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+struct Header {
+    version: i32,
+    id: i32,
+}
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+struct Message {
+    #[asn1(components_of)]
+    header: Header,
+    num: i32,
+}
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+struct Nested {
+    #[asn1(flatten)]
+    message: Message,
+    #[asn1(tag = 0)]
+    extra: i32,
+}
+
+#[test]
+fn it_works() {
+    let m = Message {
+        header: Header { version: 1, id: 2 },
+        num: 3,
+    };
+    let bytes = encoding_asn1::marshal(&m).unwrap();
+    assert_eq!(
+        bytes,
+        vec![0x30, 0x09, 0x02, 0x01, 0x01, 0x02, 0x01, 0x02, 0x02, 0x01, 0x03]
+    );
+    let (n, rest) = encoding_asn1::unmarshal::<Message>(&bytes).unwrap();
+    assert_eq!(n, m);
+    assert!(rest.is_empty());
+
+    let m = Nested {
+        message: m,
+        extra: 4,
+    };
+    let bytes = encoding_asn1::marshal(&m).unwrap();
+    assert_eq!(
+        bytes,
+        vec![0x30, 0x0C, 0x02, 0x01, 0x01, 0x02, 0x01, 0x02, 0x02, 0x01, 0x03, 0x80, 0x01, 0x04]
+    );
+    let (n, _) = encoding_asn1::unmarshal::<Nested>(&bytes).unwrap();
+    assert_eq!(n, m);
+}
+
+#[test]
+fn components_of_non_sequence() {
+    assert!(
+        i32::unmarshal_components(&[0x02, 0x01, 0x01], &common::FieldParameters::default())
+            .is_err()
+    );
+}
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+struct Flattened {
+    #[asn1(flatten)]
+    x: i32,
+    y: i32,
+}
+
+#[test]
+fn flatten_non_sequence() {
+    // The members of an INTEGER can't be inlined, whichever way round.
+    let f = Flattened { x: 5, y: 6 };
+    assert!(matches!(
+        encoding_asn1::marshal(&f),
+        Err(marshal::Error::Structural(_))
+    ));
+    let bytes = [0x30, 0x06, 0x02, 0x01, 0x05, 0x02, 0x01, 0x06];
+    assert!(matches!(
+        encoding_asn1::unmarshal::<Flattened>(&bytes).map_err(|e| e.kind().clone()),
+        Err(unmarshal::ErrorKind::Structural(_))
+    ));
+}
@@ -0,0 +1,123 @@
+/*
+World-Schema DEFINITIONS IMPLICIT TAGS ::=
+BEGIN
+Record ::= SEQUENCE {
+  port INTEGER (0..65535),
+  key OCTET STRING (SIZE(16)),
+  pin IA5String (SIZE(4..8)) (FROM("0".."9")),
+  name UTF8String (SIZE(1..64))
+}
+END
+*/
+use encoding_asn1::{common, marshal, unmarshal, Marshal, Marshaler, Unmarshal, Unmarshaler};
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+struct Record {
+    #[asn1(range(0..=65535))]
+    port: i32,
+    #[asn1(size(16))]
+    key: Vec<u8>,
+    #[asn1(ia5, size(4..=8), alphabet = "0123456789")]
+    pin: String,
+    #[asn1(size(1..65))]
+    name: String,
+}
+
+fn record() -> Record {
+    Record {
+        port: 443,
+        key: vec![0; 16],
+        pin: "1234".to_string(),
+        name: "host".to_string(),
+    }
+}
+
+#[test]
+fn it_works() {
+    let r = record();
+    let bytes = encoding_asn1::marshal(&r).unwrap();
+    assert_eq!(&bytes[..6], &[0x30, 0x22, 0x02, 0x02, 0x01, 0xbb]);
+    assert_eq!(&bytes[24..30], &[0x16, 0x04, 0x31, 0x32, 0x33, 0x34]);
+    assert_eq!(&bytes[30..], &[0x0c, 0x04, 0x68, 0x6f, 0x73, 0x74]);
+    let (n, _) = encoding_asn1::unmarshal::<Record>(&bytes).unwrap();
+    assert_eq!(n, r);
+}
+
+#[test]
+fn marshal_violations() {
+    let r = Record {
+        port: 65536,
+        ..record()
+    };
+    assert!(matches!(
+        encoding_asn1::marshal(&r),
+        Err(marshal::Error::ConstraintViolation(_))
+    ));
+
+    let r = Record {
+        key: vec![0; 15],
+        ..record()
+    };
+    assert!(matches!(
+        encoding_asn1::marshal(&r),
+        Err(marshal::Error::ConstraintViolation(_))
+    ));
+
+    let r = Record {
+        pin: "12a4".to_string(),
+        ..record()
+    };
+    match encoding_asn1::marshal(&r) {
+        Err(e) => assert_eq!(
+            e.to_string(),
+            "constraint violation: character 'a' is not in FROM(\"0123456789\")"
+        ),
+        Ok(_) => panic!("expected a constraint violation"),
+    }
+
+    let r = Record {
+        name: String::new(),
+        ..record()
+    };
+    assert!(encoding_asn1::marshal(&r).is_err());
+}
+
+#[test]
+fn unmarshal_violations() {
+    let params = common::FieldParameters {
+        constraints: common::Constraints {
+            range: Some(0..=255),
+            ..common::Constraints::default()
+        },
+        ..common::FieldParameters::default()
+    };
+    let bytes = 256.marshal().unwrap();
+    assert!(matches!(
+        i32::unmarshal_with_params(&bytes, &params).map_err(|e| e.kind().clone()),
+        Err(unmarshal::ErrorKind::ConstraintViolation(_))
+    ));
+
+    let params = common::FieldParameters {
+        constraints: common::Constraints {
+            size: Some(2..=2),
+            ..common::Constraints::default()
+        },
+        ..common::FieldParameters::default()
+    };
+    let bytes = vec![1u8, 2, 3].marshal().unwrap();
+    assert!(matches!(
+        Vec::<u8>::unmarshal_with_params(&bytes, &params).map_err(|e| e.kind().clone()),
+        Err(unmarshal::ErrorKind::ConstraintViolation(_))
+    ));
+
+    let mut r = record();
+    r.pin = "12345".to_string();
+    let mut bytes = encoding_asn1::marshal(&r).unwrap();
+    // Replace the last digit of the pin with a letter.
+    let i = bytes.iter().position(|b| *b == b'5').unwrap();
+    bytes[i] = b'x';
+    assert!(matches!(
+        encoding_asn1::unmarshal::<Record>(&bytes).map_err(|e| e.kind().clone()),
+        Err(unmarshal::ErrorKind::ConstraintViolation(_))
+    ));
+}
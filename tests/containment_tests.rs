@@ -0,0 +1,125 @@
+/*
+World-Schema DEFINITIONS IMPLICIT TAGS ::=
+BEGIN
+Point ::= SEQUENCE {
+  x INTEGER,
+  y INTEGER
+}
+Shape ::= CHOICE {
+  point [0] Point,
+  radius [1] INTEGER
+}
+Tagged ::= SEQUENCE {
+  id [2] INTEGER
+}
+END
+*/
+use encoding_asn1::unmarshal::ErrorKind;
+use encoding_asn1::{Marshal, Unmarshal};
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+enum Shape {
+    #[asn1(tag = 0)]
+    Point(Point),
+    #[asn1(tag = 1)]
+    Radius(i32),
+}
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+struct Tagged {
+    #[asn1(tag = 2)]
+    id: i32,
+}
+
+fn kind<'a, T: encoding_asn1::Unmarshaler<'a>>(bytes: &'a [u8]) -> ErrorKind {
+    match encoding_asn1::unmarshal::<T>(bytes) {
+        Ok(_) => panic!("{:02x?} decoded", bytes),
+        Err(e) => e.kind().clone(),
+    }
+}
+
+#[test]
+fn tags() {
+    assert_eq!(
+        encoding_asn1::unmarshal::<i32>(&[0x02, 0x01, 0x05])
+            .unwrap()
+            .0,
+        5
+    );
+    // OCTET STRING, context-specific and constructed INTEGER are not INTEGER.
+    for bytes in [
+        &[0x04, 0x01, 0x05][..],
+        &[0x82, 0x01, 0x05],
+        &[0x22, 0x03, 0x02, 0x01, 0x05],
+    ] {
+        assert!(
+            matches!(kind::<i32>(bytes), ErrorKind::UnexpectedTag { .. }),
+            "{:02x?}",
+            bytes
+        );
+    }
+
+    // A SEQUENCE must be constructed.
+    assert!(matches!(
+        kind::<Point>(&[0x10, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x02]),
+        ErrorKind::UnexpectedTag { .. }
+    ));
+
+    // An IMPLICIT tag replaces the UNIVERSAL one.
+    assert_eq!(
+        encoding_asn1::unmarshal::<Tagged>(&[0x30, 0x03, 0x82, 0x01, 0x07])
+            .unwrap()
+            .0,
+        Tagged { id: 7 }
+    );
+    assert!(matches!(
+        kind::<Tagged>(&[0x30, 0x03, 0x02, 0x01, 0x07]),
+        ErrorKind::UnexpectedTag { .. }
+    ));
+
+    // A CHOICE alternative is tagged EXPLICIT, so must be constructed.
+    for bytes in [
+        &[0x80, 0x03, 0x02, 0x01, 0x05][..],
+        &[0x81, 0x03, 0x02, 0x01, 0x05],
+    ] {
+        assert!(
+            matches!(kind::<Shape>(bytes), ErrorKind::UnexpectedTag { .. }),
+            "{:02x?}",
+            bytes
+        );
+    }
+}
+
+#[test]
+fn containment() {
+    // y claims more bytes than the SEQUENCE holds, though the input has them.
+    let bytes = [0x30, 0x05, 0x02, 0x01, 0x01, 0x02, 0x02, 0x00, 0x02];
+    assert_eq!(kind::<Point>(&bytes), ErrorKind::Truncated);
+
+    // Leftover bytes inside a SEQUENCE.
+    let bytes = [0x30, 0x08, 0x02, 0x01, 0x01, 0x02, 0x01, 0x02, 0x05, 0x00];
+    let err = encoding_asn1::unmarshal::<Point>(&bytes).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::TrailingData);
+    assert_eq!(err.offset(), Some(8));
+    assert_eq!(err.path(), "Point");
+
+    // Leftover bytes inside a CHOICE alternative.
+    let bytes = [0xa1, 0x05, 0x02, 0x01, 0x03, 0x05, 0x00];
+    let err = encoding_asn1::unmarshal::<Shape>(&bytes).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::TrailingData);
+    assert_eq!(err.offset(), Some(5));
+    assert_eq!(err.path(), "Shape.Radius");
+
+    assert_eq!(
+        encoding_asn1::unmarshal::<Shape>(&[0xa1, 0x03, 0x02, 0x01, 0x03])
+            .unwrap()
+            .0,
+        Shape::Radius(3)
+    );
+}
@@ -0,0 +1,141 @@
+/*
+World-Schema DEFINITIONS IMPLICIT TAGS ::=
+BEGIN
+Request ::= SEQUENCE {
+  num INTEGER,
+  data OCTET STRING
+}
+Body ::= CHOICE {
+  request [0] Request,
+  ping [1] INTEGER
+}
+Message ::= SEQUENCE {
+  version INTEGER,
+  body Body
+}
+END
+*/
+use encoding_asn1::common::{Identifier, TAG_INTEGER, TAG_OCTET_STRING};
+use encoding_asn1::unmarshal::ErrorKind;
+use encoding_asn1::{Marshal, Unmarshal};
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+struct Request {
+    num: i32,
+    data: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+enum Body {
+    #[asn1(tag = 0)]
+    Request(Request),
+    #[asn1(tag = 1)]
+    Ping(i32),
+}
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+struct Message {
+    version: i32,
+    body: Body,
+}
+
+fn message() -> Vec<u8> {
+    encoding_asn1::marshal(&Message {
+        version: 1,
+        body: Body::Request(Request {
+            num: 7,
+            data: vec![0xaa],
+        }),
+    })
+    .unwrap()
+}
+
+#[test]
+fn path_and_offset() {
+    // 30 0d 02 01 01 a0 08 30 06 02 01 07 04 01 aa
+    let mut bytes = message();
+    assert_eq!(bytes[9], 0x02);
+    // Replace the INTEGER num with an OCTET STRING.
+    bytes[9] = 0x04;
+
+    let err = encoding_asn1::unmarshal::<Message>(&bytes).unwrap_err();
+    assert_eq!(
+        err.kind(),
+        &ErrorKind::UnexpectedTag {
+            expected: Some(Identifier::universal(TAG_INTEGER, false)),
+            found: Identifier::universal(TAG_OCTET_STRING, false),
+        }
+    );
+    assert_eq!(err.offset(), Some(9));
+    assert_eq!(err.path(), "Message.body.Request.num");
+    assert_eq!(
+        err.to_string(),
+        "unexpected tag [UNIVERSAL 4], expected [UNIVERSAL 2] at offset 9 in Message.body.Request.num\n  \
+         0001: 0d 02 01 01 a0 08 30 06 04 01 07 04 01 aa\n                                ^^"
+    );
+
+    // The same error found in another copy of the input reads the same.
+    let copy = bytes.clone();
+    let other = encoding_asn1::unmarshal::<Message>(&copy).unwrap_err();
+    assert_eq!(format!("{:?}", err), format!("{:?}", other));
+    assert!(format!("{:?}", err).contains("offset: Some(9)"));
+}
+
+#[test]
+fn truncated() {
+    let bytes = message();
+    let err = encoding_asn1::unmarshal::<Message>(&bytes[..bytes.len() - 1]).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::Truncated);
+    // The offset is where the input ran out.
+    assert_eq!(err.offset(), Some(14));
+    assert_eq!(err.path(), "Message");
+
+    let err = encoding_asn1::unmarshal::<i32>(&[0x02]).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::Truncated);
+    assert_eq!(err.path(), "");
+}
+
+#[test]
+fn kinds() {
+    let err = encoding_asn1::unmarshal::<i32>(&[0x02, 0x02, 0x00, 0x01]).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::NonMinimal(_)));
+    assert_eq!(err.offset(), Some(2));
+
+    let err = encoding_asn1::unmarshal::<i32>(&[0x02, 0x80, 0x01, 0x00, 0x00]).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::IndefiniteLength);
+
+    // An unknown CHOICE alternative.
+    let err = encoding_asn1::unmarshal::<Body>(&[0xa2, 0x03, 0x02, 0x01, 0x00]).unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        ErrorKind::UnexpectedTag { expected: None, .. }
+    ));
+    assert_eq!(err.path(), "Body");
+    assert_eq!(
+        err.to_string(),
+        "unexpected tag [2] constructed at offset 0 in Body\n  0000: a2 03 02 01 00\n        ^^"
+    );
+}
+
+#[test]
+fn offset_within_input() {
+    // The offset counts from the start of the input, wherever it lives.
+    let mut bytes = message();
+    bytes[9] = 0x04;
+    let mut framed = vec![0xff, 0xff];
+    framed.extend_from_slice(&bytes);
+    let err = encoding_asn1::unmarshal::<Message>(&framed[2..]).unwrap_err();
+    assert_eq!(err.offset(), Some(9));
+    assert_eq!(err.path(), "Message.body.Request.num");
+
+    // An error in a segment of a constructed string, which is reassembled
+    // into a buffer of its own, is at the segment.
+    // 30 0d 02 01 07 24 80 04 01 61 02 01 62 00 00
+    let bytes = [
+        0x30, 0x0d, 0x02, 0x01, 0x07, 0x24, 0x80, 0x04, 0x01, 0x61, 0x02, 0x01, 0x62, 0x00, 0x00,
+    ];
+    let err = encoding_asn1::unmarshal::unmarshal_ber::<Request>(&bytes).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::UnexpectedTag { .. }));
+    assert_eq!(err.offset(), Some(10));
+    assert_eq!(err.path(), "Request.data");
+}
@@ -0,0 +1,103 @@
+/*
+World-Schema DEFINITIONS IMPLICIT TAGS ::=
+BEGIN
+Certificate ::= SEQUENCE {
+  serial INTEGER,
+  subject UTF8String
+}
+END
+*/
+use encoding_asn1::unmarshal::ErrorKind;
+use encoding_asn1::{Marshal, Unmarshal, Unmarshaler};
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+struct Certificate {
+    serial: i32,
+    subject: String,
+}
+
+fn certificate(serial: i32) -> Certificate {
+    Certificate {
+        serial,
+        subject: format!("host{}", serial),
+    }
+}
+
+fn bundle(n: i32) -> Vec<u8> {
+    (1..=n)
+        .flat_map(|i| encoding_asn1::marshal(&certificate(i)).unwrap())
+        .collect()
+}
+
+#[test]
+fn exact() {
+    let bytes = encoding_asn1::marshal(&certificate(1)).unwrap();
+    assert_eq!(Certificate::from_der(&bytes).unwrap(), certificate(1));
+    assert_eq!(
+        encoding_asn1::unmarshal_exact::<Certificate>(&bytes).unwrap(),
+        certificate(1)
+    );
+
+    // unmarshal hands back what follows the value, unmarshal_exact rejects it.
+    let mut padded = bytes.clone();
+    padded.push(0x00);
+    assert_eq!(
+        encoding_asn1::unmarshal::<Certificate>(&padded).unwrap().1,
+        &[0x00]
+    );
+    let err = Certificate::from_der(&padded).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::TrailingData);
+    assert_eq!(err.offset(), Some(bytes.len()));
+    assert!(err.to_string().starts_with("trailing data at offset 12"));
+
+    let err = encoding_asn1::unmarshal_exact::<Certificate>(&bundle(2)).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::TrailingData);
+}
+
+#[test]
+fn many() {
+    let bytes = bundle(3);
+    let certificates: Vec<Certificate> = encoding_asn1::unmarshal_many(&bytes)
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        certificates,
+        vec![certificate(1), certificate(2), certificate(3)]
+    );
+    assert_eq!(encoding_asn1::unmarshal_many::<Certificate>(&[]).count(), 0);
+
+    // An error in the third value is located within the whole bundle, and
+    // ends the iteration.
+    let mut bytes = bundle(3);
+    let third = bytes.len() - 12;
+    assert_eq!(bytes[third + 2], 0x02);
+    bytes[third + 2] = 0x04;
+    let mut many = encoding_asn1::unmarshal_many::<Certificate>(&bytes);
+    assert!(many.next().unwrap().is_ok());
+    assert!(many.next().unwrap().is_ok());
+    let err = many.next().unwrap().unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::UnexpectedTag { .. }));
+    assert_eq!(err.offset(), Some(third + 2));
+    assert_eq!(err.path(), "Certificate.serial");
+    assert!(many.next().is_none());
+    assert_eq!(many.remaining().len(), 12);
+
+    // A bundle cut short.
+    let bytes = bundle(2);
+    let mut many = encoding_asn1::unmarshal_many::<Certificate>(&bytes[..bytes.len() - 1]);
+    assert!(many.next().unwrap().is_ok());
+    assert_eq!(
+        many.next().unwrap().unwrap_err().kind(),
+        &ErrorKind::Truncated
+    );
+}
+
+#[test]
+fn empty_values() {
+    // An absent OPTIONAL value takes up no bytes, so it can't be iterated.
+    let mut many = encoding_asn1::unmarshal_many::<Option<i32>>(&[0x05, 0x00]);
+    assert!(matches!(
+        many.next().unwrap().unwrap_err().kind(),
+        ErrorKind::Structural(_)
+    ));
+}
@@ -24,7 +24,7 @@ value Message ::= {
   }
 }
 */
-use encoding_asn1::{common, Encoder, Marshal, Marshaler};
+use encoding_asn1::Marshal;
 
 #[derive(Debug, Marshal)]
 struct Message {
@@ -58,7 +58,8 @@ fn it_works() {
         encoding_asn1::marshal(&Message {
             seq: 1,
             body: Body::Request(Request { num: 1 })
-        }),
+        })
+        .unwrap(),
         vec![0x30, 0x0C, 0x02, 0x01, 0x01, 0xBF, 0x97, 0x38, 0x05, 0x30, 0x03, 0x02, 0x01, 0x01]
     );
 }
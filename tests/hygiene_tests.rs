@@ -0,0 +1,80 @@
+use std::borrow::Cow;
+
+// Re-exports the crate under another path, as a wrapper crate would.
+mod reexport {
+    pub use encoding_asn1 as asn1;
+}
+
+#[derive(Debug, PartialEq, encoding_asn1::Marshal, encoding_asn1::Unmarshal)]
+struct Wrapper<T> {
+    id: i32,
+    value: T,
+}
+
+#[derive(Debug, PartialEq, reexport::asn1::Marshal, reexport::asn1::Unmarshal)]
+#[asn1(crate = "reexport::asn1")]
+enum Body<T> {
+    #[asn1(tag = 0)]
+    Request(Wrapper<T>),
+}
+
+mod name {
+    use encoding_asn1::{
+        common, marshal,
+        unmarshal::{Error, ErrorKind},
+        Marshaler, Unmarshaler,
+    };
+    use std::borrow::Cow;
+
+    pub fn marshal_with_params(
+        name: &str,
+        params: &common::FieldParameters,
+    ) -> Result<Vec<u8>, marshal::Error> {
+        name.as_bytes().to_vec().marshal_with_params(params)
+    }
+
+    pub fn unmarshal_with_params<'a, 'b>(
+        bytes: &'b [u8],
+        params: &common::FieldParameters,
+    ) -> Result<(Cow<'a, str>, &'b [u8]), Error> {
+        let (v, bytes) = Vec::<u8>::unmarshal_with_params(bytes, params)?;
+        let s = String::from_utf8(v)
+            .map_err(|e| Error::from(ErrorKind::InvalidValue(e.to_string())))?;
+        Ok((Cow::Owned(s), bytes))
+    }
+}
+
+#[derive(Debug, PartialEq, encoding_asn1::Marshal, encoding_asn1::Unmarshal)]
+struct Named<'a> {
+    #[asn1(with = "name")]
+    name: Cow<'a, str>,
+}
+
+#[test]
+fn it_works() {
+    let w = Wrapper { id: 1, value: 2 };
+    let bytes = encoding_asn1::marshal(&w).unwrap();
+    assert_eq!(bytes, vec![0x30, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x02]);
+    let (n, _) = encoding_asn1::unmarshal::<Wrapper<i32>>(&bytes).unwrap();
+    assert_eq!(n, w);
+
+    let b = Body::Request(Wrapper {
+        id: 1,
+        value: vec![0x05u8],
+    });
+    let bytes = encoding_asn1::marshal(&b).unwrap();
+    assert_eq!(
+        bytes,
+        vec![0xa0, 0x08, 0x30, 0x06, 0x02, 0x01, 0x01, 0x04, 0x01, 0x05]
+    );
+    let (n, _) = encoding_asn1::unmarshal::<Body<Vec<u8>>>(&bytes).unwrap();
+    assert_eq!(n, b);
+
+    let n = Named {
+        name: Cow::Borrowed("hi"),
+    };
+    let bytes = encoding_asn1::marshal(&n).unwrap();
+    assert_eq!(bytes, vec![0x30, 0x04, 0x04, 0x02, 0x68, 0x69]);
+    let (m, _) = encoding_asn1::unmarshal::<Named>(&bytes).unwrap();
+    assert_eq!(m, n);
+}
@@ -0,0 +1,193 @@
+/*
+World-Schema DEFINITIONS IMPLICIT TAGS ::=
+BEGIN
+Node ::= SEQUENCE {
+  children SEQUENCE OF Node
+}
+Blobs ::= SEQUENCE {
+  a OCTET STRING,
+  b OCTET STRING,
+  names SEQUENCE OF UTF8String
+}
+END
+*/
+use encoding_asn1::common::{EncodingRules, FieldParameters, Limits};
+use encoding_asn1::stream::Decoder;
+use encoding_asn1::unmarshal::ErrorKind;
+use encoding_asn1::{Marshal, SequenceOf, Unmarshal};
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+struct Node {
+    children: SequenceOf<Node>,
+}
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+struct Blobs {
+    a: Vec<u8>,
+    b: Vec<u8>,
+    names: SequenceOf<String>,
+}
+
+fn limited(limits: Limits) -> FieldParameters {
+    FieldParameters::default().with_limits(limits)
+}
+
+fn is_limit(kind: &ErrorKind) -> bool {
+    matches!(kind, ErrorKind::LimitExceeded(_))
+}
+
+fn nested(levels: usize) -> Node {
+    let mut node = Node {
+        children: SequenceOf(vec![]),
+    };
+    for _ in 0..levels {
+        node = Node {
+            children: SequenceOf(vec![node]),
+        };
+    }
+    node
+}
+
+#[test]
+fn depth() {
+    // Each Node is two levels deep: the SEQUENCE and its SEQUENCE OF.
+    let bytes = encoding_asn1::marshal(&nested(20)).unwrap();
+    assert_eq!(
+        encoding_asn1::unmarshal::<Node>(&bytes).unwrap().0,
+        nested(20)
+    );
+
+    let limits = Limits {
+        max_depth: 30,
+        ..Limits::default()
+    };
+    let err = encoding_asn1::unmarshal_with_params::<Node>(&bytes, &limited(limits)).unwrap_err();
+    assert!(is_limit(err.kind()), "{}", err);
+    assert!(err.path().starts_with("Node.children.0.children.0"));
+
+    // Thousands of levels fail on the default limit rather than the stack.
+    let levels = 10_000;
+    let mut bytes = vec![];
+    for _ in 0..levels {
+        bytes.extend([0x30, 0x80, 0x30, 0x80]);
+    }
+    bytes.extend(vec![0x00; 4 * levels]);
+    let err = encoding_asn1::unmarshal_ber::<Node>(&bytes).unwrap_err();
+    assert!(is_limit(err.kind()), "{}", err);
+}
+
+#[test]
+fn element_length() {
+    let limits = Limits {
+        max_element_length: 4,
+        ..Limits::default()
+    };
+    let bytes = [0x04, 0x05, 0x01, 0x02, 0x03, 0x04, 0x05];
+    let err =
+        encoding_asn1::unmarshal_with_params::<Vec<u8>>(&bytes, &limited(limits)).unwrap_err();
+    assert!(is_limit(err.kind()));
+    assert_eq!(err.offset(), Some(0));
+    assert!(
+        encoding_asn1::unmarshal_with_params::<Vec<u8>>(&bytes[..6], &limited(limits)).is_err()
+    );
+
+    // A length claiming 2 GB is rejected from the header.
+    let bytes = [0x04, 0x84, 0x7f, 0xff, 0xff, 0xff, 0x00];
+    let err =
+        encoding_asn1::unmarshal_with_params::<Vec<u8>>(&bytes, &limited(limits)).unwrap_err();
+    assert!(is_limit(err.kind()));
+    assert_eq!(
+        encoding_asn1::unmarshal_ber::<Vec<u8>>(&bytes)
+            .unwrap_err()
+            .kind(),
+        &ErrorKind::Truncated
+    );
+
+    // The streaming decoder doesn't wait for the contents either.
+    let mut decoder = Decoder::new(&bytes[..]).with_limits(limits);
+    match decoder.read_tlv().unwrap_err() {
+        encoding_asn1::stream::Error::Decode(e) => assert!(is_limit(e.kind())),
+        e => panic!("{}", e),
+    }
+}
+
+#[test]
+fn elements() {
+    let limits = Limits {
+        max_elements: 2,
+        ..Limits::default()
+    };
+    let bytes = encoding_asn1::marshal(&SequenceOf(vec![1, 2, 3])).unwrap();
+    let err = encoding_asn1::unmarshal_with_params::<SequenceOf<i32>>(&bytes, &limited(limits))
+        .unwrap_err();
+    assert!(is_limit(err.kind()));
+    assert_eq!(err.offset(), Some(8));
+
+    let bytes = encoding_asn1::marshal(&SequenceOf(vec![1, 2])).unwrap();
+    assert!(
+        encoding_asn1::unmarshal_with_params::<SequenceOf<i32>>(&bytes, &limited(limits)).is_ok()
+    );
+}
+
+#[test]
+fn total_bytes() {
+    let blobs = Blobs {
+        a: vec![0xaa; 10],
+        b: vec![0xbb; 10],
+        names: SequenceOf(vec![]),
+    };
+    let bytes = encoding_asn1::marshal(&blobs).unwrap();
+    let limits = |max_total_bytes| Limits {
+        max_total_bytes,
+        ..Limits::default()
+    };
+
+    // Each OCTET STRING is within the limit, together they aren't.
+    let err =
+        encoding_asn1::unmarshal_with_params::<Blobs>(&bytes, &limited(limits(15))).unwrap_err();
+    assert!(is_limit(err.kind()));
+    assert_eq!(err.path(), "Blobs.b");
+    assert_eq!(
+        encoding_asn1::unmarshal_with_params::<Blobs>(&bytes, &limited(limits(20)))
+            .unwrap()
+            .0,
+        blobs
+    );
+
+    // The limit holds for each value on its own, so params can be reused.
+    let params = limited(limits(20));
+    for _ in 0..2 {
+        assert_eq!(
+            encoding_asn1::unmarshal_with_params::<Blobs>(&bytes, &params)
+                .unwrap()
+                .0,
+            blobs
+        );
+    }
+    let bundle = [&bytes[..], &bytes[..]].concat();
+    for v in encoding_asn1::unmarshal::unmarshal_many_with_params::<Blobs>(&bundle, &params) {
+        assert_eq!(v.unwrap(), blobs);
+    }
+
+    // The limit holds without with_limits, for params built in place.
+    let params = FieldParameters {
+        limits: limits(12),
+        ..FieldParameters::default()
+    };
+    let err = encoding_asn1::unmarshal_with_params::<Blobs>(&bytes, &params).unwrap_err();
+    assert!(is_limit(err.kind()));
+    assert_eq!(err.path(), "Blobs.b");
+
+    // Segments reassembled under BER count too.
+    let bytes = [
+        0x24, 0x80, 0x04, 0x02, 0x01, 0x02, 0x04, 0x02, 0x03, 0x04, 0x00, 0x00,
+    ];
+    let params = limited(limits(3)).with_rules(EncodingRules::Ber);
+    assert!(encoding_asn1::unmarshal_with_params::<Vec<u8>>(&bytes, &params).is_err());
+    let params = limited(limits(4)).with_rules(EncodingRules::Ber);
+    assert!(encoding_asn1::unmarshal_with_params::<Vec<u8>>(&bytes, &params).is_ok());
+
+    // Borrowed values allocate nothing.
+    let bytes = encoding_asn1::marshal(&vec![0xaa; 100]).unwrap();
+    assert!(encoding_asn1::unmarshal_with_params::<&[u8]>(&bytes, &limited(limits(0))).is_ok());
+}
@@ -0,0 +1,130 @@
+/*
+World-Schema DEFINITIONS IMPLICIT TAGS ::=
+BEGIN
+Header ::= SEQUENCE {
+  version INTEGER,
+  id [0] INTEGER OPTIONAL
+}
+Flags ::= SET {
+  a [0] INTEGER,
+  b [1] OCTET STRING OPTIONAL
+}
+Body ::= CHOICE {
+  text [0] UTF8String,
+  numbers [1] SEQUENCE OF INTEGER
+}
+Message ::= SEQUENCE {
+  COMPONENTS OF Header,
+  flags [1] EXPLICIT Flags,
+  num [2] EXPLICIT [APPLICATION 3] IMPLICIT INTEGER,
+  body Body
+}
+END
+*/
+use encoding_asn1::{
+    Marshal, Marshaler, RawValue, SequenceOf, Unmarshal, Unmarshaler, UnmarshalerOwned,
+};
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+struct Header {
+    version: i32,
+    #[asn1(tag = 0)]
+    id: Option<i32>,
+}
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+#[asn1(set)]
+struct Flags {
+    #[asn1(tag = 0)]
+    a: i32,
+    #[asn1(tag = 1)]
+    b: Option<Vec<u8>>,
+}
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+enum Body {
+    #[asn1(tag = 0)]
+    Text(String),
+    #[asn1(tag = 1)]
+    Numbers(SequenceOf<i32>),
+}
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+struct Message {
+    #[asn1(components_of)]
+    header: Header,
+    #[asn1(explicit, tag = 1)]
+    flags: Flags,
+    #[asn1(tag(explicit, 2), tag(application, 3))]
+    num: i32,
+    body: Body,
+}
+
+// check decodes every truncation and every single byte mutation of the
+// encoding of v, none of which may panic.
+fn check<T: Marshaler + UnmarshalerOwned>(v: &T) {
+    let bytes = v.marshal().unwrap();
+    assert!(T::unmarshal(&bytes).is_ok());
+
+    for i in 0..bytes.len() {
+        let _ = T::unmarshal(&bytes[..i]);
+        let _ = T::unmarshal(&bytes[i..]);
+
+        let mut mutated = bytes.clone();
+        for b in 0..=255 {
+            mutated[i] = b;
+            let _ = T::unmarshal(&mutated);
+        }
+    }
+}
+
+#[test]
+fn it_works() {
+    check(&-2147483648);
+    check(&vec![0u8; 200]);
+    check(&"héllo".to_string());
+    check(&SequenceOf(vec![1, 256, -129]));
+    check(&RawValue {
+        class: 2,
+        tag: 40,
+        is_compound: false,
+        bytes: vec![1, 2, 3],
+        full_bytes: vec![],
+    });
+    check(&Message {
+        header: Header {
+            version: 1,
+            id: Some(2),
+        },
+        flags: Flags {
+            a: 3,
+            b: Some(vec![4]),
+        },
+        num: 5,
+        body: Body::Numbers(SequenceOf(vec![6, 7])),
+    });
+}
+
+#[test]
+fn hostile() {
+    let inputs: Vec<&[u8]> = vec![
+        &[],
+        &[0x02],
+        &[0x02, 0x00],
+        &[0x02, 0x05, 0x01, 0x02, 0x03, 0x04, 0x05],
+        &[0x02, 0x84, 0x7f, 0xff, 0xff, 0xff, 0x01],
+        &[0x30, 0x84, 0x7f, 0xff, 0xff, 0xff],
+        &[0x30, 0x80, 0x00, 0x00],
+        &[0x1f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+        &[0x30, 0x02, 0x02, 0x05],
+        &[0x30, 0x04, 0x30, 0x02, 0x30, 0x00],
+    ];
+    for bytes in inputs {
+        assert!(i32::unmarshal(bytes).is_err(), "{:02x?}", bytes);
+        assert!(Message::unmarshal(bytes).is_err(), "{:02x?}", bytes);
+        let _ = RawValue::unmarshal(bytes);
+        let _ = Vec::<u8>::unmarshal(bytes);
+        let _ = String::unmarshal(bytes);
+        let _ = SequenceOf::<Option<i32>>::unmarshal(bytes);
+    }
+}
@@ -14,7 +14,7 @@ fn it_works() {
     let s: types::OctetString = "hello".as_bytes().to_vec();
     println!("s: {:?}", s);
     assert_eq!(
-        encoding_asn1::marshal(&s),
+        encoding_asn1::marshal(&s).unwrap(),
         vec![0x04, 0x05, 0x68, 0x65, 0x6c, 0x6c, 0x6f]
     );
 }
@@ -0,0 +1,64 @@
+/*
+World-Schema DEFINITIONS IMPLICIT TAGS ::=
+BEGIN
+Request ::= SEQUENCE {
+  id INTEGER,
+  timeout [0] INTEGER OPTIONAL,
+  retries [1] INTEGER OPTIONAL,
+  num INTEGER
+}
+END
+*/
+use encoding_asn1::{Marshal, Unmarshal};
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+struct Request {
+    id: i32,
+    #[asn1(tag = 0)]
+    timeout: Option<i32>,
+    #[asn1(tag = 1)]
+    retries: Option<i32>,
+    num: i32,
+}
+
+#[test]
+fn it_works() {
+    let r = Request {
+        id: 1,
+        timeout: None,
+        retries: None,
+        num: 2,
+    };
+    let bytes = encoding_asn1::marshal(&r).unwrap();
+    assert_eq!(bytes, vec![0x30, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x02]);
+    let (n, _) = encoding_asn1::unmarshal::<Request>(&bytes).unwrap();
+    assert_eq!(n, r);
+
+    let r = Request {
+        id: 1,
+        timeout: Some(5),
+        retries: None,
+        num: 2,
+    };
+    let bytes = encoding_asn1::marshal(&r).unwrap();
+    assert_eq!(
+        bytes,
+        vec![0x30, 0x09, 0x02, 0x01, 0x01, 0x80, 0x01, 0x05, 0x02, 0x01, 0x02]
+    );
+    let (n, _) = encoding_asn1::unmarshal::<Request>(&bytes).unwrap();
+    assert_eq!(n, r);
+
+    let r = Request {
+        id: 1,
+        timeout: None,
+        retries: Some(3),
+        num: 2,
+    };
+    let bytes = encoding_asn1::marshal(&r).unwrap();
+    assert_eq!(
+        bytes,
+        vec![0x30, 0x09, 0x02, 0x01, 0x01, 0x81, 0x01, 0x03, 0x02, 0x01, 0x02]
+    );
+    let (n, _) = encoding_asn1::unmarshal::<Request>(&bytes).unwrap();
+    assert_eq!(n, r);
+}
@@ -0,0 +1,179 @@
+/*
+World-Schema DEFINITIONS IMPLICIT TAGS ::=
+BEGIN
+Message ::= SEQUENCE {
+  id INTEGER,
+  note [0] EXPLICIT UTF8String OPTIONAL,
+  flags [1] IMPLICIT INTEGER OPTIONAL,
+  body [2] EXPLICIT SEQUENCE {
+    data OCTET STRING,
+    extra ANY
+  }
+}
+END
+*/
+use encoding_asn1::common::{Identifier, Limits, Tag, TAG_INTEGER, TAG_SEQUENCE};
+use encoding_asn1::unmarshal::{Error, ErrorKind};
+use encoding_asn1::{Marshal, Reader};
+
+#[derive(Debug, PartialEq, Marshal)]
+struct Message {
+    id: i32,
+    #[asn1(explicit, tag = 0)]
+    note: Option<String>,
+    #[asn1(tag = 1)]
+    flags: Option<i32>,
+    #[asn1(explicit, tag = 2)]
+    body: Body,
+}
+
+#[derive(Debug, PartialEq, Marshal)]
+struct Body {
+    data: Vec<u8>,
+    extra: i32,
+}
+
+fn message() -> Message {
+    Message {
+        id: 7,
+        note: None,
+        flags: Some(3),
+        body: Body {
+            data: vec![0xaa, 0xbb],
+            extra: 1,
+        },
+    }
+}
+
+// read_message reads a Message by hand.
+fn read_message(bytes: &[u8]) -> Result<Message, Error> {
+    let mut r = Reader::new(bytes);
+    let message = r.read_sequence(|r| {
+        Ok(Message {
+            id: r.read()?,
+            note: r.read_optional(Tag::explicit(0))?,
+            flags: r.read_optional(Tag::implicit(1))?,
+            body: r.read_explicit(2, |r| {
+                r.read_sequence(|r| {
+                    Ok(Body {
+                        data: r.read::<&[u8]>()?.to_vec(),
+                        extra: r.read()?,
+                    })
+                })
+            })?,
+        })
+    })?;
+    r.finish()?;
+    Ok(message)
+}
+
+#[test]
+fn reader() {
+    let bytes = encoding_asn1::marshal(&message()).unwrap();
+    assert_eq!(read_message(&bytes).unwrap(), message());
+
+    let mut with_note = message();
+    with_note.note = Some("hi".to_string());
+    with_note.flags = None;
+    let bytes = encoding_asn1::marshal(&with_note).unwrap();
+    assert_eq!(read_message(&bytes).unwrap(), with_note);
+}
+
+#[test]
+fn peek() {
+    let bytes = encoding_asn1::marshal(&message()).unwrap();
+    let mut r = Reader::new(&bytes);
+    assert_eq!(
+        r.peek_tag().unwrap(),
+        Some(Identifier::universal(TAG_SEQUENCE, true))
+    );
+    let ids = r
+        .read_sequence(|r| {
+            let mut ids = vec![];
+            while let Some(id) = r.peek_tag()? {
+                ids.push(id);
+                r.read_tlv()?;
+            }
+            Ok(ids)
+        })
+        .unwrap();
+    assert_eq!(ids.len(), 3);
+    assert_eq!(ids[0], Identifier::universal(TAG_INTEGER, false));
+    assert_eq!(ids[1].tag, 1);
+    assert_eq!(ids[2].tag, 2);
+    assert!(r.is_empty());
+    assert_eq!(r.peek_tag().unwrap(), None);
+}
+
+#[test]
+fn bounds() {
+    let bytes = encoding_asn1::marshal(&message()).unwrap();
+
+    // Components left unread.
+    let err = Reader::new(&bytes)
+        .read_sequence(|r| r.read::<i32>())
+        .unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::TrailingData);
+    assert_eq!(err.offset(), Some(5));
+
+    // Bytes after the value.
+    let mut padded = bytes.clone();
+    padded.extend([0x05, 0x00]);
+    let err = read_message(&padded).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::TrailingData);
+    assert_eq!(err.offset(), Some(bytes.len()));
+
+    // Reading past the end of the SEQUENCE, though the input goes on.
+    let mut r = Reader::new(&padded);
+    let err = r
+        .read_sequence(|r| {
+            for _ in 0..4 {
+                r.read_tlv()?;
+            }
+            Ok(())
+        })
+        .unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::Truncated);
+
+    // Errors within nested readers are located in the whole input.
+    let mut bad = bytes.clone();
+    let at = bytes.len() - 3;
+    assert_eq!(bad[at], 0x02);
+    bad[at] = 0x04;
+    let err = read_message(&bad).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::UnexpectedTag { .. }));
+    assert_eq!(err.offset(), Some(at));
+
+    // The wrong EXPLICIT tag.
+    let err = Reader::new(&bytes)
+        .read_sequence(|r| {
+            r.read::<i32>()?;
+            r.read_explicit(2, |r| r.read_tlv())
+        })
+        .unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        ErrorKind::UnexpectedTag {
+            expected: Some(Identifier { tag: 2, .. }),
+            ..
+        }
+    ));
+}
+
+#[test]
+fn limits() {
+    let bytes = encoding_asn1::marshal(&message()).unwrap();
+    let limits = Limits {
+        max_depth: 2,
+        ..Limits::default()
+    };
+    let err = Reader::new(&bytes)
+        .with_limits(limits)
+        .read_sequence(|r| {
+            r.read::<i32>()?;
+            r.read_optional::<i32>(Tag::implicit(1))?;
+            r.read_explicit(2, |r| r.read_sequence(|r| r.read_tlv()))
+        })
+        .unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::LimitExceeded(_)));
+}
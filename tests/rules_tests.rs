@@ -0,0 +1,128 @@
+/*
+World-Schema DEFINITIONS IMPLICIT TAGS ::=
+BEGIN
+Flags ::= SEQUENCE {
+  enabled BOOLEAN,
+  ids SET OF INTEGER,
+  name [0] EXPLICIT UTF8String
+}
+END
+*/
+use encoding_asn1::common::{EncodingRules, FieldParameters};
+use encoding_asn1::unmarshal::ErrorKind;
+use encoding_asn1::{Marshal, SetOf, Unmarshal, Unmarshaler};
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+struct Flags {
+    enabled: bool,
+    ids: SetOf<i32>,
+    #[asn1(explicit, tag = 0)]
+    name: String,
+}
+
+fn ber() -> FieldParameters {
+    FieldParameters::default().with_rules(EncodingRules::Ber)
+}
+
+#[test]
+fn boolean() {
+    assert_eq!(
+        encoding_asn1::marshal(&true).unwrap(),
+        vec![0x01, 0x01, 0xff]
+    );
+    assert_eq!(
+        encoding_asn1::marshal(&false).unwrap(),
+        vec![0x01, 0x01, 0x00]
+    );
+    assert!(
+        encoding_asn1::unmarshal::<bool>(&[0x01, 0x01, 0xff])
+            .unwrap()
+            .0
+    );
+    assert!(
+        !encoding_asn1::unmarshal::<bool>(&[0x01, 0x01, 0x00])
+            .unwrap()
+            .0
+    );
+
+    let bytes = [0x01, 0x01, 0x01];
+    let err = encoding_asn1::unmarshal::<bool>(&bytes).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::NonCanonical("BOOLEAN"));
+    assert!(encoding_asn1::unmarshal_ber::<bool>(&bytes).unwrap().0);
+
+    // A BOOLEAN is a single byte under any rules.
+    assert!(encoding_asn1::unmarshal_ber::<bool>(&[0x01, 0x02, 0x00, 0xff]).is_err());
+}
+
+#[test]
+fn set_of() {
+    let ids = SetOf(vec![300, 2, 1]);
+    let bytes = encoding_asn1::marshal(&ids).unwrap();
+    assert_eq!(
+        bytes,
+        vec![0x31, 0x0a, 0x02, 0x01, 0x01, 0x02, 0x01, 0x02, 0x02, 0x02, 0x01, 0x2c]
+    );
+    assert_eq!(
+        encoding_asn1::unmarshal::<SetOf<i32>>(&bytes).unwrap().0,
+        SetOf(vec![1, 2, 300])
+    );
+
+    let bytes = [0x31, 0x06, 0x02, 0x01, 0x02, 0x02, 0x01, 0x01];
+    let err = encoding_asn1::unmarshal::<SetOf<i32>>(&bytes).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::NonCanonical("SET OF ordering"));
+    assert_eq!(err.offset(), Some(5));
+    assert_eq!(
+        encoding_asn1::unmarshal_ber::<SetOf<i32>>(&bytes)
+            .unwrap()
+            .0,
+        SetOf(vec![2, 1])
+    );
+}
+
+#[test]
+fn lengths() {
+    // 0x81 0x03 is a non-minimal encoding of the length 3.
+    let bytes = [0x04, 0x81, 0x03, 0x01, 0x02, 0x03];
+    let err = encoding_asn1::unmarshal::<Vec<u8>>(&bytes).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::NonMinimal("length"));
+    assert_eq!(
+        Vec::<u8>::unmarshal_with_params(&bytes, &ber()).unwrap().0,
+        vec![1, 2, 3]
+    );
+
+    // Leading zeros.
+    let bytes = [0x04, 0x82, 0x00, 0x01, 0x07];
+    assert!(encoding_asn1::unmarshal::<Vec<u8>>(&bytes).is_err());
+    assert_eq!(
+        encoding_asn1::unmarshal_ber::<Vec<u8>>(&bytes).unwrap().0,
+        vec![7]
+    );
+}
+
+#[test]
+fn derived() {
+    let flags = Flags {
+        enabled: true,
+        ids: SetOf(vec![1, 2]),
+        name: "a".to_string(),
+    };
+    let der = encoding_asn1::marshal(&flags).unwrap();
+    assert_eq!(encoding_asn1::unmarshal::<Flags>(&der).unwrap().0, flags);
+
+    // The same value with a BOOLEAN of 0x01, the SET OF out of order and a
+    // long form length under the EXPLICIT tag.
+    let bytes = vec![
+        0x30, 0x11, 0x01, 0x01, 0x01, 0x31, 0x06, 0x02, 0x01, 0x02, 0x02, 0x01, 0x01, 0xa0, 0x04,
+        0x0c, 0x81, 0x01, 0x61,
+    ];
+    let err = encoding_asn1::unmarshal::<Flags>(&bytes).unwrap_err();
+    assert_eq!(err.path(), "Flags.enabled");
+    assert_eq!(
+        encoding_asn1::unmarshal_ber::<Flags>(&bytes).unwrap().0,
+        Flags {
+            enabled: true,
+            ids: SetOf(vec![2, 1]),
+            name: "a".to_string(),
+        }
+    );
+}
@@ -0,0 +1,93 @@
+/*
+World-Schema DEFINITIONS IMPLICIT TAGS ::=
+BEGIN
+Options ::= SET {
+  name UTF8String,
+  timeout [1] INTEGER OPTIONAL,
+  retries [0] INTEGER,
+  id INTEGER
+}
+END
+*/
+use encoding_asn1::{unmarshal, Marshal, Unmarshal};
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+#[asn1(set)]
+struct Options {
+    name: String,
+    #[asn1(tag = 1)]
+    timeout: Option<i32>,
+    #[asn1(tag = 0)]
+    retries: i32,
+    id: i32,
+}
+
+#[test]
+fn it_works() {
+    let o = Options {
+        name: "a".to_string(),
+        timeout: Some(5),
+        retries: 3,
+        id: 7,
+    };
+    let bytes = encoding_asn1::marshal(&o).unwrap();
+    // Sorted by tag: INTEGER, UTF8String, [0], [1].
+    assert_eq!(
+        bytes,
+        vec![0x31, 0x0c, 0x02, 0x01, 0x07, 0x0c, 0x01, 0x61, 0x80, 0x01, 0x03, 0x81, 0x01, 0x05]
+    );
+    let (n, _) = encoding_asn1::unmarshal::<Options>(&bytes).unwrap();
+    assert_eq!(n, o);
+
+    // Under BER members may appear in any order, and OPTIONAL ones may be
+    // missing.
+    let bytes = vec![
+        0x31, 0x09, 0x80, 0x01, 0x03, 0x0c, 0x01, 0x61, 0x02, 0x01, 0x07, 0xff,
+    ];
+    let (n, rest) = encoding_asn1::unmarshal_ber::<Options>(&bytes).unwrap();
+    assert_eq!(n, Options { timeout: None, ..o });
+    assert_eq!(rest, &[0xff]);
+
+    // DER and CER only accept them in the order of their tags.
+    let err = encoding_asn1::unmarshal::<Options>(&bytes).unwrap_err();
+    assert_eq!(
+        err.kind(),
+        &unmarshal::ErrorKind::NonCanonical("SET ordering")
+    );
+    assert_eq!(err.offset(), Some(5));
+    let mut cer = vec![0x31, 0x80];
+    cer.extend_from_slice(&bytes[2..11]);
+    cer.extend_from_slice(&[0x00, 0x00]);
+    let err = encoding_asn1::unmarshal_cer::<Options>(&cer).unwrap_err();
+    assert_eq!(
+        err.kind(),
+        &unmarshal::ErrorKind::NonCanonical("SET ordering")
+    );
+}
+
+#[test]
+fn errors() {
+    // id appears twice.
+    let bytes = vec![
+        0x31, 0x0c, 0x02, 0x01, 0x07, 0x02, 0x01, 0x07, 0x0c, 0x01, 0x61, 0x80, 0x01, 0x03,
+    ];
+    match encoding_asn1::unmarshal::<Options>(&bytes).map_err(|e| e.kind().clone()) {
+        Err(unmarshal::ErrorKind::Structural(msg)) => {
+            assert_eq!(msg, "duplicate member `id` in SET")
+        }
+        r => panic!("unexpected result {:?}", r),
+    }
+
+    // retries is missing.
+    let bytes = vec![0x31, 0x06, 0x02, 0x01, 0x07, 0x0c, 0x01, 0x61];
+    match encoding_asn1::unmarshal::<Options>(&bytes).map_err(|e| e.kind().clone()) {
+        Err(unmarshal::ErrorKind::Structural(msg)) => {
+            assert_eq!(msg, "missing member `retries` in SET")
+        }
+        r => panic!("unexpected result {:?}", r),
+    }
+
+    // [2] is not a member.
+    let bytes = vec![0x31, 0x03, 0x82, 0x01, 0x00];
+    assert!(encoding_asn1::unmarshal::<Options>(&bytes).is_err());
+}
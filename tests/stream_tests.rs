@@ -0,0 +1,160 @@
+/*
+World-Schema DEFINITIONS IMPLICIT TAGS ::=
+BEGIN
+Message ::= SEQUENCE {
+  id INTEGER,
+  data [0] OCTET STRING OPTIONAL
+}
+END
+*/
+use encoding_asn1::common::EncodingRules;
+use encoding_asn1::stream::{frame_length, Decoder, Parser, Status};
+use encoding_asn1::unmarshal::ErrorKind;
+use encoding_asn1::{Marshal, Unmarshal};
+use std::io::{Cursor, Read};
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+struct Message {
+    id: i32,
+    #[asn1(tag = 0)]
+    data: Option<Vec<u8>>,
+}
+
+fn messages() -> (Vec<Message>, Vec<u8>) {
+    let messages = vec![
+        Message { id: 1, data: None },
+        Message {
+            id: 2,
+            data: Some(vec![0xaa; 300]),
+        },
+        Message { id: 3, data: None },
+    ];
+    let mut bytes = vec![];
+    for m in &messages {
+        bytes.extend(encoding_asn1::marshal(m).unwrap());
+    }
+    (messages, bytes)
+}
+
+// Trickle hands out one byte per read, as a slow socket might.
+struct Trickle<R>(R);
+
+impl<R: Read> Read for Trickle<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = buf.len().min(1);
+        self.0.read(&mut buf[..n])
+    }
+}
+
+#[test]
+fn decoder() {
+    let (messages, bytes) = messages();
+    let mut decoder = Decoder::new(Cursor::new(&bytes));
+    assert_eq!(decoder.decode::<Message>().unwrap().unwrap(), messages[0]);
+    // Nothing past the first message has been read.
+    assert_eq!(decoder.get_ref().position(), 5);
+    assert_eq!(decoder.decode::<Message>().unwrap().unwrap(), messages[1]);
+    assert_eq!(decoder.decode::<Message>().unwrap().unwrap(), messages[2]);
+    assert!(decoder.decode::<Message>().unwrap().is_none());
+
+    let mut decoder = Decoder::new(Trickle(Cursor::new(&bytes)));
+    let mut decoded = vec![];
+    while let Some(m) = decoder.decode::<Message>().unwrap() {
+        decoded.push(m);
+    }
+    assert_eq!(decoded, messages);
+
+    // A borrowed value points into the decoder's buffer.
+    let mut decoder = Decoder::new(&[0x04, 0x02, 0x01, 0x02][..]);
+    assert_eq!(decoder.decode::<&[u8]>().unwrap().unwrap(), &[0x01, 0x02]);
+}
+
+#[test]
+fn decoder_errors() {
+    let (_, bytes) = messages();
+    // The stream ends within the second message.
+    let mut decoder = Decoder::new(&bytes[..20]);
+    decoder.decode::<Message>().unwrap();
+    match decoder.decode::<Message>().unwrap_err() {
+        encoding_asn1::stream::Error::Decode(e) => {
+            assert_eq!(e.kind(), &ErrorKind::Truncated);
+            assert_eq!(e.offset(), Some(15));
+        }
+        e => panic!("{}", e),
+    }
+
+    // A header that DER doesn't allow is rejected before any contents are read.
+    let mut decoder = Decoder::new(&[0x30, 0x80, 0x02, 0x01, 0x01, 0x00, 0x00][..]);
+    assert!(decoder.read_tlv().is_err());
+    let mut decoder = Decoder::new(&[0x30, 0x80, 0x02, 0x01, 0x01, 0x00, 0x00][..])
+        .with_rules(EncodingRules::Ber);
+    assert_eq!(decoder.read_tlv().unwrap().unwrap().len(), 7);
+}
+
+#[test]
+fn parser() {
+    let (messages, bytes) = messages();
+    let mut parser = Parser::new();
+    assert_eq!(parser.parse::<Message>().unwrap(), Status::NeedMore(2));
+
+    let mut decoded = vec![];
+    let mut needed = vec![];
+    for b in &bytes {
+        parser.push(&[*b]);
+        loop {
+            match parser.parse::<Message>().unwrap() {
+                Status::Ready(m) => decoded.push(m),
+                Status::NeedMore(n) => {
+                    needed.push(n);
+                    break;
+                }
+            }
+        }
+    }
+    assert_eq!(decoded, messages);
+    assert_eq!(parser.buffered(), 0);
+    // After 30 82, the two bytes of the length; after 30 82 01, the last one;
+    // then all of the contents.
+    assert_eq!(&needed[6..9], &[2, 1, 0x133]);
+
+    let mut parser = Parser::new();
+    parser.push(&bytes[..10]);
+    assert_eq!(
+        parser.parse::<Message>().unwrap(),
+        Status::Ready(Message { id: 1, data: None })
+    );
+    parser.push(&bytes[10..]);
+    assert!(matches!(parser.next_tlv().unwrap(), Status::Ready(tlv) if tlv.len() == 311));
+}
+
+#[test]
+fn frames() {
+    let ber = EncodingRules::Ber;
+    // Long form tag numbers.
+    assert_eq!(frame_length(&[0x9f], ber).unwrap(), Status::NeedMore(2));
+    assert_eq!(
+        frame_length(&[0x9f, 0x81], ber).unwrap(),
+        Status::NeedMore(2)
+    );
+    assert_eq!(
+        frame_length(&[0x9f, 0x81, 0x00], ber).unwrap(),
+        Status::NeedMore(1)
+    );
+    assert_eq!(
+        frame_length(&[0x9f, 0x81, 0x00, 0x01, 0x00], ber).unwrap(),
+        Status::Ready(5)
+    );
+
+    // Nested indefinite lengths.
+    let bytes = [0x30, 0x80, 0x30, 0x80, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00];
+    for i in 0..bytes.len() {
+        assert!(matches!(
+            frame_length(&bytes[..i], ber).unwrap(),
+            Status::NeedMore(_)
+        ));
+    }
+    assert_eq!(frame_length(&bytes, ber).unwrap(), Status::Ready(10));
+    assert_eq!(frame_length(&bytes[..9], ber).unwrap(), Status::NeedMore(1));
+
+    assert!(frame_length(&bytes, EncodingRules::Der).is_err());
+}
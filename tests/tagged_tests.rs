@@ -0,0 +1,102 @@
+/*
+World-Schema DEFINITIONS IMPLICIT TAGS ::=
+BEGIN
+Message ::= SEQUENCE {
+  id [0] EXPLICIT INTEGER,
+  version [1] EXPLICIT INTEGER OPTIONAL,
+  flags [APPLICATION 2] INTEGER,
+  values SEQUENCE OF [3] EXPLICIT INTEGER,
+  names [4] SEQUENCE OF UTF8String
+}
+Value ::= CHOICE {
+  a [0] [APPLICATION 1] EXPLICIT INTEGER,
+  b [1] [PRIVATE 2] INTEGER
+}
+END
+*/
+use encoding_asn1::common::{CLASS_APPLICATION, CLASS_PRIVATE};
+use encoding_asn1::{
+    Application, Explicit, Implicit, Marshal, Marshaler, SequenceOf, Unmarshal, Unmarshaler,
+};
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+struct Message {
+    id: Explicit<i32, 0>,
+    version: Option<Explicit<i32, 1>>,
+    flags: Application<i32, 2>,
+    values: SequenceOf<Explicit<i32, 3>>,
+    names: Implicit<SequenceOf<String>, 4>,
+}
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+enum Value {
+    #[asn1(tag = 0)]
+    A(Explicit<i32, 1, CLASS_APPLICATION>),
+    #[asn1(tag = 1)]
+    B(Implicit<i32, 2, CLASS_PRIVATE>),
+}
+
+#[test]
+fn it_works() {
+    let mut m = Message {
+        id: Explicit(1),
+        version: Some(Explicit(2)),
+        flags: Application(3),
+        values: SequenceOf(vec![Explicit(4), Explicit(5)]),
+        names: Implicit(SequenceOf(vec!["a".to_string()])),
+    };
+    let bytes = encoding_asn1::marshal(&m).unwrap();
+    assert_eq!(
+        bytes,
+        vec![
+            0x30, 0x1e, //
+            0xa0, 0x03, 0x02, 0x01, 0x01, //
+            0xa1, 0x03, 0x02, 0x01, 0x02, //
+            0x42, 0x01, 0x03, //
+            0x30, 0x0a, 0xa3, 0x03, 0x02, 0x01, 0x04, 0xa3, 0x03, 0x02, 0x01, 0x05, //
+            0xa4, 0x03, 0x0c, 0x01, 0x61,
+        ]
+    );
+    let (n, rest) = encoding_asn1::unmarshal::<Message>(&bytes).unwrap();
+    assert_eq!(n, m);
+    assert!(rest.is_empty());
+
+    m.version = None;
+    let bytes = encoding_asn1::marshal(&m).unwrap();
+    let (n, _) = encoding_asn1::unmarshal::<Message>(&bytes).unwrap();
+    assert_eq!(n, m);
+}
+
+#[test]
+fn choice() {
+    let v = Value::A(Explicit(7));
+    let bytes = encoding_asn1::marshal(&v).unwrap();
+    assert_eq!(bytes, vec![0xa0, 0x05, 0x61, 0x03, 0x02, 0x01, 0x07]);
+    let (w, _) = encoding_asn1::unmarshal::<Value>(&bytes).unwrap();
+    assert_eq!(w, v);
+
+    let v = Value::B(Implicit(8));
+    let bytes = encoding_asn1::marshal(&v).unwrap();
+    assert_eq!(bytes, vec![0xa1, 0x03, 0xc2, 0x01, 0x08]);
+    let (w, _) = encoding_asn1::unmarshal::<Value>(&bytes).unwrap();
+    assert_eq!(w, v);
+}
+
+#[test]
+fn nested() {
+    // [1] IMPLICIT [2] EXPLICIT INTEGER
+    let v: Implicit<Explicit<i32, 2>, 1> = Implicit(Explicit(9));
+    let bytes = v.marshal().unwrap();
+    assert_eq!(bytes, vec![0xa1, 0x03, 0x02, 0x01, 0x09]);
+    let (w, _) = <Implicit<Explicit<i32, 2>, 1>>::unmarshal(&bytes).unwrap();
+    assert_eq!(*w.0, 9);
+
+    // [1] EXPLICIT [2] EXPLICIT INTEGER
+    let v: Explicit<Explicit<i32, 2>, 1> = Explicit(Explicit(9));
+    let bytes = v.marshal().unwrap();
+    assert_eq!(bytes, vec![0xa1, 0x05, 0xa2, 0x03, 0x02, 0x01, 0x09]);
+    let (w, _) = <Explicit<Explicit<i32, 2>, 1>>::unmarshal(&bytes).unwrap();
+    assert_eq!(w, v);
+
+    assert!(<Explicit<i32, 1>>::unmarshal(&[0xa2, 0x03, 0x02, 0x01, 0x09]).is_err());
+}
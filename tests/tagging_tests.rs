@@ -0,0 +1,147 @@
+/*
+World-Schema DEFINITIONS IMPLICIT TAGS ::=
+BEGIN
+Layered ::= SEQUENCE {
+  a [0] EXPLICIT [APPLICATION 3] IMPLICIT INTEGER,
+  b [1] EXPLICIT [2] EXPLICIT INTEGER,
+  c [3] IMPLICIT [4] EXPLICIT INTEGER,
+  d [5] EXPLICIT [PRIVATE 6] EXPLICIT [7] INTEGER,
+  e [8] EXPLICIT [9] EXPLICIT INTEGER OPTIONAL,
+  f [10] EXPLICIT Inner
+}
+Inner ::= SEQUENCE {
+  x INTEGER
+}
+Note ::= SEQUENCE {
+  id INTEGER,
+  note [0] EXPLICIT UTF8String OPTIONAL
+}
+END
+*/
+use encoding_asn1::common::{self, FieldParameters, Tag};
+use encoding_asn1::{Marshal, Unmarshal};
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+struct Inner {
+    x: i32,
+}
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+struct Layered {
+    #[asn1(tag(explicit, 0), tag(application, 3))]
+    a: i32,
+    #[asn1(tag(explicit, 1), tag(explicit, 2))]
+    b: i32,
+    #[asn1(tag(3), tag(explicit, 4))]
+    c: i32,
+    #[asn1(tag(explicit, 5), tag(explicit, private, 6), tag = 7)]
+    d: i32,
+    #[asn1(tag(explicit, 8), tag(explicit, 9))]
+    e: Option<i32>,
+    #[asn1(explicit, tag = 10)]
+    f: Inner,
+}
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+struct Note {
+    id: i32,
+    #[asn1(explicit, tag = 0)]
+    note: Option<String>,
+}
+
+#[test]
+fn it_works() {
+    let mut m = Layered {
+        a: 1,
+        b: 2,
+        c: 3,
+        d: 4,
+        e: Some(5),
+        f: Inner { x: 6 },
+    };
+    let bytes = encoding_asn1::marshal(&m).unwrap();
+    assert_eq!(
+        bytes,
+        vec![
+            0x30, 0x26, //
+            0xa0, 0x03, 0x43, 0x01, 0x01, //
+            0xa1, 0x05, 0xa2, 0x03, 0x02, 0x01, 0x02, //
+            0xa3, 0x03, 0x02, 0x01, 0x03, //
+            0xa5, 0x05, 0xe6, 0x03, 0x87, 0x01, 0x04, //
+            0xa8, 0x05, 0xa9, 0x03, 0x02, 0x01, 0x05, //
+            0xaa, 0x05, 0x30, 0x03, 0x02, 0x01, 0x06,
+        ]
+    );
+    let (n, rest) = encoding_asn1::unmarshal::<Layered>(&bytes).unwrap();
+    assert_eq!(n, m);
+    assert!(rest.is_empty());
+
+    m.e = None;
+    let bytes = encoding_asn1::marshal(&m).unwrap();
+    assert_eq!(bytes[1], 0x1f);
+    let (n, _) = encoding_asn1::unmarshal::<Layered>(&bytes).unwrap();
+    assert_eq!(n, m);
+}
+
+#[test]
+fn params() {
+    let params = FieldParameters {
+        tag: Some(3),
+        application: true,
+        tags: vec![Tag {
+            class: common::CLASS_CONTEXT_SPECIFIC,
+            number: 0,
+            explicit: true,
+        }],
+        ..Default::default()
+    };
+    let bytes = encoding_asn1::marshal::marshal_with_params(&7, &params).unwrap();
+    assert_eq!(bytes, vec![0xa0, 0x03, 0x43, 0x01, 0x07]);
+    let (v, _) = encoding_asn1::unmarshal::unmarshal_with_params::<i32>(&bytes, &params).unwrap();
+    assert_eq!(v, 7);
+}
+
+#[test]
+fn mismatched_tags() {
+    let params = FieldParameters {
+        tags: vec![
+            Tag {
+                class: common::CLASS_CONTEXT_SPECIFIC,
+                number: 1,
+                explicit: true,
+            },
+            Tag {
+                class: common::CLASS_CONTEXT_SPECIFIC,
+                number: 2,
+                explicit: true,
+            },
+        ],
+        ..Default::default()
+    };
+    let bad = [
+        // [1] [3] INTEGER
+        &[0xa1, 0x05, 0xa3, 0x03, 0x02, 0x01, 0x02][..],
+        // [1] [2] INTEGER with trailing data inside [1]
+        &[0xa1, 0x07, 0xa2, 0x03, 0x02, 0x01, 0x02, 0x05, 0x00][..],
+        // [1] [2] truncated
+        &[0xa1, 0x05, 0xa2, 0x03][..],
+    ];
+    for bytes in bad {
+        assert!(encoding_asn1::unmarshal::unmarshal_with_params::<i32>(bytes, &params).is_err());
+    }
+}
+
+#[test]
+fn empty_explicit() {
+    let n = Note { id: 5, note: None };
+    assert_eq!(
+        encoding_asn1::marshal(&n).unwrap(),
+        vec![0x30, 0x03, 0x02, 0x01, 0x05]
+    );
+
+    // A present [0] that holds nothing is not an absent note.
+    let bytes = [0x30, 0x05, 0x02, 0x01, 0x05, 0xa0, 0x00];
+    let err = encoding_asn1::unmarshal::<Note>(&bytes).unwrap_err();
+    assert_eq!(err.kind(), &encoding_asn1::unmarshal::ErrorKind::Truncated);
+    assert_eq!(err.path(), "Note.note");
+}
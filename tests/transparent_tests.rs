@@ -0,0 +1,50 @@
+/*
+World-Schema DEFINITIONS IMPLICIT TAGS ::=
+BEGIN
+Version ::= INTEGER
+Request ::= SEQUENCE {
+  version Version,
+  serial [0] Version
+}
+END
+*/
+use encoding_asn1::{Marshal, Unmarshal};
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+#[asn1(transparent)]
+struct Version(i32);
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+#[asn1(transparent)]
+struct SerialNumber {
+    value: i32,
+}
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+struct Request {
+    version: Version,
+    #[asn1(tag = 0)]
+    serial: SerialNumber,
+}
+
+#[test]
+fn it_works() {
+    assert_eq!(
+        encoding_asn1::marshal(&Version(2)).unwrap(),
+        vec![0x02, 0x01, 0x02]
+    );
+    let (v, _) = encoding_asn1::unmarshal::<Version>(&[0x02, 0x01, 0x02]).unwrap();
+    assert_eq!(v, Version(2));
+
+    let r = Request {
+        version: Version(2),
+        serial: SerialNumber { value: 128 },
+    };
+    let bytes = encoding_asn1::marshal(&r).unwrap();
+    assert_eq!(
+        bytes,
+        vec![0x30, 0x07, 0x02, 0x01, 0x02, 0x80, 0x02, 0x00, 0x80]
+    );
+    let (n, _) = encoding_asn1::unmarshal::<Request>(&bytes).unwrap();
+    assert_eq!(n, r);
+}
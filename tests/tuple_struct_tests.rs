@@ -0,0 +1,79 @@
+/*
+World-Schema DEFINITIONS IMPLICIT TAGS ::=
+BEGIN
+Point ::= SEQUENCE {
+  x INTEGER,
+  y INTEGER
+}
+Empty ::= SEQUENCE {}
+Nothing ::= NULL
+Message ::= SEQUENCE {
+  id INTEGER,
+  nothing [0] Nothing
+}
+END
+*/
+use encoding_asn1::{Marshal, Unmarshal};
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+struct Point(i32, i32);
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+struct Empty;
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+#[asn1(null)]
+struct Nothing;
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+struct Message {
+    id: i32,
+    #[asn1(tag = 0)]
+    nothing: Nothing,
+    #[asn1(skip)]
+    cached: Option<String>,
+}
+
+#[test]
+fn tuple_struct() {
+    let p = Point(1, 2);
+    let bytes = encoding_asn1::marshal(&p).unwrap();
+    assert_eq!(bytes, vec![0x30, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x02]);
+    let (q, rest) = encoding_asn1::unmarshal::<Point>(&bytes).unwrap();
+    assert_eq!(q, p);
+    assert!(rest.is_empty());
+}
+
+#[test]
+fn unit_struct() {
+    let bytes = encoding_asn1::marshal(&Empty).unwrap();
+    assert_eq!(bytes, vec![0x30, 0x00]);
+    let (e, _) = encoding_asn1::unmarshal::<Empty>(&bytes).unwrap();
+    assert_eq!(e, Empty);
+
+    let bytes = encoding_asn1::marshal(&Nothing).unwrap();
+    assert_eq!(bytes, vec![0x05, 0x00]);
+    let (n, _) = encoding_asn1::unmarshal::<Nothing>(&bytes).unwrap();
+    assert_eq!(n, Nothing);
+    assert!(encoding_asn1::unmarshal::<Nothing>(&[0x05, 0x01, 0x00]).is_err());
+}
+
+#[test]
+fn skipped_field() {
+    let m = Message {
+        id: 7,
+        nothing: Nothing,
+        cached: Some("ignored".to_string()),
+    };
+    let bytes = encoding_asn1::marshal(&m).unwrap();
+    assert_eq!(bytes, vec![0x30, 0x05, 0x02, 0x01, 0x07, 0x80, 0x00]);
+    let (n, _) = encoding_asn1::unmarshal::<Message>(&bytes).unwrap();
+    assert_eq!(
+        n,
+        Message {
+            id: 7,
+            nothing: Nothing,
+            cached: None,
+        }
+    );
+}
@@ -0,0 +1,19 @@
+use encoding_asn1::{Marshal, Unmarshal};
+
+#[derive(Marshal)]
+enum Implicit {
+    #[asn1(implicit, tag = 0)]
+    A(i32),
+    #[asn1(tag = 1)]
+    B(i32),
+}
+
+#[derive(Unmarshal)]
+enum Layered {
+    #[asn1(tag = 0)]
+    A(i32),
+    #[asn1(tag(explicit, 1), tag(2))]
+    B(i32),
+}
+
+fn main() {}
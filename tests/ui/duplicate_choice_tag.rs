@@ -0,0 +1,11 @@
+use encoding_asn1::Unmarshal;
+
+#[derive(Unmarshal)]
+enum Body {
+    #[asn1(tag = 0)]
+    Request(i32),
+    #[asn1(tag = 0)]
+    Response(i32),
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use encoding_asn1::Marshal;
+
+#[derive(Marshal)]
+struct Message {
+    #[asn1(range(10..=1))]
+    id: i32,
+}
+
+fn main() {}
@@ -0,0 +1,19 @@
+use encoding_asn1::Unmarshal;
+
+#[derive(Unmarshal)]
+struct Tagged {
+    #[asn1(tag = 1)]
+    a: Option<i32>,
+    #[asn1(tag = 1)]
+    b: i32,
+}
+
+#[derive(Unmarshal)]
+struct Untagged {
+    a: Option<i32>,
+    #[asn1(tag = 2)]
+    b: Option<i32>,
+    c: i32,
+}
+
+fn main() {}
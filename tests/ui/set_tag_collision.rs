@@ -0,0 +1,20 @@
+use encoding_asn1::{Marshal, Unmarshal};
+
+#[derive(Marshal, Unmarshal)]
+#[asn1(set)]
+struct Untagged {
+    a: i32,
+    b: i32,
+}
+
+#[derive(Unmarshal)]
+#[asn1(set)]
+struct Tagged {
+    #[asn1(tag = 1)]
+    a: Option<i32>,
+    b: String,
+    #[asn1(tag = 1)]
+    c: i32,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use encoding_asn1::Marshal;
+
+#[derive(Marshal)]
+union Value {
+    a: i32,
+    b: u32,
+}
+
+fn main() {}
@@ -0,0 +1,307 @@
+/*
+World-Schema DEFINITIONS IMPLICIT TAGS ::=
+BEGIN
+Extension ::= SEQUENCE {
+  id OBJECT IDENTIFIER,
+  critical BOOLEAN
+}
+Record ::= SEQUENCE {
+  serial INTEGER,
+  name [0] EXPLICIT UTF8String,
+  flags [1] IMPLICIT SEQUENCE { a INTEGER, b BIT STRING },
+  tags SET OF UTF8String,
+  extra ANY
+}
+END
+*/
+use encoding_asn1::common::{Limits, CLASS_CONTEXT_SPECIFIC, CLASS_UNIVERSAL};
+use encoding_asn1::unmarshal::ErrorKind;
+use encoding_asn1::{BitStringRef, Marshal, SetOf, Unmarshal, Value};
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+struct Record<'a> {
+    serial: i32,
+    #[asn1(explicit, tag = 0)]
+    name: String,
+    #[asn1(tag = 1)]
+    flags: Flags<'a>,
+    tags: SetOf<String>,
+    extra: Value,
+}
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+struct Flags<'a> {
+    a: i32,
+    b: BitStringRef<'a>,
+}
+
+fn record() -> Record<'static> {
+    Record {
+        serial: -2,
+        name: "hi".to_string(),
+        flags: Flags {
+            a: 300,
+            b: BitStringRef {
+                bytes: &[0xa0],
+                bit_length: 3,
+            },
+        },
+        tags: SetOf(vec!["x".to_string()]),
+        extra: Value::Null,
+    }
+}
+
+// round_trip unmarshals bytes as a Value and checks that it marshals to
+// the same bytes.
+fn round_trip(bytes: &[u8], ber: bool) -> Value {
+    let v = match ber {
+        true => encoding_asn1::unmarshal_ber::<Value>(bytes).unwrap().0,
+        false => encoding_asn1::unmarshal_exact::<Value>(bytes).unwrap(),
+    };
+    assert_eq!(encoding_asn1::marshal(&v).unwrap(), bytes, "{:?}", v);
+    v
+}
+
+#[test]
+fn it_works() {
+    let bytes = encoding_asn1::marshal(&record()).unwrap();
+    let v = round_trip(&bytes, false);
+    assert_eq!(
+        v,
+        Value::Sequence(vec![
+            Value::Integer(vec![0xfe]),
+            Value::Tagged {
+                class: CLASS_CONTEXT_SPECIFIC,
+                tag: 0,
+                values: vec![Value::Utf8String("hi".to_string())],
+            },
+            Value::Tagged {
+                class: CLASS_CONTEXT_SPECIFIC,
+                tag: 1,
+                values: vec![
+                    Value::Integer(vec![0x01, 0x2c]),
+                    Value::BitString {
+                        bytes: vec![0xa0],
+                        bit_length: 3,
+                    },
+                ],
+            },
+            Value::Set(vec![Value::Utf8String("x".to_string())]),
+            Value::Null,
+        ])
+    );
+    assert_eq!(v.values().unwrap().len(), 5);
+    assert!(v.identifier().is_compound);
+
+    // A Value can stand for any field.
+    let (r, _) = encoding_asn1::unmarshal::<Record>(&bytes).unwrap();
+    assert_eq!(r, record());
+}
+
+#[test]
+fn display() {
+    let bytes = encoding_asn1::marshal(&record()).unwrap();
+    let v = round_trip(&bytes, false);
+    assert_eq!(
+        v.to_string(),
+        "    0:d=0  hl=2 l=  26 cons: SEQUENCE
+    2:d=1  hl=2 l=   1 prim:  INTEGER           :-2
+    5:d=1  hl=2 l=   4 cons:  cont [ 0 ]
+    7:d=2  hl=2 l=   2 prim:   UTF8STRING        :hi
+   11:d=1  hl=2 l=   8 cons:  cont [ 1 ]
+   13:d=2  hl=2 l=   2 prim:   INTEGER           :300
+   17:d=2  hl=2 l=   2 prim:   BIT STRING        :[HEX DUMP]:A0
+   21:d=1  hl=2 l=   3 cons:  SET
+   23:d=2  hl=2 l=   1 prim:   UTF8STRING        :x
+   26:d=1  hl=2 l=   0 prim:  NULL
+"
+    );
+
+    let bytes = [
+        0x30, 0x80, 0x06, 0x03, 0x55, 0x1d, 0x13, 0x01, 0x01, 0xff, 0x00, 0x00,
+    ];
+    let v = round_trip(&bytes, true);
+    assert_eq!(
+        v.to_string(),
+        "    0:d=0  hl=2 l= inf cons: SEQUENCE
+    2:d=1  hl=2 l=   3 prim:  OBJECT            :2.5.29.19
+    7:d=1  hl=2 l=   1 prim:  BOOLEAN           :TRUE
+   10:d=1  hl=2 l=   0 prim:  EOC
+"
+    );
+}
+
+#[test]
+fn universal_types() {
+    let cases: &[(&[u8], Value)] = &[
+        (&[0x01, 0x01, 0x00], Value::Boolean(false)),
+        (&[0x02, 0x02, 0xff, 0x7f], Value::Integer(vec![0xff, 0x7f])),
+        (
+            &[0x03, 0x01, 0x00],
+            Value::BitString {
+                bytes: vec![],
+                bit_length: 0,
+            },
+        ),
+        (&[0x04, 0x01, 0xaa], Value::OctetString(vec![0xaa])),
+        (&[0x05, 0x00], Value::Null),
+        (
+            &[0x06, 0x03, 0x2a, 0x86, 0x48],
+            Value::ObjectIdentifier(vec![0x2a, 0x86, 0x48]),
+        ),
+        (
+            &[0x07, 0x01, 0x61],
+            Value::ObjectDescriptor("a".to_string()),
+        ),
+        (
+            &[0x28, 0x02, 0x05, 0x00],
+            Value::External(vec![Value::Null]),
+        ),
+        (&[0x09, 0x01, 0x40], Value::Real(vec![0x40])),
+        (&[0x0a, 0x01, 0x02], Value::Enumerated(vec![0x02])),
+        (&[0x2b, 0x00], Value::EmbeddedPdv(vec![])),
+        (
+            &[0x0c, 0x02, 0xc3, 0xa9],
+            Value::Utf8String("é".to_string()),
+        ),
+        (
+            &[0x0d, 0x02, 0x81, 0x00],
+            Value::RelativeOid(vec![0x81, 0x00]),
+        ),
+        (&[0x0e, 0x01, 0x31], Value::Time("1".to_string())),
+        (&[0x30, 0x00], Value::Sequence(vec![])),
+        (&[0x31, 0x00], Value::Set(vec![])),
+        (&[0x12, 0x01, 0x31], Value::NumericString("1".to_string())),
+        (&[0x13, 0x01, 0x61], Value::PrintableString("a".to_string())),
+        (&[0x14, 0x01, 0xc2], Value::T61String(vec![0xc2])),
+        (&[0x15, 0x01, 0x61], Value::VideotexString(vec![0x61])),
+        (&[0x16, 0x01, 0x61], Value::Ia5String("a".to_string())),
+        (
+            &[
+                0x17, 0x0d, 0x39, 0x31, 0x30, 0x35, 0x30, 0x36, 0x32, 0x33, 0x34, 0x35, 0x34, 0x30,
+                0x5a,
+            ],
+            Value::UtcTime("910506234540Z".to_string()),
+        ),
+        (
+            &[0x18, 0x05, 0x32, 0x30, 0x32, 0x36, 0x5a],
+            Value::GeneralizedTime("2026Z".to_string()),
+        ),
+        (&[0x19, 0x01, 0x61], Value::GraphicString(vec![0x61])),
+        (&[0x1a, 0x01, 0x61], Value::VisibleString("a".to_string())),
+        (&[0x1b, 0x01, 0x61], Value::GeneralString(vec![0x61])),
+        (
+            &[0x1c, 0x04, 0x00, 0x01, 0xf6, 0x00],
+            Value::UniversalString("😀".to_string()),
+        ),
+        (&[0x3d, 0x00], Value::CharacterString(vec![])),
+        (&[0x1e, 0x02, 0x00, 0x61], Value::BmpString("a".to_string())),
+        (&[0x1f, 0x1f, 0x01, 0x61], Value::Date("a".to_string())),
+        (&[0x1f, 0x20, 0x01, 0x61], Value::TimeOfDay("a".to_string())),
+        (&[0x1f, 0x21, 0x01, 0x61], Value::DateTime("a".to_string())),
+        (&[0x1f, 0x22, 0x01, 0x61], Value::Duration("a".to_string())),
+        (&[0x1f, 0x23, 0x01, 0x61], Value::OidIri("a".to_string())),
+        (
+            &[0x1f, 0x24, 0x01, 0x61],
+            Value::RelativeOidIri("a".to_string()),
+        ),
+    ];
+    for (bytes, want) in cases {
+        assert_eq!(&round_trip(bytes, false), want, "{:02x?}", bytes);
+        assert_eq!(&encoding_asn1::marshal(want).unwrap()[..], *bytes);
+        assert!(!want.to_string().is_empty());
+    }
+}
+
+#[test]
+fn unknown() {
+    // Values that can't be interpreted, or whose variant would encode them
+    // differently, keep their encoding.
+    let cases: &[&[u8]] = &[
+        &[0x80, 0x01, 0x05],       // [0] IMPLICIT, primitive.
+        &[0x05, 0x01, 0x00],       // NULL with contents.
+        &[0x02, 0x00],             // empty INTEGER.
+        &[0x03, 0x01, 0x01],       // BIT STRING with padding and no bits.
+        &[0x06, 0x01, 0x80],       // truncated OBJECT IDENTIFIER arc.
+        &[0x0c, 0x01, 0xff],       // invalid UTF-8.
+        &[0x1e, 0x02, 0xd8, 0x00], // surrogate in a BMPString.
+        &[0x0f, 0x00],             // reserved UNIVERSAL tag.
+        &[0x1f, 0x25, 0x00],       // UNIVERSAL tag beyond those known.
+    ];
+    for bytes in cases {
+        let v = round_trip(bytes, false);
+        assert!(matches!(v, Value::Unknown(_)), "{:?}", v);
+        assert!(v.to_string().contains("[HEX DUMP]:"));
+    }
+    assert_eq!(
+        round_trip(&[0x80, 0x01, 0x05], false).to_string(),
+        "    0:d=0  hl=2 l=   1 prim: cont [ 0 ]        :[HEX DUMP]:05\n"
+    );
+
+    // A primitive SEQUENCE is not one.
+    let v = round_trip(&[0x10, 0x00], false);
+    assert_eq!(v.identifier().class, CLASS_UNIVERSAL);
+    assert!(v.values().is_none());
+}
+
+#[test]
+fn ber() {
+    // A BOOLEAN other than 0xff and a length in more octets than needed.
+    for bytes in [&[0x01, 0x01, 0x01][..], &[0x04, 0x81, 0x01, 0xaa]] {
+        assert!(matches!(round_trip(bytes, true), Value::Unknown(_)));
+    }
+
+    // Within a value that is interpreted.
+    let bytes = [0x30, 0x06, 0x01, 0x01, 0x01, 0x02, 0x01, 0x07];
+    let v = round_trip(&bytes, true);
+    let values = v.values().unwrap();
+    assert!(matches!(values[0], Value::Unknown(_)));
+    assert_eq!(values[1], Value::Integer(vec![0x07]));
+
+    // A string in segments, with an indefinite length.
+    let bytes = [0x24, 0x80, 0x04, 0x01, 0xaa, 0x04, 0x01, 0xbb, 0x00, 0x00];
+    let v = round_trip(&bytes, true);
+    assert_eq!(
+        v,
+        Value::Indefinite(Box::new(Value::Tagged {
+            class: CLASS_UNIVERSAL,
+            tag: 4,
+            values: vec![
+                Value::OctetString(vec![0xaa]),
+                Value::OctetString(vec![0xbb])
+            ],
+        }))
+    );
+}
+
+#[test]
+fn cer() {
+    let bytes = encoding_asn1::marshal_cer(&record()).unwrap();
+    let v = encoding_asn1::unmarshal_cer::<Value>(&bytes).unwrap().0;
+    assert_eq!(encoding_asn1::marshal(&v).unwrap(), bytes);
+    assert!(matches!(v, Value::Indefinite(_)));
+    let (r, _) = encoding_asn1::unmarshal_cer::<Record>(&bytes).unwrap();
+    assert_eq!(r, record());
+}
+
+#[test]
+fn errors() {
+    // The contents of a constructed value must be encodings.
+    let err = encoding_asn1::unmarshal::<Value>(&[0x30, 0x02, 0x02, 0x05]).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::Truncated);
+    assert_eq!(err.offset(), Some(4));
+
+    let mut v = Value::Null;
+    for _ in 0..20 {
+        v = Value::Sequence(vec![v]);
+    }
+    let bytes = encoding_asn1::marshal(&v).unwrap();
+    assert_eq!(round_trip(&bytes, false), v);
+
+    let params = encoding_asn1::common::FieldParameters::default().with_limits(Limits {
+        max_depth: 10,
+        ..Limits::default()
+    });
+    let err = encoding_asn1::unmarshal_with_params::<Value>(&bytes, &params).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::LimitExceeded(_)));
+}
@@ -0,0 +1,85 @@
+use encoding_asn1::unmarshal::Error;
+use encoding_asn1::{common, Marshal, Marshaler, Unmarshal, Unmarshaler};
+use std::net::Ipv4Addr;
+
+// ipv4 encodes an IPv4 address as a 4 byte OCTET STRING.
+mod ipv4 {
+    use encoding_asn1::unmarshal::{Error, ErrorKind};
+    use encoding_asn1::{common, marshal, Marshaler, Unmarshaler};
+    use std::net::Ipv4Addr;
+
+    pub fn marshal_with_params(
+        addr: &Ipv4Addr,
+        params: &common::FieldParameters,
+    ) -> Result<Vec<u8>, marshal::Error> {
+        addr.octets().to_vec().marshal_with_params(params)
+    }
+
+    pub fn unmarshal_with_params<'a>(
+        bytes: &'a [u8],
+        params: &common::FieldParameters,
+    ) -> Result<(Ipv4Addr, &'a [u8]), Error> {
+        let (octets, bytes) = Vec::<u8>::unmarshal_with_params(bytes, params)?;
+        if octets.len() != 4 {
+            return Err(Error::new(
+                ErrorKind::InvalidValue("IPv4 address must be 4 bytes".to_string()),
+                params.offset(),
+            ));
+        }
+        Ok((
+            Ipv4Addr::new(octets[0], octets[1], octets[2], octets[3]),
+            bytes,
+        ))
+    }
+}
+
+// negated stores an INTEGER with its sign flipped.
+fn marshal_negated(
+    v: &i32,
+    params: &common::FieldParameters,
+) -> Result<Vec<u8>, encoding_asn1::marshal::Error> {
+    (-v).marshal_with_params(params)
+}
+
+fn unmarshal_negated<'a>(
+    bytes: &'a [u8],
+    params: &common::FieldParameters,
+) -> Result<(i32, &'a [u8]), Error> {
+    let (v, bytes) = i32::unmarshal_with_params(bytes, params)?;
+    Ok((-v, bytes))
+}
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+struct Host {
+    #[asn1(with = "ipv4")]
+    addr: Ipv4Addr,
+    #[asn1(encode_with = "marshal_negated", decode_with = "unmarshal_negated")]
+    port: i32,
+}
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+enum Target {
+    #[asn1(tag = 0, with = "ipv4")]
+    Address(Ipv4Addr),
+}
+
+#[test]
+fn it_works() {
+    let h = Host {
+        addr: Ipv4Addr::new(127, 0, 0, 1),
+        port: 1,
+    };
+    let bytes = encoding_asn1::marshal(&h).unwrap();
+    assert_eq!(
+        bytes,
+        vec![0x30, 0x09, 0x04, 0x04, 0x7f, 0x00, 0x00, 0x01, 0x02, 0x01, 0xff]
+    );
+    let (n, _) = encoding_asn1::unmarshal::<Host>(&bytes).unwrap();
+    assert_eq!(n, h);
+
+    let t = Target::Address(Ipv4Addr::new(10, 0, 0, 1));
+    let bytes = encoding_asn1::marshal(&t).unwrap();
+    assert_eq!(bytes, vec![0xa0, 0x06, 0x04, 0x04, 0x0a, 0x00, 0x00, 0x01]);
+    let (n, _) = encoding_asn1::unmarshal::<Target>(&bytes).unwrap();
+    assert_eq!(n, t);
+}
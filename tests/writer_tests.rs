@@ -0,0 +1,229 @@
+/*
+World-Schema DEFINITIONS IMPLICIT TAGS ::=
+BEGIN
+Message ::= SEQUENCE {
+  id INTEGER,
+  note [0] EXPLICIT UTF8String OPTIONAL,
+  flags [1] IMPLICIT INTEGER OPTIONAL,
+  body [2] EXPLICIT SEQUENCE {
+    data OCTET STRING,
+    extra INTEGER
+  }
+}
+Options ::= SET {
+  name UTF8String,
+  retries [0] INTEGER,
+  id INTEGER
+}
+Extension ::= SEQUENCE {
+  id OBJECT IDENTIFIER,
+  value OCTET STRING (CONTAINING SEQUENCE { ca BOOLEAN })
+}
+END
+*/
+use encoding_asn1::common::EncodingRules;
+use encoding_asn1::{Marshal, ObjectIdentifierRef, Reader, Unmarshal, Writer};
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+struct Message {
+    id: i32,
+    #[asn1(explicit, tag = 0)]
+    note: Option<String>,
+    #[asn1(tag = 1)]
+    flags: Option<i32>,
+    #[asn1(explicit, tag = 2)]
+    body: Body,
+}
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+struct Body {
+    data: Vec<u8>,
+    extra: i32,
+}
+
+#[derive(Debug, PartialEq, Marshal, Unmarshal)]
+#[asn1(set)]
+struct Options {
+    name: String,
+    #[asn1(tag = 0)]
+    retries: i32,
+    id: i32,
+}
+
+// write_message writes a Message by hand.
+fn write_message(w: &mut Writer, m: &Message) -> Result<(), encoding_asn1::marshal::Error> {
+    w.sequence(|w| {
+        w.write(&m.id)?;
+        if let Some(note) = &m.note {
+            w.explicit(0, |w| w.write(note))?;
+        }
+        w.implicit(1, &m.flags)?;
+        w.explicit(2, |w| {
+            w.sequence(|w| {
+                w.write(&m.body.data)?;
+                w.write(&m.body.extra)
+            })
+        })
+    })
+}
+
+fn messages() -> Vec<Message> {
+    let body = || Body {
+        data: vec![0xaa, 0xbb],
+        extra: 1,
+    };
+    vec![
+        Message {
+            id: 7,
+            note: None,
+            flags: Some(3),
+            body: body(),
+        },
+        Message {
+            id: -1,
+            note: Some("hi".to_string()),
+            flags: None,
+            body: body(),
+        },
+    ]
+}
+
+#[test]
+fn it_works() {
+    for m in messages() {
+        let mut w = Writer::new();
+        write_message(&mut w, &m).unwrap();
+        let bytes = w.finish();
+        assert_eq!(bytes, encoding_asn1::marshal(&m).unwrap());
+        assert_eq!(
+            encoding_asn1::unmarshal_exact::<Message>(&bytes).unwrap(),
+            m
+        );
+    }
+}
+
+#[test]
+fn absent_explicit() {
+    // An absent OPTIONAL value written within its EXPLICIT tag writes nothing,
+    // as the derive does.
+    for m in messages() {
+        let mut w = Writer::new();
+        w.sequence(|w| {
+            w.write(&m.id)?;
+            w.explicit(0, |w| w.write(&m.note))?;
+            w.implicit(1, &m.flags)?;
+            w.explicit(2, |w| w.write(&m.body))
+        })
+        .unwrap();
+        assert_eq!(w.finish(), encoding_asn1::marshal(&m).unwrap());
+    }
+
+    let mut w = Writer::new();
+    w.explicit(0, |w| w.write(&None::<String>)).unwrap();
+    assert!(w.is_empty());
+}
+
+#[test]
+fn writes_in_order() {
+    let mut w = Writer::new();
+    assert!(w.is_empty());
+    w.write(&1).unwrap();
+    w.write("a").unwrap();
+    w.write_raw(&[0x05, 0x00]);
+    assert!(!w.is_empty());
+    assert_eq!(
+        w.finish(),
+        vec![0x02, 0x01, 0x01, 0x0c, 0x01, 0x61, 0x05, 0x00]
+    );
+}
+
+#[test]
+fn set_is_sorted() {
+    let o = Options {
+        name: "a".to_string(),
+        retries: 3,
+        id: 7,
+    };
+    let mut w = Writer::new();
+    w.set(|w| {
+        w.write(&o.name)?;
+        w.implicit(0, &o.retries)?;
+        w.write(&o.id)
+    })
+    .unwrap();
+    assert_eq!(w.finish(), encoding_asn1::marshal(&o).unwrap());
+}
+
+#[test]
+fn containing() {
+    let mut w = Writer::new();
+    w.sequence(|w| {
+        w.write(&ObjectIdentifierRef::parse(&[0x55, 0x1d, 0x13]).unwrap())?;
+        w.octet_string_containing(|w| w.sequence(|w| w.write(&true)))
+    })
+    .unwrap();
+    let bytes = w.finish();
+    assert_eq!(
+        bytes,
+        vec![0x30, 0x0c, 0x06, 0x03, 0x55, 0x1d, 0x13, 0x04, 0x05, 0x30, 0x03, 0x01, 0x01, 0xff]
+    );
+
+    // The contents read back as an encoding of their own.
+    let mut r = Reader::new(&bytes);
+    let ca = r
+        .read_sequence(|r| {
+            r.read_tlv()?;
+            let mut inner = Reader::new(r.read::<&[u8]>()?);
+            let ca = inner.read_sequence(|r| r.read::<bool>())?;
+            inner.finish()?;
+            Ok(ca)
+        })
+        .unwrap();
+    assert!(ca);
+
+    let mut w = Writer::new();
+    w.bit_string_containing(|w| w.write(&5)).unwrap();
+    assert_eq!(w.finish(), vec![0x03, 0x04, 0x00, 0x02, 0x01, 0x05]);
+}
+
+#[test]
+fn cer() {
+    let m = &messages()[1];
+    let mut w = Writer::new().with_rules(EncodingRules::Cer);
+    write_message(&mut w, m).unwrap();
+    let bytes = w.finish();
+    assert_eq!(bytes, encoding_asn1::marshal_cer(m).unwrap());
+    assert_eq!(
+        encoding_asn1::unmarshal_cer::<Message>(&bytes).unwrap().0,
+        *m
+    );
+
+    // Long contents are segmented as any long OCTET STRING is.
+    let mut w = Writer::new().with_rules(EncodingRules::Cer);
+    w.octet_string_containing(|w| w.write(&vec![0u8; 1500]))
+        .unwrap();
+    let bytes = w.finish();
+    assert_eq!(&bytes[..2], &[0x24, 0x80]);
+    let (contents, _) = encoding_asn1::unmarshal_cer::<Vec<u8>>(&bytes).unwrap();
+    let (data, _) = encoding_asn1::unmarshal_cer::<Vec<u8>>(&contents).unwrap();
+    assert_eq!(data, vec![0u8; 1500]);
+}
+
+#[test]
+fn errors_propagate() {
+    #[derive(Debug, Marshal)]
+    struct Small {
+        #[asn1(size(1..3))]
+        data: Vec<u8>,
+    }
+
+    let mut w = Writer::new();
+    let err = w
+        .sequence(|w| w.write(&Small { data: vec![] }))
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        encoding_asn1::marshal::Error::ConstraintViolation(_)
+    ));
+    assert!(w.is_empty());
+}